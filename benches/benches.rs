@@ -54,7 +54,7 @@ mod fib {
             use self::test::Bencher;
             
             extern crate alloc;
-            use std::rc::Rc;
+            use std::sync::Arc;
             use adapton::macros::* ;
             use adapton::adapton_sigs::* ;
             //use adapton::adapton_state::* ;
@@ -106,7 +106,7 @@ mod fib {
             use self::test::Bencher;
             
             extern crate alloc;
-            use std::rc::Rc;
+            use std::sync::Arc;
             use adapton::macros::* ;
             use adapton::adapton_sigs::* ;
             use adapton::engine::* ;
@@ -202,7 +202,7 @@ mod fact {
         extern crate test;
         use self::test::Bencher;
 
-        use std::rc::Rc;
+        use std::sync::Arc;
         
         use adapton::macros::* ;
         use adapton::adapton_sigs::* ;
@@ -255,7 +255,7 @@ mod hourglass {
         extern crate test;
         use self::test::Bencher;
 
-        use std::rc::Rc;
+        use std::sync::Arc;
 
         use adapton::macros::* ;
         use adapton::adapton_sigs::* ;
@@ -326,3 +326,85 @@ mod hourglass {
         // }
     }
 }
+
+
+mod fold_up {
+    const LEAVES:usize = 1024;
+    const NAME_EVERY:usize = 8;
+    const CHANGES:usize = 32;
+    const THREADS:usize = 4;
+
+    #[cfg(test)]
+    mod sync_engine {
+        use super::{LEAVES, NAME_EVERY, CHANGES, THREADS};
+
+        extern crate test;
+        use self::test::Bencher;
+
+        use adapton::adapton_sigs::* ;
+        use adapton::collection::* ;
+        use adapton::sync_engine::* ;
+
+        type Leaf = Art<u64,Loc>;
+        type T = Tree<SyncEngine,Leaf,u32>;
+        type L = List<SyncEngine,Leaf>;
+
+        // Stands in for the work of a leaf.
+        fn spin (x:u64) -> u64 {
+            let mut y = x;
+            for _ in 0..1000 { y = test::black_box(y.wrapping_mul(31).wrapping_add(x)) }
+            y
+        }
+
+        pub fn sum (st:&mut SyncEngine, tree:T) -> u64 {
+            <T as TreeT<SyncEngine,Leaf>>::fold_up(st, tree,
+                                                   &|_| 0,
+                                                   &|st,x| { let x = st.force(&x); spin(x) },
+                                                   &|_,_,l,r| l.wrapping_add(r),
+                                                   &|_,_,_,l,r| l.wrapping_add(r))
+        }
+
+        // A wide tree over LEAVES cells, with a name every NAME_EVERY leaves.
+        pub fn wide_tree (st:&mut SyncEngine) -> (Vec<MutArt<u64,Loc>>, T) {
+            let mut cells = Vec::new();
+            let mut list = <L as ListT<SyncEngine,Leaf>>::nil(st);
+            for i in 0..LEAVES {
+                let nm = st.name_of_usize(i);
+                let cell = st.cell(nm, i as u64);
+                let leaf = st.read_only(cell.clone());
+                list = <L as ListT<SyncEngine,Leaf>>::cons(st, leaf, list);
+                if i % NAME_EVERY == 0 {
+                    let nm = st.name_of_string(format!("leaves{}", i));
+                    list = <L as ListT<SyncEngine,Leaf>>::name(st, nm, list)
+                } ;
+                cells.push(cell)
+            }
+            let tree = tree_of_list::<SyncEngine,Leaf,T,L>(st, Dir2::Left, list);
+            (cells, tree)
+        }
+
+        // Edits leaves all across the tree, so that independent subtrees
+        // need repair, and re-folds it.
+        fn repair (b:&mut Bencher, threads:usize) {
+            let mut st = SyncEngine::with_config(SyncEngineConfig{ threads:threads, ..SyncEngineConfig::default() });
+            let (cells, tree) = wide_tree(&mut st);
+            test::black_box(sum(&mut st, tree.clone()));
+            let mut round = 0;
+            b.iter(|| {
+                round += 1;
+                for i in 0..CHANGES { st.set(cells[i * LEAVES / CHANGES].clone(), round) }
+                test::black_box(sum(&mut st, tree.clone()))
+            });
+        }
+
+        #[bench]
+        fn bench_repair_sequential(b: &mut Bencher) {
+            repair(b, 0)
+        }
+
+        #[bench]
+        fn bench_repair_threads(b: &mut Bencher) {
+            repair(b, THREADS)
+        }
+    }
+}
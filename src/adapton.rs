@@ -2,6 +2,10 @@
 #![feature(associated_type_defaults)]
 #![feature(custom_derive)]
 #![feature(zero_one)]
+#![feature(rc_counts)]
+#![feature(arc_counts)]
+#![feature(map_entry_keys)]
+#![feature(core_intrinsics)]
 
 #![cfg_attr(test, feature(plugin))]
 #![cfg_attr(test, plugin(quickcheck_macros))]
@@ -20,6 +24,7 @@ pub mod macros ;
 pub mod adapton_sigs ;
 pub mod engine ;
 pub mod naive ;
+pub mod sync_engine ;
 
 pub mod simple ;
 pub mod collection ;
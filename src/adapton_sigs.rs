@@ -1,7 +1,10 @@
+use std::fmt;
 use std::fmt::{Debug};
+use std::error::Error;
 use std::hash::{Hash,Hasher};
-use std::rc::Rc;
+use std::sync::Arc;
 use std::marker::PhantomData;
+use std::collections::BTreeMap;
 use macros::{ProgPt};
 use std::ops::Sub;
 use std::num::Zero;
@@ -20,7 +23,7 @@ use std::num::Zero;
 //                  Each articulation is implemented as a node in the DCG.
 //
 //     Examples:
-//      * Pure values (see `Adapton::put`)
+//      * Pure values (see `Adapton::put` and `Adapton::put_shared`)
 //      * Mutable reference cells (see `Adapton::cell`),
 //      * Thunks (see `Adapton::thunk`),
 //                  
@@ -32,11 +35,15 @@ use std::num::Zero;
 /// The `Adapton` trait provides a language of
 /// dependence-graph-building operations based on the core calculus
 /// described in ["Incremental Computation with Names", 2015](http://arxiv.org/abs/1503.07792)
+///
+/// Values, arguments and producers are `Send` and `Sync`, and
+/// articulations hold them by `Arc`, so that an implementation may
+/// share them among threads (see `sync_engine::SyncEngine`).
 
 pub trait Adapton : Debug+PartialEq+Eq+Hash+Clone {
     // TODO-later: Report ICE: If I replace the trait combinations below with `AdaptonData`:
-    type Name : Debug+PartialEq+Eq+Hash+Clone; // Always be mindful of clones.
-    type Loc  : Debug+PartialEq+Eq+Hash+Clone; // Always be mindful of clones.
+    type Name : Debug+PartialEq+Eq+Hash+Clone+Send+Sync; // Always be mindful of clones.
+    type Loc  : Debug+PartialEq+Eq+Hash+Clone+Send+Sync; // Always be mindful of clones.
         
     fn new () -> Self ;
 
@@ -54,25 +61,84 @@ pub trait Adapton : Debug+PartialEq+Eq+Hash+Clone {
         where F:FnOnce(&mut Self) -> Res ;
     
     /// Creates immutable, eager articulation.
-    fn put<T:Eq+Debug+Clone> (self:&mut Self, T) -> Art<T,Self::Loc> ;
+    fn put<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, T) -> Art<T,Self::Loc> ;
+
+    /// Like `put`, but stores the value once, keyed by its content
+    /// hash: equal values put anywhere share one articulation, so
+    /// comparing the articulations is cheap.
+    fn put_shared<T:Eq+Hash+Debug+Clone+Send+Sync> (self:&mut Self, T) -> Art<T,Self::Loc> ;
 
     /// Creates a mutable articulation.
-    fn cell<T:Eq+Debug+Clone> (self:&mut Self, Self::Name, T) -> MutArt<T,Self::Loc> ;
+    fn cell<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, Self::Name, T) -> MutArt<T,Self::Loc> ;
+
+    /// Mutates a mutable articulation, from the outer layer (viz.,
+    /// not from within a running thunk).
+    fn set<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, MutArt<T,Self::Loc>, T) ;
 
-    /// Mutates a mutable articulation.
-    fn set<T:Eq+Debug+Clone> (self:&mut Self, MutArt<T,Self::Loc>, T) ;
+    /// Mutates a mutable articulation, from the inner layer (viz.,
+    /// from within a running thunk).  The write is an effect of that
+    /// thunk: it dirties the cell's observers, and when another write
+    /// overwrites it, the thunk is re-run on demand, to re-write it.
+    fn set_inner<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, MutArt<T,Self::Loc>, T) ;
 
     /// Creates an articulated computation.
-    fn thunk<Arg:Eq+Hash+Debug+Clone,Spurious:Clone,Res:Eq+Debug+Clone>
+    fn thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync,Spurious:Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync>
         (self:&mut Self,
          id:ArtIdChoice<Self::Name>,
          prog_pt:ProgPt,
-         fn_box:Rc<Box< Fn(&mut Self, Arg, Spurious) -> Res >>,
+         fn_box:Arc<Box< Fn(&mut Self, Arg, Spurious) -> Res + Send + Sync >>,
          arg:Arg, spurious:Spurious)
          -> Art<Res,Self::Loc> ;
 
+    /// Like `thunk`, where change propagation compares the results of
+    /// the thunk by `res_eq`, rather than by `Eq`: when a re-produced
+    /// result is equal to the prior one, its observers are not
+    /// re-executed.
+    fn thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync,Spurious:Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync>
+        (self:&mut Self,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box< Fn(&mut Self, Arg, Spurious) -> Res + Send + Sync >>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> Art<Res,Self::Loc> ;
+
     /// Demand & observe arts (all kinds): force
-    fn force<T:Eq+Debug+Clone> (self:&mut Self, &Art<T,Self::Loc>) -> T ;
+    fn force<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, &Art<T,Self::Loc>) -> T ;
+
+    ///  # Fallible variants:
+    ///
+    /// Each operation above panics on misuse (e.g., a name reused at
+    /// a different type).  These variants report such errors instead,
+    /// including errors raised by nested operations, which abort the
+    /// producers that performed them.
+
+    fn try_put_shared<T:Eq+Hash+Debug+Clone+Send+Sync> (self:&mut Self, T) -> AdaptonResult<Art<T,Self::Loc>> ;
+
+    fn try_cell<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, Self::Name, T) -> AdaptonResult<MutArt<T,Self::Loc>> ;
+
+    fn try_set<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, MutArt<T,Self::Loc>, T) -> AdaptonResult<()> ;
+
+    fn try_set_inner<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, MutArt<T,Self::Loc>, T) -> AdaptonResult<()> ;
+
+    fn try_thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync,Spurious:Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync>
+        (self:&mut Self,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box< Fn(&mut Self, Arg, Spurious) -> Res + Send + Sync >>,
+         arg:Arg, spurious:Spurious)
+         -> AdaptonResult<Art<Res,Self::Loc>> ;
+
+    fn try_thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync,Spurious:Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync>
+        (self:&mut Self,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box< Fn(&mut Self, Arg, Spurious) -> Res + Send + Sync >>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> AdaptonResult<Art<Res,Self::Loc>> ;
+
+    fn try_force<T:Eq+Debug+Clone+Send+Sync> (self:&mut Self, &Art<T,Self::Loc>) -> AdaptonResult<T> ;
   
 
     ///  # Derived fork functions:
@@ -113,14 +179,14 @@ impl<X:Debug+Hash+PartialEq+Eq+Clone> AdaptonData for X { }
 /// `T`).
 #[derive(Hash,Debug,PartialEq,Eq,Clone)]
 pub enum Art<T,Loc> {
-    Rc(Rc<T>),    // No entry in table. No dependency tracking.
-    Loc(Rc<Loc>), // Location in table.
+    Rc(Arc<T>),    // No entry in table. No dependency tracking.
+    Loc(Arc<Loc>), // Location in table.
 }
 
 /// TODO: Same scoping issue as `Art`; should be in `Adapton` trait.
 #[derive(Hash,Debug,PartialEq,Eq,Clone)]
 pub struct MutArt<T,Loc> {
-    pub loc:Rc<Loc>,
+    pub loc:Arc<Loc>,
     pub phantom: PhantomData<T>
 }
 
@@ -139,11 +205,165 @@ pub enum ArtIdChoice<Name> {
     Nominal(Name), // Identifies an Art::Loc based on a programmer-chosen name.
 }
 
+/// How change propagation decides whether the result of a thunk
+/// changed (see `Adapton::thunk_with_eq`), when `Eq` is too costly
+/// (e.g., for large results) or too strict (e.g., for results that are
+/// equal as sets, but not as sequences).
+pub enum ResEq<Res> {
+    /// Results are equal when the comparator holds.
+    Comparator(fn(&Res, &Res) -> bool),
+    /// Results are equal when their fingerprints are.
+    Fingerprint(fn(&Res) -> u64),
+}
+
+impl<Res> ResEq<Res> {
+    pub fn eq (self:&Self, res1:&Res, res2:&Res) -> bool {
+        match *self {
+            ResEq::Comparator(eq) => eq(res1, res2),
+            ResEq::Fingerprint(fingerprint) => fingerprint(res1) == fingerprint(res2),
+        }
+    }
+}
+
+impl<Res> Clone for ResEq<Res> {
+    fn clone(&self) -> Self {
+        match *self {
+            ResEq::Comparator(eq) => ResEq::Comparator(eq),
+            ResEq::Fingerprint(fingerprint) => ResEq::Fingerprint(fingerprint),
+        }
+    }
+}
+
+impl<Res> Debug for ResEq<Res> {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResEq::Comparator(_)  => write!(f, "Comparator"),
+            ResEq::Fingerprint(_) => write!(f, "Fingerprint"),
+        }
+    }
+}
+
+/// Misuses of the `Adapton` operations, reported by their fallible
+/// variants.  Locations, types and program points are rendered with
+/// `Debug`, since their representations vary by implementation.
+#[derive(Debug,Hash,PartialEq,Eq,Clone)]
+pub enum AdaptonError {
+    /// No node exists at the location.
+    DanglingLoc { loc:String },
+    /// The source node has no successor edge to the target node.
+    DanglingEdge { src:String, tgt:String },
+    /// The location holds a node of another type.
+    TypeMismatch { loc:String, expected:String, found:String },
+    /// The location holds another kind of node (e.g., a cell, not a thunk).
+    KindMismatch { loc:String, expected:&'static str, found:&'static str },
+    /// A nominal allocation dirtied an edge of the running producer.
+    DirtyNewEdge { src:String, tgt:String },
+    /// The outer layer appears as the predecessor of a node.
+    RootInPreds { loc:String },
+    /// A running thunk used `set`, rather than `set_inner`.
+    SetInInnerLayer { loc:String, thunk:String },
+    /// The outer layer used `set_inner`, rather than `set`.
+    SetInnerInOuterLayer { loc:String },
+    /// A thunk forced itself, via the listed thunks (the first and last are the same).
+    Cycle { path:Vec<String> },
+    /// A running thunk used `checkpoint` or `rollback`.
+    CheckpointInInnerLayer { thunk:String },
+    /// A rollback to a checkpoint that an earlier rollback undid.
+    StaleCheckpoint,
+    /// Two different names (or locations) have the same hash.
+    HashCollision { hash:String, first:String, second:String },
+}
+
+pub type AdaptonResult<T> = Result<T,AdaptonError>;
+
+impl fmt::Display for AdaptonError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AdaptonError::DanglingLoc{ref loc} =>
+                write!(f, "dangling pointer: {}", loc),
+            AdaptonError::DanglingEdge{ref src, ref tgt} =>
+                write!(f, "dangling edge: {} has no successor {}", src, tgt),
+            AdaptonError::TypeMismatch{ref loc, ref expected, ref found} =>
+                write!(f, "type error: {} holds a node of type {}, but is used at type {}", loc, found, expected),
+            AdaptonError::KindMismatch{ref loc, ref expected, ref found} =>
+                write!(f, "kind error: {} is a {}, but is used as a {}", loc, found, expected),
+            AdaptonError::DirtyNewEdge{ref src, ref tgt} =>
+                write!(f, "nominal side effect error: new edge {} --> {} is dirty", src, tgt),
+            AdaptonError::RootInPreds{ref loc} =>
+                write!(f, "internal error: root is a predecessor of {}", loc),
+            AdaptonError::SetInInnerLayer{ref loc, ref thunk} =>
+                write!(f, "layer error: thunk {} uses set on {}; within a thunk, use set_inner", thunk, loc),
+            AdaptonError::SetInnerInOuterLayer{ref loc} =>
+                write!(f, "layer error: the outer layer uses set_inner on {}; outside of thunks, use set", loc),
+            AdaptonError::Cycle{ref path} =>
+                write!(f, "cycle: {}", path.join(" --> ")),
+            AdaptonError::CheckpointInInnerLayer{ref thunk} =>
+                write!(f, "layer error: thunk {} uses checkpoint or rollback; use them outside of thunks", thunk),
+            AdaptonError::StaleCheckpoint =>
+                write!(f, "stale checkpoint: an earlier rollback undid it"),
+            AdaptonError::HashCollision{ref hash, ref first, ref second} =>
+                write!(f, "hash collision: {} and {} have the same hash {}", first, second, hash),
+        }
+    }
+}
+
+impl Error for AdaptonError {
+    fn description(&self) -> &str {
+        match *self {
+            AdaptonError::DanglingLoc{..}      => "dangling pointer",
+            AdaptonError::DanglingEdge{..}     => "dangling edge",
+            AdaptonError::TypeMismatch{..}     => "type error",
+            AdaptonError::KindMismatch{..}     => "kind error",
+            AdaptonError::DirtyNewEdge{..}     => "nominal side effect error",
+            AdaptonError::RootInPreds{..}      => "root in preds",
+            AdaptonError::SetInInnerLayer{..}  => "layer error",
+            AdaptonError::SetInnerInOuterLayer{..} => "layer error",
+            AdaptonError::Cycle{..}            => "cycle",
+            AdaptonError::CheckpointInInnerLayer{..} => "layer error",
+            AdaptonError::StaleCheckpoint      => "stale checkpoint",
+            AdaptonError::HashCollision{..}    => "hash collision",
+        }
+    }
+}
+
+/// Counters of the work done by an engine; see `Adapton::cnt`.
+/// Each field counts events, except for `stack_max` and `table_size`,
+/// which are measurements: subtracting two `Cnt`s keeps the
+/// measurements of the later one.
 #[derive(Debug,Hash,PartialEq,Eq,Clone)]
 pub struct Cnt {
     pub dirty : usize,
     pub eval  : usize,
     pub change_prop : usize,
+    pub collected : usize, // DCG nodes reclaimed by the engine's collector
+    pub hit     : usize, // cached results reused, after change propagation finds them clean
+    pub create  : usize, // DCG nodes created
+    pub realloc : usize, // thunks re-allocated with a new argument (or producer)
+    pub intern  : usize, // name symbols and paths allocated, rather than found already interned
+    pub edge_create : usize,
+    pub edge_remove : usize,
+    pub stack_max  : usize, // maximum depth of the stack of running thunks
+    pub table_size : usize, // number of DCG nodes
+    pub by_prog_pt : BTreeMap<&'static str, ProgPtCnt>, // per-function counts, keyed by ProgPt symbol
+}
+
+/// The counters of `Cnt` that are attributed to a single function
+/// (viz., to the thunks whose producers share a `ProgPt` symbol).
+#[derive(Debug,Hash,PartialEq,Eq,Clone)]
+pub struct ProgPtCnt {
+    pub dirty : usize,
+    pub eval  : usize,
+    pub change_prop : usize,
+    pub hit     : usize,
+    pub create  : usize,
+    pub realloc : usize,
+}
+
+impl Cnt {
+    /// The counters attributed to `prog_pt`, created on first use.
+    pub fn prog_pt<'r> (self:&'r mut Self, prog_pt:&ProgPt) -> &'r mut ProgPtCnt {
+        self.by_prog_pt.entry(prog_pt.symbol).or_insert(ProgPtCnt::zero())
+    }
 }
 
 // pub trait Sub<RHS = Self> {
@@ -154,10 +374,25 @@ pub struct Cnt {
 impl Sub for Cnt {
     type Output=Cnt;
     fn sub(self, rhs: Self) -> Self::Output {
+        let mut by_prog_pt = BTreeMap::new() ;
+        for (symbol, cnt) in self.by_prog_pt.into_iter() {
+            let cnt = match rhs.by_prog_pt.get(symbol) { None => cnt, Some(rhs) => cnt - rhs.clone() } ;
+            if cnt != ProgPtCnt::zero() { by_prog_pt.insert(symbol, cnt) ; }
+        } ;
         Cnt {
             dirty : self.dirty - rhs.dirty,
             eval  : self.eval - rhs.eval,
             change_prop : self.change_prop - rhs.change_prop,
+            collected : self.collected - rhs.collected,
+            hit     : self.hit - rhs.hit,
+            create  : self.create - rhs.create,
+            realloc : self.realloc - rhs.realloc,
+            intern  : self.intern - rhs.intern,
+            edge_create : self.edge_create - rhs.edge_create,
+            edge_remove : self.edge_remove - rhs.edge_remove,
+            stack_max  : self.stack_max,
+            table_size : self.table_size,
+            by_prog_pt : by_prog_pt,
         }
     }
 }
@@ -168,6 +403,80 @@ impl Zero for Cnt {
             dirty : 0 as usize,
             change_prop : 0 as usize,
             eval : 0 as usize,
+            collected : 0 as usize,
+            hit : 0 as usize,
+            create : 0 as usize,
+            realloc : 0 as usize,
+            intern : 0 as usize,
+            edge_create : 0 as usize,
+            edge_remove : 0 as usize,
+            stack_max : 0 as usize,
+            table_size : 0 as usize,
+            by_prog_pt : BTreeMap::new(),
+        }
+    }
+}
+
+impl Sub for ProgPtCnt {
+    type Output=ProgPtCnt;
+    fn sub(self, rhs: Self) -> Self::Output {
+        ProgPtCnt {
+            dirty : self.dirty - rhs.dirty,
+            eval  : self.eval - rhs.eval,
+            change_prop : self.change_prop - rhs.change_prop,
+            hit     : self.hit - rhs.hit,
+            create  : self.create - rhs.create,
+            realloc : self.realloc - rhs.realloc,
+        }
+    }
+}
+
+impl Zero for ProgPtCnt {
+    fn zero() -> Self {
+        ProgPtCnt {
+            dirty : 0 as usize,
+            eval : 0 as usize,
+            change_prop : 0 as usize,
+            hit : 0 as usize,
+            create : 0 as usize,
+            realloc : 0 as usize,
+        }
+    }
+}
+
+/// An estimate of the memory that an engine uses, in bytes; see
+/// `Engine::memory_report` and `AdaptonFromScratch::memory_report`.
+/// Sizes are shallow: the heap data owned by arguments and results
+/// (e.g., the contents of a `Vec` argument) is not counted.
+#[derive(Debug,PartialEq,Eq,Clone)]
+pub struct MemoryReport {
+    pub nodes   : usize, // node structures, including producers and their arguments
+    pub succs   : usize, // successor edges, and their dependency information
+    pub preds   : usize, // predecessor lists
+    pub results : usize, // cached results of thunks, and values of cells
+    pub names   : usize, // Path and NameSym chains (counting shared chains once)
+    pub keys    : usize, // Arc<Loc> table keys, and table slots
+    pub by_kind    : BTreeMap<&'static str, usize>, // nodes, succs, preds, results and keys, by node kind
+    pub by_prog_pt : BTreeMap<&'static str, usize>, // likewise, for thunks, by ProgPt symbol
+}
+
+impl MemoryReport {
+    pub fn total (self:&Self) -> usize {
+        self.nodes + self.succs + self.preds + self.results + self.names + self.keys
+    }
+}
+
+impl Zero for MemoryReport {
+    fn zero() -> Self {
+        MemoryReport {
+            nodes : 0 as usize,
+            succs : 0 as usize,
+            preds : 0 as usize,
+            results : 0 as usize,
+            names : 0 as usize,
+            keys : 0 as usize,
+            by_kind : BTreeMap::new(),
+            by_prog_pt : BTreeMap::new(),
         }
     }
 }
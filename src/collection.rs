@@ -1,7 +1,7 @@
 #![feature(associated_type_defaults)]
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::rc::Rc;
+use std::sync::Arc;
     
 use macros::* ;
 use adapton_sigs::* ;
@@ -88,7 +88,7 @@ fn has_consecutive_names<A:Adapton,X,L:ListT<A,X>> (st:&mut A, list:L::List) ->
 }
 
 pub struct Experiment ;
-impl<A:Adapton,X:Zero+Hash+Debug+PartialEq+Eq+Clone+PartialOrd> ExperimentT<A,X,Vec<X>>
+impl<A:Adapton,X:Zero+Hash+Debug+PartialEq+Eq+Clone+PartialOrd+Send+Sync> ExperimentT<A,X,Vec<X>>
     for Experiment
 {
     type ListEdit = ListZipper<A,X,List<A,X>> ;
@@ -143,7 +143,7 @@ fn eval_edit<A:Adapton,X,E:ListEdit<A,X>> (st:&mut A, edit:CursorEdit<X,E::Dir>,
     }
 }
 
-fn eval_reduce<A:Adapton,X:Zero+Hash+Eq+PartialOrd+Debug+Clone,T:TreeT<A,X>> (st:&mut A, tree:T::Tree, red:&ListReduce) -> Vec<X> {
+fn eval_reduce<A:Adapton,X:Zero+Hash+Eq+PartialOrd+Debug+Clone+Send+Sync,T:TreeT<A,X>> (st:&mut A, tree:T::Tree, red:&ListReduce) -> Vec<X> {
     match *red {
         ListReduce::Max => { let x = tree_reduce_monoid::<A,X,T,_> (st, tree, X::zero(), &|st,x,y| if x > y {x} else {y}) ; info!("max: {:?}", x); vec![ x ] },
         ListReduce::Min => { let x = tree_reduce_monoid::<A,X,T,_> (st, tree, X::zero(), &|st,x,y| if x < y {x} else {y}) ; vec![ x ] },
@@ -387,7 +387,7 @@ impl<A:Adapton
 
 
 pub trait ListT<A:Adapton,Hd> : Debug+Clone {
-    type List : Debug+Hash+PartialEq+Eq+Clone ;
+    type List : Debug+Hash+PartialEq+Eq+Clone+Send+Sync ;
     
     fn nil  (&mut A) -> Self::List ;
     fn cons (&mut A, Hd, Self::List) -> Self::List ;
@@ -395,7 +395,7 @@ pub trait ListT<A:Adapton,Hd> : Debug+Clone {
     // requisite "adaptonic" constructors: `name` and `art`:
     fn name (&mut A, A::Name, Self::List) -> Self::List ;
     fn art  (&mut A, Art<Self::List,A::Loc>) -> Self::List ;
-    fn rc   (&mut A, Rc<Self::List>) -> Self::List ;
+    fn rc   (&mut A, Arc<Self::List>) -> Self::List ;
 
     fn elim<Res,Nil,Cons,Name> (&mut A, Self::List, Nil, Cons, Name) -> Res
         where Nil:FnOnce(&mut A) -> Res
@@ -423,8 +423,8 @@ pub trait ListT<A:Adapton,Hd> : Debug+Clone {
 }
 
 pub trait TreeT<A:Adapton,Leaf> {
-    type Lev  : Debug+Hash+PartialEq+Eq+Clone ;
-    type Tree : Debug+Hash+PartialEq+Eq+Clone ;
+    type Lev  : Debug+Hash+PartialEq+Eq+Clone+Send+Sync ;
+    type Tree : Debug+Hash+PartialEq+Eq+Clone+Send+Sync ;
 
     fn lev<X:Hash>(&X) -> Self::Lev ;
     fn lev_bits () -> Self::Lev ;
@@ -441,7 +441,7 @@ pub trait TreeT<A:Adapton,Leaf> {
     // requisite "adaptonic" constructors: `name` and `art`:
     fn name (&mut A, A::Name, Self::Lev, Self::Tree, Self::Tree) -> Self::Tree ;
     fn art  (&mut A, Art<Self::Tree,A::Loc>) -> Self::Tree ;
-    fn rc   (&mut A, Rc<Self::Tree>) -> Self::Tree ;
+    fn rc   (&mut A, Arc<Self::Tree>) -> Self::Tree ;
 
     fn elim<Res,NilC,LeafC,BinC,NameC>
         (&mut A, Self::Tree, NilC, LeafC, BinC, NameC) -> Res
@@ -459,11 +459,11 @@ pub trait TreeT<A:Adapton,Leaf> {
         ,     NameC : FnOnce(&mut A, A::Name, Self::Lev, Self::Tree, Self::Tree, Arg) -> Res
         ;
     
-    fn fold_lr<Res:Hash+Debug+Eq+Clone,LeafC,BinC,NameC>
+    fn fold_lr<Res:Hash+Debug+Eq+Clone+Send+Sync,LeafC,BinC,NameC>
         (st:&mut A, tree:Self::Tree, res:Res, leaf:&LeafC, bin:&BinC, name:&NameC) -> Res
-        where LeafC:Fn(&mut A, Leaf,    Res ) -> Res
-        ,      BinC:Fn(&mut A, Self::Lev,     Res ) -> Res 
-        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res ) -> Res 
+        where LeafC:Fn(&mut A, Leaf,    Res ) -> Res + Sync
+        ,      BinC:Fn(&mut A, Self::Lev,     Res ) -> Res + Sync
+        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res ) -> Res + Sync
     {
         Self::elim_move
             (st, tree, res,
@@ -485,11 +485,11 @@ pub trait TreeT<A:Adapton,Leaf> {
              )
     }
 
-    fn fold_rl<Res:Hash+Debug+Eq+Clone,LeafC,BinC,NameC>
+    fn fold_rl<Res:Hash+Debug+Eq+Clone+Send+Sync,LeafC,BinC,NameC>
         (st:&mut A, tree:Self::Tree, res:Res, leaf:&LeafC, bin:&BinC, name:&NameC) -> Res
-        where LeafC:Fn(&mut A, Leaf,    Res ) -> Res
-        ,      BinC:Fn(&mut A, Self::Lev,     Res ) -> Res
-        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res ) -> Res
+        where LeafC:Fn(&mut A, Leaf,    Res ) -> Res + Sync
+        ,      BinC:Fn(&mut A, Self::Lev,     Res ) -> Res + Sync
+        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res ) -> Res + Sync
     {
         Self::elim_move
             (st, tree, res,
@@ -511,12 +511,12 @@ pub trait TreeT<A:Adapton,Leaf> {
              )
     }
 
-    fn fold_up<Res:Hash+Debug+Eq+Clone,NilC,LeafC,BinC,NameC>
+    fn fold_up<Res:Hash+Debug+Eq+Clone+Send+Sync,NilC,LeafC,BinC,NameC>
         (st:&mut A, tree:Self::Tree, nil:&NilC, leaf:&LeafC, bin:&BinC, name:&NameC) -> Res
-        where  NilC:Fn(&mut A) -> Res
-        ,     LeafC:Fn(&mut A, Leaf              ) -> Res
-        ,      BinC:Fn(&mut A, Self::Lev,     Res, Res ) -> Res
-        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res, Res ) -> Res
+        where  NilC:Fn(&mut A) -> Res + Sync
+        ,     LeafC:Fn(&mut A, Leaf              ) -> Res + Sync
+        ,      BinC:Fn(&mut A, Self::Lev,     Res, Res ) -> Res + Sync
+        ,     NameC:Fn(&mut A, A::Name, Self::Lev, Res, Res ) -> Res + Sync
     {
         //info!(" * ");
         Self::elim
@@ -594,9 +594,9 @@ pub fn tree_append
 //          )
 // }
 
-pub fn tree_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug,T:TreeT<A,Elm>,BinOp>
+pub fn tree_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug+Send+Sync,T:TreeT<A,Elm>,BinOp>
     (st:&mut A, tree:T::Tree, zero:Elm, binop:&BinOp) -> Elm
-    where BinOp:Fn(&mut A, Elm, Elm) -> Elm
+    where BinOp:Fn(&mut A, Elm, Elm) -> Elm + Sync
 {
     T::fold_up(st, tree,
                         &|_| zero.clone(),
@@ -606,9 +606,9 @@ pub fn tree_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug,T:TreeT<A,Elm>,BinOp
                )
 }
 
-pub fn list_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug,L:ListT<A,Elm>,BinOp,T:TreeT<A,Elm>>
+pub fn list_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug+Send+Sync,L:ListT<A,Elm>,BinOp,T:TreeT<A,Elm>>
     (st:&mut A, list:L::List, zero:Elm, binop:&BinOp) -> Elm
-    where BinOp:Fn(&mut A, Elm, Elm) -> Elm
+    where BinOp:Fn(&mut A, Elm, Elm) -> Elm + Sync
 {
     let tree = tree_of_list::<A,Elm,T,L>(st, Dir2::Left, list);
     tree_reduce_monoid::<A,Elm,T,BinOp>(st, tree, zero, binop)
@@ -629,7 +629,7 @@ pub fn list_reduce_monoid<A:Adapton,Elm:Eq+Hash+Clone+Debug,L:ListT<A,Elm>,BinOp
 
 pub fn tree_filter<A:Adapton,X:Hash+Clone,T:TreeT<A,X>,F>
     (st:&mut A, tree:T::Tree, f:&F) -> T::Tree
-    where F:Fn(&mut A, &X) -> bool
+    where F:Fn(&mut A, &X) -> bool + Sync
 {
     T::fold_up(st, tree,
                &|st| T::nil(st),
@@ -679,7 +679,7 @@ pub fn rev_list_of_vec<A:Adapton,X:Clone,L:ListT<A,X>> (st:&mut A, v:Vec<X>) ->
 pub enum List<A:Adapton,Hd> {
     Nil,
     Cons(Hd,      Box<List<A,Hd>>),
-    Rc(           Rc<List<A,Hd>>),
+    Rc(           Arc<List<A,Hd>>),
     Name(A::Name, Box<List<A,Hd>>),
     Art(          Art<List<A,Hd>, A::Loc>),
 }
@@ -688,7 +688,7 @@ pub enum List<A:Adapton,Hd> {
 //       It's not actually *contained* within the List structure; it cannot be ecountered there.
 //       It's only ever present in a negative position (as a function parameter).
 impl< A:Adapton+Debug+Hash+PartialEq+Eq+Clone
-    , Hd:Debug+Hash+PartialEq+Eq+Clone
+    , Hd:Debug+Hash+PartialEq+Eq+Clone+Send+Sync
     >
     ListT<A,Hd>
     for List<A,Hd>
@@ -698,7 +698,7 @@ impl< A:Adapton+Debug+Hash+PartialEq+Eq+Clone
     fn nil  (_:&mut A)                             -> Self::List { List::Nil }
     fn cons (_:&mut A, hd:Hd, tl:Self::List)       -> Self::List { List::Cons(hd,Box::new(tl)) }
     fn name (_:&mut A, nm:A::Name, tl:Self::List)  -> Self::List { List::Name(nm, Box::new(tl)) }
-    fn rc   (_:&mut A, rc:Rc<List<A,Hd>>)          -> Self::List { List::Rc(rc) }
+    fn rc   (_:&mut A, rc:Arc<List<A,Hd>>)         -> Self::List { List::Rc(rc) }
     fn art  (_:&mut A, art:Art<List<A,Hd>,A::Loc>) -> Self::List { List::Art(art) }
 
     fn elim<Res,Nil,Cons,Name>
@@ -744,7 +744,7 @@ pub enum Tree<A:Adapton,X,Lev> {
     Leaf(X),
     Bin(          Lev, Box<Tree<A,X,Lev>>, Box<Tree<A,X,Lev>> ),
     Name(A::Name, Lev, Box<Tree<A,X,Lev>>, Box<Tree<A,X,Lev>> ),
-    Rc(                 Rc<Tree<A,X,Lev>>),
+    Rc(                Arc<Tree<A,X,Lev>>),
     Art(               Art<Tree<A,X,Lev>, A::Loc>),
 }
 
@@ -753,7 +753,7 @@ pub enum Tree<A:Adapton,X,Lev> {
 //       It's only ever present in a negative position (as a function parameter).
 impl
     <A:Adapton+Debug+Hash+PartialEq+Eq+Clone
-    ,Leaf:     Debug+Hash+PartialEq+Eq+Clone
+    ,Leaf:     Debug+Hash+PartialEq+Eq+Clone+Send+Sync
     >
     TreeT<A,Leaf>
     for Tree<A,Leaf,u32>
@@ -776,7 +776,7 @@ impl
     fn leaf (_:&mut A, x:Leaf)                                             -> Self::Tree { Tree::Leaf(x) }
     fn bin  (_:&mut A, lev:Self::Lev, l:Self::Tree, r:Self::Tree)            -> Self::Tree { Tree::Bin(lev,Box::new(l),Box::new(r)) }
     fn name (_:&mut A, nm:A::Name, lev:Self::Lev, l:Self::Tree,r:Self::Tree) -> Self::Tree { Tree::Name(nm, lev, Box::new(l),Box::new(r)) }
-    fn rc   (_:&mut A, rc:Rc<Self::Tree>)                                  -> Self::Tree { Tree::Rc(rc) }
+    fn rc   (_:&mut A, rc:Arc<Self::Tree>)                                 -> Self::Tree { Tree::Rc(rc) }
     fn art  (_:&mut A, art:Art<Self::Tree,A::Loc>)                         -> Self::Tree { Tree::Art(art) }
 
     fn elim_move<Arg,Res,NilC,LeafC,BinC,NameC>
@@ -1,13 +1,22 @@
 use std::fmt::Debug;
+use std::io;
+use std::io::{Read,Write};
+use std::fs::File;
 use std::collections::HashMap;
-use std::mem::replace;
-use std::mem::transmute;
-use std::rc::Rc;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::mem::{replace,size_of,size_of_val};
+use std::any::{Any,TypeId};
+use std::intrinsics::type_name;
+use std::rc::{self,Rc};
+use std::sync::{Arc,Weak};
 use std::fmt;
 use std::marker::PhantomData;
 use std::fmt::{Formatter,Result};
-use std::hash::{Hash,Hasher};
+use std::hash::{Hash,Hasher,SipHasher};
 use std::num::Zero;
+use std::panic::{catch_unwind,resume_unwind,AssertUnwindSafe};
 
 use macros::*;
 use adapton_sigs::*;
@@ -31,39 +40,239 @@ macro_rules! engineMsg {
     }}
 }
 
-// Names provide a symbolic way to identify nodes.
-#[derive(Hash,PartialEq,Eq,Clone)]
+// A 128-bit hash, which identifies names and locations: among n
+// symbols, the chance of a collision is about n*n / 2^129.  (To detect
+// collisions, see `EngineConfig::check_collisions`.)
+#[derive(Hash,PartialEq,Eq,PartialOrd,Ord,Clone,Copy)]
+struct Fingerprint(u64, u64);
+impl Debug for Fingerprint {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "{:016x}{:016x}", self.0, self.1) }
+}
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "{:016x}{:016x}", self.0, self.1) }
+}
+
+// Two SipHashes of obj, under different keys.
+fn fingerprint<T:Hash>(obj:T) -> Fingerprint {
+    let mut hasher0 = SipHasher::new_with_keys(0, 0) ;
+    let mut hasher1 = SipHasher::new_with_keys(0x736f6d6570736575, 0x646f72616e646f6d) ;
+    obj.hash(&mut hasher0) ;
+    obj.hash(&mut hasher1) ;
+    Fingerprint(hasher0.finish(), hasher1.finish())
+}
+
+// Names provide a symbolic way to identify nodes.  A name is
+// identified by its fingerprint, alone.
+#[derive(Clone)]
 pub struct Name {
-    hash : u64, // hash of symbol
-    symbol : Rc<NameSym>,
+    hash : Fingerprint, // hash of symbol
+    symbol : Arc<NameSym>,
 }
 impl Debug for Name {
     fn fmt(&self, f:&mut Formatter) -> Result { self.symbol.fmt(f) }
 }
+impl PartialEq for Name {
+    fn eq(&self, other:&Name) -> bool { self.hash == other.hash }
+}
+impl Eq for Name { }
+impl Hash for Name {
+    fn hash<H>(&self, state:&mut H) where H:Hasher { self.hash.hash(state) }
+}
 
-// Each location identifies a node in the DCG.
-#[derive(Hash,PartialEq,Eq,Clone)]
+// Each location identifies a node in the DCG.  A location is
+// identified by its fingerprint, alone.
+#[derive(Clone)]
 pub struct Loc {
-    hash : u64, // hash of (path,id)
-    path : Rc<Path>,
-    id   : Rc<ArtId<Name>>,
+    hash : Fingerprint, // hash of (path,id)
+    path : Arc<Path>,
+    id   : Arc<ArtId<Name>>,
 }
 impl Debug for Loc {
     fn fmt(&self, f:&mut Formatter) -> Result { self.path.fmt(f) ; self.id.fmt(f) }
 }
+impl PartialEq for Loc {
+    fn eq(&self, other:&Loc) -> bool { self.hash == other.hash }
+}
+impl Eq for Loc { }
+impl Hash for Loc {
+    fn hash<H>(&self, state:&mut H) where H:Hasher { self.hash.hash(state) }
+}
 
 #[derive(Hash,Debug,PartialEq,Eq,Clone)]
 enum ArtId<Name> {
-    Structural(u64), // Identifies an Art::Loc based on hashing content.
+    Structural(Fingerprint), // Identifies an Art::Loc based on hashing content.
     Nominal(Name),   // Identifies an Art::Loc based on a programmer-chosen name.
 }
 
 #[derive(Debug)]
 pub struct Engine {
-    root  : Rc<Loc>,
-    table : HashMap<Rc<Loc>, Box<GraphNode>>,
+    root  : Arc<Loc>,
+    table : HashMap<Arc<Loc>, Box<GraphNode>>,
     stack : Vec<Frame>,
     cnt   : Cnt,
+    cache : Cache,
+    error : Option<AdaptonError>, // Raised by an infallible operation; recovered by `recover`.
+    observers : Vec<Box<EngineObserver>>,
+    roots : Vec<Root>, // Re-validated by `propagate`.
+    undo  : UndoLog,   // Prior states, for `rollback`.
+    fingerprints : Option<Fingerprints>, // When checking for collisions.
+    interned : Interned, // Hash-consed names and paths.
+    reasons : Option<HashMap<Loc, Reason>>, // When recording explanations.
+}
+
+// Why the node at a loc last changed: a thunk re-produced its result,
+// or a cell was set (see `Engine::explain`).
+#[derive(Debug)]
+struct Reason {
+    cause : Option<(Effect, Loc)>, // For a thunk, the changed succ that made it re-produce
+    old   : String,
+    new   : String,
+}
+
+// The shared copies of name symbols and paths, by fingerprint, so
+// that rebuilding a name or path allocates nothing.  The entries are
+// weak, and `collect` removes the ones that nothing uses any more.
+struct Interned {
+    syms  : HashMap<Fingerprint, Weak<NameSym>>,
+    paths : HashMap<Fingerprint, Weak<Path>>,
+}
+impl Debug for Interned {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "Interned{{syms:{}, paths:{}}}", self.syms.len(), self.paths.len()) }
+}
+
+// The symbols of names, and the paths and identities of locations, by
+// their fingerprints (see `EngineConfig::check_collisions`).
+#[derive(Debug)]
+struct Fingerprints {
+    names : HashMap<Fingerprint, Arc<NameSym>>,
+    paths : HashMap<Fingerprint, Arc<Path>>,
+    locs  : HashMap<Fingerprint, (Arc<Path>, Arc<ArtId<Name>>)>,
+}
+
+// A thunk that `Engine::propagate` keeps consistent; validate
+// re-validates it at its (hidden) result type.
+#[derive(Clone)]
+struct Root {
+    loc      : Arc<Loc>,
+    validate : fn(&mut Engine, &Arc<Loc>) -> AdaptonResult<()>,
+}
+impl Debug for Root {
+    fn fmt(&self, f:&mut Formatter) -> Result { self.loc.fmt(f) }
+}
+
+/// Receives the events of an `Engine` as they happen; see
+/// `Engine::add_observer`.  Thunks are identified by their `Loc` and
+/// the `ProgPt` of their producer; the outer layer, and cells, have no
+/// `ProgPt`.  Each callback ignores its event by default.
+pub trait EngineObserver : Debug {
+    /// A thunk begins to (re-)produce its result.
+    fn produce_begin (self:&mut Self, _loc:&Loc, _prog_pt:&ProgPt) { }
+    /// A thunk has (re-)produced its result.
+    fn produce_end (self:&mut Self, _loc:&Loc, _prog_pt:&ProgPt) { }
+    /// `force` finds a cached result, which change propagation
+    /// validates, without re-producing it.
+    fn cache_hit (self:&mut Self, _loc:&Loc, _prog_pt:&ProgPt) { }
+    /// Change propagation begins to validate the node at `loc`.
+    fn change_prop_begin (self:&mut Self, _loc:&Loc, _prog_pt:Option<&ProgPt>) { }
+    /// Change propagation has validated the node at `loc`.
+    fn change_prop_end (self:&mut Self, _loc:&Loc, _prog_pt:Option<&ProgPt>, _changed:bool) { }
+    /// The running thunk (or the outer layer) creates an edge.
+    fn edge_new (self:&mut Self, _src:&Loc, _src_prog_pt:Option<&ProgPt>, _effect:&Effect, _tgt:&Loc) { }
+    /// Dirtying (by `dirty_pred_observers` or `dirty_alloc`) marks an edge dirty.
+    fn edge_dirty (self:&mut Self, _src:&Loc, _src_prog_pt:Option<&ProgPt>, _effect:&Effect, _tgt:&Loc) { }
+    /// A cell is set, by `set`, `set_inner`, or `cell` with an existing name.
+    fn cell_set (self:&mut Self, _loc:&Loc, _changed:bool) { }
+    /// A thunk is allocated at an existing name; `changed` when its
+    /// argument or producer differs from the prior allocation.
+    fn realloc (self:&mut Self, _loc:&Loc, _prog_pt:&ProgPt, _changed:bool) { }
+}
+
+/// Configuration for an `Engine`; see `Engine::with_config`.
+#[derive(Debug)]
+pub struct EngineConfig {
+    pub cache_limit : Option<usize>, // Maximum number of cached CompNode results; None means no limit.
+    pub eviction    : Eviction,      // Which cached results to drop when over the limit.
+    pub check_collisions : bool,     // Keep every symbol and path, and fail when two share a fingerprint (for debugging).
+    pub record_explanations : bool,  // Record why nodes change, with their values, for `Engine::explain`.
+}
+
+impl Default for EngineConfig {
+    fn default () -> EngineConfig {
+        EngineConfig{ cache_limit:None, eviction:Eviction::LeastRecentlyUsed, check_collisions:false,
+                      record_explanations:false }
+    }
+}
+
+/// Chooses the cached results that a bounded engine evicts.
+/// Eviction drops the result of a CompNode, but keeps the node and
+/// its edges; the next `force` of the node re-produces its result.
+#[derive(Debug)]
+pub enum Eviction {
+    LeastRecentlyUsed,   // Produced, validated by change propagation, or forced.
+    LeastRecentlyForced, // Forced, by the outer layer or by another thunk.
+    Policy(Box<EvictionPolicy>),
+}
+
+/// An event in the life of a cached CompNode result.
+#[derive(Debug,PartialEq,Eq,Clone)]
+pub enum CacheEvent {
+    Produce,  // The result was (re-)produced.
+    Validate, // Change propagation found the result to be consistent.
+    Force,    // The result was demanded by `force`.
+}
+
+/// A user-supplied eviction policy.  Policies identify nodes by `Loc`
+/// values, rather than by `Arc<Loc>`s, so that they never keep nodes
+/// alive (see `Engine::collect`).
+pub trait EvictionPolicy : Debug {
+    /// Records an event on the cached result at `loc`.
+    fn touch  (self:&mut Self, loc:&Loc, event:CacheEvent) ;
+    /// Stops tracking `loc`; its result was cleared or its node freed.
+    /// Returns whether `loc` was tracked.
+    fn forget (self:&mut Self, loc:&Loc) -> bool ;
+    /// Removes and returns the next location whose result to evict.
+    fn victim (self:&mut Self) -> Option<Loc> ;
+}
+
+#[derive(Debug)]
+struct Cache {
+    limit  : Option<usize>,
+    size   : usize, // Number of CompNodes holding a result (tracked only when limit is Some).
+    policy : Box<EvictionPolicy>,
+}
+
+// Implements both built-in eviction orders: when forced_only is set,
+// only Force events refresh an entry's timestamp.
+#[derive(Debug)]
+struct Recency {
+    forced_only : bool,
+    clock       : u64,
+    stamps      : HashMap<Loc,u64>,
+    order       : BTreeMap<u64,Loc>,
+}
+
+impl EvictionPolicy for Recency {
+    fn touch (self:&mut Self, loc:&Loc, event:CacheEvent) {
+        if self.forced_only && event != CacheEvent::Force && self.stamps.contains_key(loc) { return } ;
+        self.forget(loc) ;
+        self.clock += 1 ;
+        self.stamps.insert(loc.clone(), self.clock) ;
+        self.order.insert(self.clock, loc.clone()) ;
+    }
+    fn forget (self:&mut Self, loc:&Loc) -> bool {
+        match self.stamps.remove(loc) {
+            None => false,
+            Some(stamp) => { self.order.remove(&stamp) ; true }
+        }
+    }
+    fn victim (self:&mut Self) -> Option<Loc> {
+        let oldest = match self.order.iter().next() {
+            None => return None,
+            Some((_, loc)) => loc.clone()
+        } ;
+        self.forget(&oldest) ;
+        Some(oldest)
+    }
 }
 
 impl Hash  for     Engine { fn hash<H>(&self, _state: &mut H) where H: Hasher { unimplemented!() }}
@@ -80,34 +289,119 @@ enum NameSym {
     Root, // Root identifies the outside environment of Rust code.
     String(String), // Strings encode globally-unique symbols.
     Usize(usize),   // USizes encode globally-unique symbols.
-    Pair(Rc<NameSym>,Rc<NameSym>), // A pair of unique symbols, interpeted as a symbol, is unique
-    ForkL(Rc<NameSym>), // Left projection of a unique symbol is unique
-    ForkR(Rc<NameSym>), // Right projection of a unique symbol is unique
-    //Rc(Rc<NameSym>),
+    Pair(Arc<NameSym>,Arc<NameSym>), // A pair of unique symbols, interpeted as a symbol, is unique
+    ForkL(Arc<NameSym>), // Left projection of a unique symbol is unique
+    ForkR(Arc<NameSym>), // Right projection of a unique symbol is unique
+    //Rc(Arc<NameSym>),
     //Nil,  // Nil for non-symbolic, hash-based names.
 }
 
-// Paths are built implicitly via the Adapton::ns command.
-#[derive(Hash,Debug,PartialEq,Eq,Clone)]
+// Paths are built implicitly via the Adapton::ns command.  A child
+// path keeps its fingerprint, so hashing or comparing a path does not
+// walk it.
+#[derive(Clone)]
 enum Path {
     Empty,
-    Child(Rc<Path>,Name),
+    Child(Arc<Path>,Name,Fingerprint), // Fingerprint of (parent, name)
+}
+impl Debug for Path {
+    fn fmt(&self, f:&mut Formatter) -> Result {
+        match *self {
+            Path::Empty => write!(f, "Empty"),
+            Path::Child(ref parent, ref name, _) => write!(f, "Child({:?}, {:?})", parent, name),
+        }
+    }
+}
+impl PartialEq for Path {
+    fn eq(&self, other:&Path) -> bool {
+        match (self, other) {
+            (&Path::Empty, &Path::Empty) => true,
+            (&Path::Child(_, _, ref fp1), &Path::Child(_, _, ref fp2)) => fp1 == fp2,
+            _ => false,
+        }
+    }
+}
+impl Eq for Path { }
+impl Hash for Path {
+    fn hash<H>(&self, state:&mut H) where H:Hasher {
+        match *self {
+            Path::Empty => 0u8.hash(state),
+            Path::Child(_, _, ref fp) => fp.hash(state),
+        }
+    }
+}
+
+fn path_child(parent:Arc<Path>, name:Name) -> Path {
+    let hash = fingerprint(&("child", &*parent, name.hash)) ;
+    Path::Child(parent, name, hash)
 }
 
 // The DCG structure consists of `GraphNode`s:
 trait GraphNode {
-    fn preds_alloc<'r> (self:&'r mut Self) -> Vec<Rc<Loc>> ;
-    fn preds_obs<'r>   (self:&'r mut Self) -> Vec<Rc<Loc>> ;
-    fn preds_insert<'r>(self:&'r mut Self, Effect, &Rc<Loc>) -> () ;
-    fn preds_remove<'r>(self:&'r mut Self, &Rc<Loc>) -> () ;
+    fn preds_alloc<'r> (self:&'r mut Self) -> Vec<Arc<Loc>> ;
+    fn preds_obs<'r>   (self:&'r mut Self) -> Vec<Arc<Loc>> ;
+    fn preds_write<'r> (self:&'r mut Self) -> Vec<Arc<Loc>> ;
+    fn preds<'r>       (self:&'r mut Self) -> Vec<(Effect,Arc<Loc>)> ;
+    fn preds_insert<'r>(self:&'r mut Self, Effect, &Arc<Loc>) -> () ;
+    fn preds_remove<'r>(self:&'r mut Self, &Arc<Loc>) -> () ;
     fn succs_def<'r>   (self:&'r mut Self) -> bool ;
     fn succs_mut<'r>   (self:&'r mut Self) -> &'r mut Vec<Succ> ;
+    fn res_clear<'r>   (self:&'r mut Self) -> bool ;
+    fn running_set     (self:&mut Self, running:bool) -> () ;
+    fn res_type        (self:&Self) -> NodeType ;
+    fn kind            (self:&Self) -> &'static str ;
+    fn variant         (self:&Self) -> &'static str ;
+    fn prog_pt         (self:&Self) -> Option<ProgPt> ;
+    fn memory          (self:&Self) -> NodeMemory ;
+    fn res_def         (self:&Self) -> bool ;
+    fn copy_node       (self:&Self) -> Box<GraphNode> ;
+    fn as_any<'r>      (self:&'r mut Self) -> &'r mut Any ;
+}
+
+// Each node carries the types of its result, and its producer carries
+// the types of its argument and spurious argument.  Looking up a node
+// at another type (e.g., by reusing a name for a different function)
+// is then reported as a type error, rather than being undefined.
+#[derive(Clone,Copy)]
+struct NodeType {
+    id   : TypeId,
+    name : &'static str,
+}
+impl NodeType {
+    fn of<T:'static> () -> NodeType {
+        NodeType{ id:TypeId::of::<T>(), name:unsafe { type_name::<T>() } }
+    }
+}
+impl PartialEq for NodeType {
+    fn eq(&self, other:&Self) -> bool { self.id == other.id }
+}
+impl Debug for NodeType {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "{}", self.name) }
+}
+
+fn type_mismatch(loc:&Arc<Loc>, expected:NodeType, found:NodeType) -> AdaptonError {
+    AdaptonError::TypeMismatch{ loc:format!("{:?}", loc),
+                                expected:format!("{:?}", expected),
+                                found:format!("{:?}", found) }
+}
+
+fn node_kind<Res>(node:&Node<Res>) -> &'static str {
+    match *node {
+        Node::Comp(_) => "thunk",
+        Node::Pure(_) => "pure node",
+        Node::Mut(_)  => "cell",
+        Node::Unused  => "unused node",
+    }
+}
+
+fn kind_mismatch(loc:&Arc<Loc>, expected:&'static str, found:&'static str) -> AdaptonError {
+    AdaptonError::KindMismatch{ loc:format!("{:?}", loc), expected:expected, found:found }
 }
 
 #[derive(Debug)]
 struct Frame {
-    loc   : Rc<Loc>,    // The currently-executing node
-    path  : Rc<Path>,   // The current path for creating new nodes; invariant: (prefix-of frame.loc.path frame.path)
+    loc   : Arc<Loc>,    // The currently-executing node
+    path  : Arc<Path>,   // The current path for creating new nodes; invariant: (prefix-of frame.loc.path frame.path)
     succs : Vec<Succ>,  // The currently-executing node's effects (viz., the nodes it demands)
 }
 
@@ -115,22 +409,44 @@ struct Frame {
 struct Succ {
     effect : Effect,
     dep    : Rc<Box<EngineDep>>, // Abstracted dependency information (e.g., for Observe Effect, the prior observed value)
-    loc    : Rc<Loc>, // Target of the effect, aka, the successor, by this edge
+    loc    : Arc<Loc>, // Target of the effect, aka, the successor, by this edge
     dirty  : bool,    // mutated to dirty when loc changes, or any of its successors change
 }
 
+/// The effect that an edge of the DCG records.
 #[derive(PartialEq,Eq,Debug,Clone)]
-enum Effect {
-    Observe,
-    Allocate,
+pub enum Effect {
+    Observe,  // by force
+    Allocate, // by cell and thunk
+    Write,    // by set_inner
 }
 struct EngineRes {
-    changed : bool,
+    changed    : bool,
+    reproduced : bool, // Whether the node re-produced its result (rather than validating it).
+}
+// The next step of change propagation at a node; see `change_prop`.
+enum ChangePropStep {
+    Done(EngineRes),  // The node is consistent, and its value changed, or not.
+    Succs(Vec<Succ>), // The dirty succs of the node come first; then `change_prop_end`.
 }
 // EngineDep abstracts over the value produced by a dependency, as
 // well as mechanisms to update and/or re-produce it.
 trait EngineDep : Debug {
-    fn change_prop (self:&Self, st:&mut Engine, loc:&Rc<Loc>) -> EngineRes ;
+    fn change_prop_begin (self:&Self, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<ChangePropStep> ;
+    // Called after `Succs`, once the dirty succs are propagated (up to
+    // the first whose value changed, if any).
+    fn change_prop_end (self:&Self, _st:&mut Engine, _loc:&Arc<Loc>, _succ_changed:bool) -> AdaptonResult<EngineRes> {
+        unreachable!()
+    }
+    // Called instead of `change_prop_begin` when loc is already being
+    // propagated, viz., the DCG has a cycle (see `Engine::force_fix`).
+    fn change_prop_cycle (self:&Self, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<EngineRes> {
+        match try!(self.change_prop_begin(st, loc)) {
+            ChangePropStep::Done(res) => Ok(res),
+            ChangePropStep::Succs(_) => unreachable!(),
+        }
+    }
+    fn as_any (self:&Self) -> &Any ;
 }
 
 
@@ -139,20 +455,33 @@ trait EngineDep : Debug {
 #[derive(Debug)]
 struct NoDependency;
 impl EngineDep for NoDependency {
-    fn change_prop (self:&Self, _st:&mut Engine, _loc:&Rc<Loc>) -> EngineRes { EngineRes{changed:false} }
+    fn change_prop_begin (self:&Self, _st:&mut Engine, _loc:&Arc<Loc>) -> AdaptonResult<ChangePropStep> { Ok(ChangePropStep::Done(EngineRes{changed:false, reproduced:false})) }
+    fn as_any (self:&Self) -> &Any { self }
 }
 
 #[derive(Debug)]
 struct AllocDependency<T> { val:T }
-impl<T:Debug> EngineDep for AllocDependency<T> {
-    fn change_prop (self:&Self, _st:&mut Engine, _loc:&Rc<Loc>) -> EngineRes { EngineRes{changed:true} } // TODO-Later: Make this a little better.
+impl<T:'static+Debug> EngineDep for AllocDependency<T> {
+    fn change_prop_begin (self:&Self, _st:&mut Engine, _loc:&Arc<Loc>) -> AdaptonResult<ChangePropStep> { Ok(ChangePropStep::Done(EngineRes{changed:true, reproduced:false})) } // TODO-Later: Make this a little better.
+    fn as_any (self:&Self) -> &Any { self }
 }
 
-
-trait ShapeShifter {
-    fn be_node<'r> (self:&'r mut Self) -> &'r mut Box<GraphNode> ;
+// A write by set_inner is changed when the cell no longer holds the
+// written value, viz., when another write overwrote it.
+#[derive(Debug)]
+struct WriteDependency<T> { val:T }
+impl<T:'static+Debug+Eq> EngineDep for WriteDependency<T> {
+    fn change_prop_begin (self:&Self, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<ChangePropStep> {
+        let node : &mut Node<T> = try!(res_node_of_loc(st, loc)) ;
+        match *node {
+            Node::Mut(ref nd) => Ok(ChangePropStep::Done(EngineRes{changed:nd.val != self.val, reproduced:false})),
+            _ => Ok(ChangePropStep::Done(EngineRes{changed:true, reproduced:false})),
+        }
+    }
+    fn as_any (self:&Self) -> &Any { self }
 }
 
+
 impl fmt::Debug for GraphNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(GraphNode)")
@@ -160,7 +489,6 @@ impl fmt::Debug for GraphNode {
 }
 
 // Structureful (Non-opaque) nodes:
-#[allow(dead_code)] // Pure case: not introduced currently.
 #[derive(Debug)]
 enum Node<Res> {
     Comp(CompNode<Res>),
@@ -169,20 +497,21 @@ enum Node<Res> {
     Unused,
 }
 
-// PureNode<T> for pure hash-consing of T's.
-// Location in table never changes value.
+// PureNode<T> for pure hash-consing of T's (see `Adapton::put_shared`).
+// Location in table never changes value, so it keeps no preds: no
+// observer ever needs to be dirtied.
 #[derive(Debug)]
 struct PureNode<T> {
     val : T,
 }
 
 // MutNode<T> for mutable content of type T.
-// The set operation mutates a MutNode; set may only be called by *outer* Rust environment.
-// Its notable that the CompNodes' producers do not directly change the value of MutNodes with set.
-// They may indirectly mutate these nodes by performing nominal allocation; mutation is limited to "one-shot" changes.
+// The set operation mutates a MutNode; set may only be called by *outer* Rust environment (viz., when the stack holds only the root frame).
+// CompNodes' producers do not change the value of MutNodes with set; they use set_inner, which records a Write edge to the MutNode.
+// They may also indirectly mutate these nodes by performing nominal allocation; mutation is limited to "one-shot" changes.
 #[derive(Debug)]
 struct MutNode<T> {
-    preds : Vec<(Effect,Rc<Loc>)>,
+    preds : Vec<(Effect,Arc<Loc>)>,
     val   : T,
 }
 
@@ -192,10 +521,13 @@ struct MutNode<T> {
 // values produced by the successors may change, indirectly
 // influencing how the producer produces its resulting value.
 struct CompNode<Res> {
-    preds    : Vec<(Effect, Rc<Loc>)>,
+    preds    : Vec<(Effect, Arc<Loc>)>,
     succs    : Vec<Succ>,
     producer : Box<Producer<Res>>, // Producer can be App<Arg,Res>, where type Arg is hidden.
     res      : Option<Res>,
+    running  : bool,        // The producer is running, viz., the node has a frame on the stack.
+    cycle    : Option<Res>, // The value observed by cyclic forces, in the current run (see `cycle_approx`).
+    res_eq   : Option<ResEq<Res>>, // Compares results, rather than `Eq` (see `res_changed`).
 }
 // Produce a value of type Res.
 trait Producer<Res> : Debug {
@@ -203,6 +535,10 @@ trait Producer<Res> : Debug {
     fn copy(self:&Self) -> Box<Producer<Res>>;
     fn eq(self:&Self, other:&Producer<Res>) -> bool;
     fn prog_pt<'r>(self:&'r Self) -> &'r ProgPt;
+    fn arg_type(self:&Self) -> NodeType;
+    fn spurious_type(self:&Self) -> NodeType;
+    fn as_any<'r>(self:&'r Self) -> &'r Any;
+    fn as_any_mut<'r>(self:&'r mut Self) -> &'r mut Any;
 }
 // Consume a value of type Arg.
 trait Consumer<Arg> : Debug {
@@ -213,7 +549,7 @@ trait Consumer<Arg> : Debug {
 #[derive(Clone)]
 struct App<Arg:Debug,Spurious,Res> {
     prog_pt: ProgPt,
-    fn_box:   Rc<Box<Fn(&mut Engine, Arg, Spurious) -> Res>>,
+    fn_box:   Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
     arg:      Arg,
     spurious: Spurious,
 }
@@ -236,6 +572,7 @@ impl<Arg:'static+PartialEq+Eq+Clone+Debug,Spurious:'static+Clone,Res:'static+Deb
     fn produce(self:&Self, st:&mut Engine) -> Res {
         let f = self.fn_box.clone() ;
         st.cnt.eval += 1 ;
+        st.cnt.prog_pt(&self.prog_pt).eval += 1 ;
         info!("{} producer begin: ({:?} {:?})", engineMsg!(st), &self.prog_pt, &self.arg);
         let res = f (st,self.arg.clone(),self.spurious.clone()) ;
         info!("{} producer end: ({:?} {:?}) produces {:?}", engineMsg!(st), &self.prog_pt, &self.arg, &res);
@@ -252,12 +589,16 @@ impl<Arg:'static+PartialEq+Eq+Clone+Debug,Spurious:'static+Clone,Res:'static+Deb
     fn prog_pt<'r>(self:&'r Self) -> &'r ProgPt {
         & self.prog_pt
     }
+    fn arg_type(self:&Self) -> NodeType { NodeType::of::<Arg>() }
+    fn spurious_type(self:&Self) -> NodeType { NodeType::of::<Spurious>() }
+    fn as_any<'r>(self:&'r Self) -> &'r Any { self }
+    fn as_any_mut<'r>(self:&'r mut Self) -> &'r mut Any { self }
     fn eq (&self, other:&Producer<Res>) -> bool {
         if &self.prog_pt == other.prog_pt() {
-            let other = Box::new(other) ;
-            // This is safe if the prog_pt implies unique Arg and Res types.
-            let other : &Box<App<Arg,Spurious,Res>> = unsafe { transmute::<_,_>( other ) } ;
-            self.arg == other.arg
+            match other.as_any().downcast_ref::<App<Arg,Spurious,Res>>() {
+                Some(other) => self.arg == other.arg,
+                None => false, // Same prog_pt, at different types.
+            }
         } else {
             false
         }
@@ -270,46 +611,84 @@ impl<Arg:Clone+PartialEq+Eq+Debug,Spurious,Res> Consumer<Arg> for App<Arg,Spurio
 
 // ----------- Location resolution:
 
-fn lookup_abs<'r>(st:&'r mut Engine, loc:&Rc<Loc>) -> &'r mut Box<GraphNode> {
+// Resolves loc, to read its node.  (The `GraphNode` methods take
+// `&mut self`, even to read; callers must not change the node, which
+// `lookup_abs_mut` would log for `rollback`.)
+fn lookup_abs<'r>(st:&'r mut Engine, loc:&Arc<Loc>) -> AdaptonResult<&'r mut Box<GraphNode>> {
     match st.table.get_mut( loc ) {
-        None => panic!("dangling pointer: {:?}", loc),
-        Some(node) => node.be_node() // This is a weird workaround; TODO-Later: Investigate.
+        None => Err(AdaptonError::DanglingLoc{loc:format!("{:?}", loc)}),
+        Some(node) => Ok(node)
+    }
+}
+
+// Resolves loc, to change its node.
+fn lookup_abs_mut<'r>(st:&'r mut Engine, loc:&Arc<Loc>) -> AdaptonResult<&'r mut Box<GraphNode>> {
+    match table_get_mut( st, loc ) {
+        None => Err(AdaptonError::DanglingLoc{loc:format!("{:?}", loc)}),
+        Some(node) => Ok(node)
     }
 }
 
-// This only is safe in contexts where the type of loc is known.
-// Unintended double-uses of names and hashes will generally cause uncaught type errors.
-fn res_node_of_loc<'r,Res> (st:&'r mut Engine, loc:&Rc<Loc>) -> &'r mut Box<Node<Res>> {
-    let abs_node = lookup_abs(st, loc) ;
-    unsafe { transmute::<_,_>(abs_node) }
+// Resolves loc to a node whose result has type Res, to read it (see
+// `lookup_abs`).  Unintended double-uses of names and hashes at
+// different types are caught here.
+fn res_node_of_loc<'r,Res:'static> (st:&'r mut Engine, loc:&Arc<Loc>) -> AdaptonResult<&'r mut Node<Res>> {
+    let abs_node = try!(lookup_abs(st, loc)) ;
+    res_node_of_abs(loc, abs_node)
+}
+
+// Like `res_node_of_loc`, to change the node.
+fn res_node_of_loc_mut<'r,Res:'static> (st:&'r mut Engine, loc:&Arc<Loc>) -> AdaptonResult<&'r mut Node<Res>> {
+    let abs_node = try!(lookup_abs_mut(st, loc)) ;
+    res_node_of_abs(loc, abs_node)
+}
+
+fn res_node_of_abs<'r,Res:'static> (loc:&Arc<Loc>, abs_node:&'r mut Box<GraphNode>) -> AdaptonResult<&'r mut Node<Res>> {
+    let node_type = abs_node.res_type() ;
+    match abs_node.as_any().downcast_mut::<Node<Res>>() {
+        Some(node) => Ok(node),
+        None => Err(type_mismatch(loc, NodeType::of::<Res>(), node_type)),
+    }
 }
 
 // ---------- Node implementation:
 
-impl <Res> GraphNode for Node<Res> {
-    fn preds_alloc<'r>(self:&'r mut Self) -> Vec<Rc<Loc>> {
+impl <Res:'static+Clone> GraphNode for Node<Res> {
+    fn preds_alloc<'r>(self:&'r mut Self) -> Vec<Arc<Loc>> {
         match *self { Node::Mut(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Allocate { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
                       Node::Comp(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Allocate { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
-                      Node::Pure(_) => unreachable!(),
+                      Node::Pure(_) => Vec::new(),
                       _ => unreachable!(),
         }}
                       
-    fn preds_obs<'r>(self:&'r mut Self) -> Vec<Rc<Loc>> {
+    fn preds_obs<'r>(self:&'r mut Self) -> Vec<Arc<Loc>> {
         match *self { Node::Mut(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Observe { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
                       Node::Comp(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Observe { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
-                      Node::Pure(_) => unreachable!(),
+                      Node::Pure(_) => Vec::new(),
+                      _ => unreachable!(),
+        }}
+    fn preds_write<'r>(self:&'r mut Self) -> Vec<Arc<Loc>> {
+        match *self { Node::Mut(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Write { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
+                      Node::Comp(ref mut nd) => nd.preds.iter().filter_map(|&(ref effect,ref loc)| if effect == &Effect::Write { Some(loc.clone()) } else { None } ).collect::<Vec<_>>(),
+                      Node::Pure(_) => Vec::new(),
+                      _ => unreachable!(),
+        }}
+    fn preds<'r>(self:&'r mut Self) -> Vec<(Effect,Arc<Loc>)> {
+        match *self { Node::Mut(ref mut nd) => nd.preds.clone(),
+                      Node::Comp(ref mut nd) => nd.preds.clone(),
+                      Node::Pure(_) => Vec::new(),
                       _ => unreachable!(),
         }}
-    fn preds_insert (self:&mut Self, eff:Effect, loc:&Rc<Loc>) -> () {
+    fn preds_insert (self:&mut Self, eff:Effect, loc:&Arc<Loc>) -> () {
         match *self { Node::Mut(ref mut nd) => nd.preds.push ((eff,loc.clone())),
                       Node::Comp(ref mut nd) => nd.preds.push ((eff,loc.clone())),
-                      Node::Pure(_) => unreachable!(),
+                      Node::Pure(_) => (),
                       _ => unreachable!(),
         }}
-    fn preds_remove (self:&mut Self, loc:&Rc<Loc>) -> () {
+    fn preds_remove (self:&mut Self, loc:&Arc<Loc>) -> () {
         match *self { Node::Mut(ref mut nd) => nd.preds.retain (|eff_pred|{ let (_,ref pred) = *eff_pred; *pred != *loc }),
                       Node::Comp(ref mut nd) => nd.preds.retain (|eff_pred|{ let (_, ref pred) = *eff_pred; *pred != *loc}),
-                      Node::Pure(_) => unreachable!(),
+                      Node::Pure(_) => (),
                       _ => unreachable!(),
         }}
     fn succs_def<'r>(self:&'r mut Self) -> bool {
@@ -320,20 +699,54 @@ impl <Res> GraphNode for Node<Res> {
                      _ => panic!("undefined"),
         }
     }
-}
-
-impl <Res> ShapeShifter for Box<Node<Res>> {
-    fn be_node<'r>(self:&'r mut Self) -> &'r mut Box<GraphNode> {
-        // TODO-Later: Why is this transmute needed here ??
-        unsafe { transmute::<_,_>(self) }
+    fn res_clear<'r>(self:&'r mut Self) -> bool {
+        match *self { Node::Comp(ref mut n) => n.res.take().is_some(),
+                      _ => false
+        }}
+    fn running_set(self:&mut Self, running:bool) -> () {
+        match *self { Node::Comp(ref mut n) => { n.running = running ; n.cycle = None },
+                      _ => ()
+        }}
+    fn res_type(self:&Self) -> NodeType { NodeType::of::<Res>() }
+    fn kind(self:&Self) -> &'static str { node_kind(self) }
+    fn variant(self:&Self) -> &'static str {
+        match *self { Node::Comp(_) => "Comp", Node::Pure(_) => "Pure", Node::Mut(_) => "Mut", Node::Unused => "Unused" }
     }
-}
-
-impl ShapeShifter for Box<GraphNode> {
-    fn be_node<'r>(self:&'r mut Self) -> &'r mut Box<GraphNode> {
-        // TODO-Later: Why is this transmute needed here ??
-        unsafe { transmute::<_,_>(self) }
+    fn prog_pt(self:&Self) -> Option<ProgPt> {
+        match *self { Node::Comp(ref nd) => Some(nd.producer.prog_pt().clone()), _ => None }
+    }
+    fn memory(self:&Self) -> NodeMemory {
+        let res  = size_of::<Res>() ;
+        let node = size_of::<Node<Res>>() - res ; // The result is counted separately.
+        let preds_memory = |preds:&Vec<(Effect,Arc<Loc>)>| preds.capacity() * size_of::<(Effect,Arc<Loc>)>() ;
+        match *self {
+            Node::Comp(ref nd) => NodeMemory{ node:node + size_of_val(&*nd.producer),
+                                              succs:succs_memory(&nd.succs),
+                                              preds:preds_memory(&nd.preds),
+                                              result:if nd.res.is_some() { res } else { 0 } },
+            Node::Mut(ref nd)  => NodeMemory{ node:node, succs:0, preds:preds_memory(&nd.preds), result:res },
+            Node::Pure(_)      => NodeMemory{ node:node, succs:0, preds:0, result:res },
+            Node::Unused       => NodeMemory{ node:node, succs:0, preds:0, result:0 },
+        }
+    }
+    fn res_def(self:&Self) -> bool {
+        match *self { Node::Comp(ref nd) => nd.res.is_some(), _ => false }
+    }
+    fn copy_node(self:&Self) -> Box<GraphNode> {
+        Box::new(match *self {
+            Node::Comp(ref nd) => Node::Comp(CompNode{ preds:nd.preds.clone(),
+                                                       succs:nd.succs.clone(),
+                                                       producer:nd.producer.copy(),
+                                                       res:nd.res.clone(),
+                                                       running:nd.running,
+                                                       cycle:nd.cycle.clone(),
+                                                       res_eq:nd.res_eq.clone() }),
+            Node::Pure(ref nd) => Node::Pure(PureNode{ val:nd.val.clone() }),
+            Node::Mut(ref nd)  => Node::Mut(MutNode{ preds:nd.preds.clone(), val:nd.val.clone() }),
+            Node::Unused       => Node::Unused,
+        })
     }
+    fn as_any<'r>(self:&'r mut Self) -> &'r mut Any { self }
 }
 
 
@@ -344,67 +757,157 @@ impl<Res> fmt::Debug for CompNode<Res> {
     }
 }
 
+// Reports an event to each observer.
+fn notify<F>(st:&mut Engine, event:F) where F:Fn(&mut EngineObserver) {
+    for observer in st.observers.iter_mut() { event(&mut **observer) }
+}
+
+// The ProgPt of the thunk at loc, for observers; None when there are
+// no observers, or loc is not a thunk (e.g., the root).
+fn observed_prog_pt(st:&Engine, loc:&Arc<Loc>) -> Option<ProgPt> {
+    if st.observers.is_empty() { return None } ;
+    prog_pt_of(st, loc)
+}
+
+// The ProgPt of the thunk at loc; None when loc is not a thunk.
+fn prog_pt_of(st:&Engine, loc:&Arc<Loc>) -> Option<ProgPt> {
+    match st.table.get(loc) { None => None, Some(node) => node.prog_pt() }
+}
+
+// Records a new edge from the running thunk (or the outer layer).
+fn push_succ(st:&mut Engine, succ:Succ) {
+    let src = match st.stack.last() { None => unreachable!(), Some(frame) => frame.loc.clone() } ;
+    let src_prog_pt = observed_prog_pt(st, &src) ;
+    notify(st, |o| o.edge_new(&src, src_prog_pt.as_ref(), &succ.effect, &succ.loc)) ;
+    st.cnt.edge_create += 1 ;
+    if is_outer_layer(st) { undo_root(st) } ;
+    match st.stack.last_mut() { None => unreachable!(), Some(frame) => frame.succs.push(succ) }
+}
+
 // Performs the computation at loc, produces a result of type Res.
 // Error if loc is not a Node::Comp.
-fn produce<Res:'static+Debug+PartialEq+Eq+Clone>(st:&mut Engine, loc:&Rc<Loc>) -> Res
+fn produce<Res:'static+Debug+PartialEq+Eq+Clone>(st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<Res>
 {
     info!("{} produce begin: {:?}", engineMsg!(st), &loc);
-    let succs : Vec<Succ> = {
-        let succs : Vec<Succ> = Vec::new();
-        let node : &mut Node<Res> = res_node_of_loc( st, loc ) ;
-        replace(node.succs_mut(), succs)
-    } ;
-    revoke_succs( st, loc, &succs );
-    st.stack.push ( Frame{loc:loc.clone(),
-                          path:loc.path.clone(),
-                          succs:Vec::new(), } );
-    let producer : Box<Producer<Res>> = {
-        let node : &mut Node<Res> = res_node_of_loc( st, loc ) ;
-        match *node {
-            Node::Comp(ref nd) => nd.producer.copy(),
-            _ => panic!("internal error"),
-        }
-    } ;
-    let res = producer.produce( st ) ;
-    let frame = match st.stack.pop() {
-        None => panic!("expected Some _: stack invariants are broken"),
-        Some(frame) => frame
-    } ;
-    assert!( &frame.loc == loc );
-    for succ in &frame.succs {
-        info!("{} produce: edge: {:?} --{:?}--dirty?:{:?}--> {:?}", engineMsg!(st), &loc, &succ.effect, &succ.dirty, &succ.loc);
-        if succ.dirty {
-            // This case witnesses an illegal use of nominal side effects
-            panic!("invariants broken: newly-built DCG edge should be clean, but is dirty.")
+    let res : Res ;
+    loop {
+        let succs : Vec<Succ> = {
+            let succs : Vec<Succ> = Vec::new();
+            let node : &mut Node<Res> = try!(res_node_of_loc_mut( st, loc )) ;
+            if ! node.succs_def() { return Err(kind_mismatch(loc, "thunk", node_kind(node))) } ;
+            replace(node.succs_mut(), succs)
         } ;
-        let succ_loc = lookup_abs( st, &succ.loc );
-        succ_loc.preds_insert( succ.effect.clone(), loc );
-    } ;
-    {
-        let node : &mut Node<Res> = res_node_of_loc( st, loc ) ;
-        match *node {
-            Node::Comp(ref mut node) => {
-                replace(&mut node.succs, frame.succs) ;
-                replace(&mut node.res, Some(res.clone()))
+        try!(revoke_succs( st, loc, &succs ));
+        let producer : Box<Producer<Res>> = {
+            let node : &mut Node<Res> = try!(res_node_of_loc_mut( st, loc )) ;
+            node.running_set(true) ;
+            match *node {
+                Node::Comp(ref nd) => nd.producer.copy(),
+                _ => panic!("internal error"),
+            }
+        } ;
+        notify(st, |o| o.produce_begin(loc, producer.prog_pt())) ;
+        st.stack.push ( Frame{loc:loc.clone(),
+                              path:loc.path.clone(),
+                              succs:Vec::new(), } );
+        if st.stack.len() > st.cnt.stack_max { st.cnt.stack_max = st.stack.len() } ;
+        let res_run = producer.produce( st ) ;
+        let frame = match st.stack.pop() {
+            None => panic!("expected Some _: stack invariants are broken"),
+            Some(frame) => frame
+        } ;
+        assert!( &frame.loc == loc );
+        for succ in &frame.succs {
+            info!("{} produce: edge: {:?} --{:?}--dirty?:{:?}--> {:?}", engineMsg!(st), &loc, &succ.effect, &succ.dirty, &succ.loc);
+            if succ.dirty {
+                // This case witnesses an illegal use of nominal side effects.
+                // The old succs are revoked, so the cached result is unsound:
+                abort_produce(st, loc) ;
+                return Err(AdaptonError::DirtyNewEdge{src:format!("{:?}", loc), tgt:format!("{:?}", succ.loc)})
+            } ;
+            if ! st.table.contains_key( &succ.loc ) {
+                abort_produce(st, loc) ;
+                return Err(AdaptonError::DanglingLoc{loc:format!("{:?}", succ.loc)})
+            } ;
+        } ;
+        // Past this point, every early return must also abort_produce,
+        // else loc stays running, and later forces report a cycle.
+        for succ in &frame.succs {
+            let inserted = lookup_abs_mut( st, &succ.loc ).map(|succ_node| succ_node.preds_insert( succ.effect.clone(), loc )) ;
+            match inserted { Ok(()) => (), Err(err) => { abort_produce(st, loc) ; return Err(err) } }
+        } ;
+        let installed = res_node_of_loc_mut::<Res>( st, loc ).map(|node| {
+            match *node {
+                Node::Comp(ref mut node) => {
+                    node.running = false ;
+                    replace(&mut node.succs, frame.succs) ;
+                    (replace(&mut node.res, Some(res_run.clone())).is_none(), node.cycle.take(), node.res_eq.clone())
+                },
+                _ => panic!("internal error"),
+            }
+        }) ;
+        let (was_empty, cycle, res_eq) = match installed {
+            Ok(installed) => installed,
+            Err(err) => { abort_produce(st, loc) ; return Err(err) }
+        } ;
+        cache_touch(st, loc, CacheEvent::Produce, was_empty) ;
+        notify(st, |o| o.produce_end(loc, producer.prog_pt())) ;
+        match cycle {
+            Some(ref approx) if res_changed(&res_eq, approx, &res_run) => {
+                // The run forced itself (see `Engine::force_fix`), and
+                // observed another value than it produced; run again:
+                info!("{} produce: {:?} is cyclic; {:?} is not yet a fixed point", engineMsg!(st), &loc, &res_run);
+                try!(dirty_pred_observers(st, loc))
             },
-            _ => panic!("internal error"),
+            _ => { res = res_run ; break }
         }
     } ;
-    match st.stack.last_mut() { None => (), Some(frame) => {
-        let succ = Succ{loc:loc.clone(),
-                        effect:Effect::Observe,
-                        dep:Rc::new(Box::new(ProducerDep{res:res.clone()})),
-                        dirty:false};
-        frame.succs.push(succ)
-    }};
+    let succ = Succ{loc:loc.clone(),
+                    effect:Effect::Observe,
+                    dep:Rc::new(Box::new(ProducerDep{res:res.clone()})),
+                    dirty:false};
+    push_succ(st, succ) ;
     info!("{} produce end: {:?} produces {:?}", engineMsg!(st), &loc, &res);
-    res
+    Ok(res)
+}
+
+// The value that a cyclic force of a running thunk observes: the
+// value observed by the prior cyclic forces of the current run, or
+// else the thunk's prior result, or else init.
+fn cycle_approx<Res:Clone>(nd:&mut CompNode<Res>, init:Option<Res>) -> Option<Res> {
+    if nd.cycle.is_none() { nd.cycle = nd.res.clone().or(init) } ;
+    nd.cycle.clone()
+}
+
+// Whether the result of a thunk changed from old to new, by its
+// `ResEq` (see `Adapton::thunk_with_eq`), or else by `Eq`.
+fn res_changed<Res:Eq>(res_eq:&Option<ResEq<Res>>, old:&Res, new:&Res) -> bool {
+    match *res_eq { None => old != new, Some(ref res_eq) => !res_eq.eq(old, new) }
 }
 
-fn re_produce<Res:'static+Debug+PartialEq+Eq+Clone>(dep:&ProducerDep<Res>, st:&mut Engine, loc:&Rc<Loc>) -> EngineRes {
-    let result : Res = produce( st, loc ) ;
-    let changed = result != dep.res ;
-    EngineRes{changed:changed}
+fn cycle_error(st:&Engine, loc:&Arc<Loc>) -> AdaptonError {
+    let loc_name = |loc:&Arc<Loc>| match prog_pt_of(st, loc) {
+        None => format!("{:?}", loc),
+        Some(prog_pt) => format!("{} {:?}", prog_pt.symbol, loc),
+    } ;
+    let start = match st.stack.iter().position(|frame| &frame.loc == loc) { None => 0, Some(start) => start } ;
+    let mut path : Vec<String> = st.stack[start..].iter().map(|frame| loc_name(&frame.loc)).collect() ;
+    path.push(loc_name(loc)) ;
+    AdaptonError::Cycle{path:path}
+}
+
+// Clears the result of a producer that did not finish.
+fn abort_produce(st:&mut Engine, loc:&Arc<Loc>) {
+    let cleared = match table_get_mut(st, loc) { None => false, Some(node) => { node.running_set(false) ; node.res_clear() } } ;
+    if cleared { cache_forget(st, loc) }
+}
+
+fn re_produce<Res:'static+Debug+PartialEq+Eq+Clone>(dep:&ProducerDep<Res>, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<EngineRes> {
+    let result : Res = try!(produce( st, loc )) ;
+    if st.reasons.is_some() { record_reason(st, loc, format!("{:?}", dep.res), format!("{:?}", result)) } ;
+    let res_eq = match *try!(res_node_of_loc::<Res>( st, loc )) { Node::Comp(ref nd) => nd.res_eq.clone(), _ => None } ;
+    let changed = res_changed(&res_eq, &dep.res, &result) ;
+    Ok(EngineRes{changed:changed, reproduced:true})
 }
 
 
@@ -416,470 +919,2525 @@ struct ProducerDep<T> { res:T }
 impl <Res:'static+Sized+Debug+PartialEq+Eq+Clone>
     EngineDep for ProducerDep<Res>
 {
-    fn change_prop(self:&Self, st:&mut Engine, loc:&Rc<Loc>) -> EngineRes {
+    fn change_prop_begin(self:&Self, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<ChangePropStep> {
         let stackLen = st.stack.len() ;
         info!("{} change_prop begin: {:?}", engineMsg!(st), loc);
+        let prog_pt = observed_prog_pt(st, loc) ;
+        notify(st, |o| o.change_prop_begin(loc, prog_pt.as_ref())) ;
         st.cnt.change_prop += 1 ;
-        { // Handle cases where there is no internal computation to re-compute:
-            let node : &mut Node<Res> = res_node_of_loc(st, loc) ;
+        match prog_pt_of(st, loc) { None => (), Some(prog_pt) => st.cnt.prog_pt(&prog_pt).change_prop += 1 } ;
+        let (early, is_empty, succs) = { // Handle cases where there is no internal computation to re-compute:
+            let node : &mut Node<Res> = try!(res_node_of_loc(st, loc)) ;
             match *node {
-                Node::Comp(_) => (),
-                Node::Pure(_) => {
-                    info!("{} change_prop early end: {:?} is Pure(_)", engineMsg(Some(stackLen)), loc);
-                    return EngineRes{changed:false}
-                },
-                Node::Mut(ref nd) => {
-                    info!("{} change_prop early end: {:?} is Mut(_)", engineMsg(Some(stackLen)), loc);
-                    return EngineRes{changed:nd.val != self.res}
+                Node::Comp(ref mut nd) if nd.running => {
+                    // A cyclic force: the thunk is running.  (Running
+                    // nodes were logged for rollback when their run began.)
+                    let changed = match cycle_approx(nd, None) { None => true, Some(ref approx) => res_changed(&nd.res_eq, &self.res, approx) } ;
+                    (Some(changed), false, Vec::new())
                 },
+                Node::Comp(ref nd) => (None, nd.res.is_none(), nd.succs.clone()),
+                Node::Pure(_) => (Some(false), false, Vec::new()),
+                Node::Mut(ref nd) => (Some(nd.val != self.res), false, Vec::new()),
                 _ => panic!("undefined")
             }
         };
-        let succs = {
-            let node : &mut Node<Res> = res_node_of_loc(st, loc) ;
-            assert!( node.succs_def() );
-            node.succs_mut().clone()
-        } ;
-        for succ in succs.iter() {
-            if succ.dirty {
-                let dep = & succ.dep ;
-                let res = dep.change_prop(st, &succ.loc) ;
-                if res.changed {
-                    info!("{} change_prop end (1/2): {:?} has a changed succ dependency: {:?}. Begin re-production:", engineMsg!(st), loc, &succ.loc);
-                    let res = re_produce (self, st, loc);
-                    info!("{} change_prop end (2/2): {:?} has a changed succ dependency: {:?}. End re-production.", engineMsg!(st), loc, &succ.loc);
-                    return res
-                }
+        match early {
+            None => (),
+            Some(changed) => {
+                info!("{} change_prop early end: {:?} is not a thunk, or is running", engineMsg(Some(stackLen)), loc);
+                return Ok(ChangePropStep::Done(change_prop_done(st, loc, changed, false)))
             }
         } ;
-        info!("{} change_prop end: {:?} is clean.", engineMsg!(st), &loc);
-        // No early return =>
-        //   all immediate dependencies are change-free:
-        EngineRes{changed:false}
+        if is_empty {
+            // The result was cleared (e.g., by a nominal re-allocation
+            // with a new argument or producer), or evicted:
+            info!("{} change_prop: {:?} is empty. Begin re-production:", engineMsg!(st), loc);
+            let res = try!(re_produce (self, st, loc)) ;
+            return Ok(ChangePropStep::Done(change_prop_done(st, loc, res.changed, true)))
+        } ;
+        Ok(ChangePropStep::Succs(succs))
     }
-}
 
-// ---------- Node implementation:
+    fn change_prop_end(self:&Self, st:&mut Engine, loc:&Arc<Loc>, succ_changed:bool) -> AdaptonResult<EngineRes> {
+        if succ_changed {
+            info!("{} change_prop end (1/2): {:?} has a changed succ dependency. Begin re-production:", engineMsg!(st), loc);
+            let res = try!(re_produce (self, st, loc));
+            info!("{} change_prop end (2/2): {:?} has a changed succ dependency. End re-production.", engineMsg!(st), loc);
+            return Ok(change_prop_done(st, loc, res.changed, true))
+        } ;
+        info!("{} change_prop end: {:?} is clean.", engineMsg!(st), &loc);
+        cache_touch(st, loc, CacheEvent::Validate, false) ;
+        st.cnt.hit += 1 ;
+        match prog_pt_of(st, loc) { None => (), Some(prog_pt) => st.cnt.prog_pt(&prog_pt).hit += 1 } ;
+        // All immediate dependencies are change-free.  Still, the
+        // result may differ from the one observed via this dependency,
+        // when another observer re-produced it first:
+        let changed = {
+            let node : &mut Node<Res> = try!(res_node_of_loc(st, loc)) ;
+            match *node {
+                Node::Comp(ref nd) => match nd.res { None => true, Some(ref res) => res_changed(&nd.res_eq, &self.res, res) },
+                _ => unreachable!(),
+            }} ;
+        Ok(change_prop_done(st, loc, changed, false))
+    }
 
-fn revoke_succs<'x> (st:&mut Engine, src:&Rc<Loc>, succs:&Vec<Succ>) {
-    for succ in succs.iter() {
-        let succ_node : &mut Box<GraphNode> = lookup_abs(st, &succ.loc) ;
-        succ_node.preds_remove(src)
+    fn change_prop_cycle(self:&Self, st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<EngineRes> {
+        let node : &mut Node<Res> = try!(res_node_of_loc(st, loc)) ;
+        match *node {
+            Node::Comp(ref nd) => Ok(EngineRes{changed:match nd.res { None => true, Some(ref res) => res_changed(&nd.res_eq, &self.res, res) }, reproduced:false}),
+            _ => unreachable!(),
+        }
     }
-}
 
-fn loc_of_id(path:Rc<Path>,id:Rc<ArtId<Name>>) -> Rc<Loc> {
-    let hash = my_hash(&(&path,&id));
-    Rc::new(Loc{path:path,id:id,hash:hash})
+    fn as_any(self:&Self) -> &Any { self }
 }
 
-// Implement "sharing" of the dirty bit.
-// The succ edge is returned as a mutable borrow, to permit checking
-// and mutating the dirty bit.
-fn get_succ_mut<'r>(st:&'r mut Engine, src_loc:&Rc<Loc>, eff:Effect, tgt_loc:&Rc<Loc>) -> &'r mut Succ {
-    let stackLen = st.stack.len() ;
-    let nd = lookup_abs( st, src_loc );
-    info!("{} get_succ_mut: resolving {:?} --{:?}--dirty:?--> {:?}", engineMsg(Some(stackLen)), &src_loc, &eff, &tgt_loc);
-    for succ in nd.succs_mut().iter_mut() {
-        if (succ.effect == eff) && (&succ.loc == tgt_loc) {
-            info!("{} get_succ_mut: resolved {:?} --{:?}--dirty:{:?}--> {:?}", engineMsg(Some(stackLen)), &src_loc, &succ.effect, &succ.dirty, &tgt_loc);
-            return succ
-        } else {}
-    } ;
-    panic!("tgt_loc is dangling in src_node.dem_succs")
+fn change_prop_done(st:&mut Engine, loc:&Arc<Loc>, changed:bool, reproduced:bool) -> EngineRes {
+    let prog_pt = observed_prog_pt(st, loc) ;
+    notify(st, |o| o.change_prop_end(loc, prog_pt.as_ref(), changed)) ;
+    EngineRes{changed:changed, reproduced:reproduced}
 }
 
-fn dirty_pred_observers(st:&mut Engine, loc:&Rc<Loc>) {
-    info!("{} dirty_pred_observers: {:?}", engineMsg!(st), loc);
-    st.cnt.dirty += 1 ;
-    let stackLen = st.stack.len() ;
-    let pred_locs : Vec<Rc<Loc>> = lookup_abs( st, loc ).preds_obs() ;
-    for pred_loc in pred_locs {
-        if st.root.eq (&pred_loc) { panic!("root in preds") }
-        else {
-            let stop : bool = {
-                // The stop bit communicates information from st for use below.
-                info!("{} dirty_pred_observers: edge {:?} --> {:?} ...", engineMsg(Some(stackLen)), &pred_loc, &loc);
-                let succ = get_succ_mut(st, &pred_loc, Effect::Observe, &loc) ;
-                if succ.dirty { true } else {
-                    replace(&mut succ.dirty, true);
-                    info!("{} dirty_pred_observers: edge marked dirty: {:?} --{:?}--dirty:{:?}--> {:?}", engineMsg(Some(stackLen)), &pred_loc, &succ.effect, &succ.dirty, &loc);
+// Propagates changes to loc, via its dependency dep.  Traverses the
+// dirty sub-DCG below loc with a worklist, rather than recursion, so
+// that its depth is limited by heap memory, rather than by the native
+// stack: each node ends after its dirty succs, deepest first, and
+// stops at the first succ whose value changed.  (Re-production re-runs
+// a producer, whose forces propagate changes to its succs anew; by
+// then, these succs are consistent.)
+//
+// Independent dirty succs are repaired one after another, on this
+// thread: producers run on `&mut Engine`, whose DCG no other thread
+// may touch, so `Engine` has no parallel mode.  Concurrent repair is
+// opt-in, and exists only on `SyncEngine`, which shares its DCG among
+// threads; see `SyncEngineConfig::threads`.
+fn change_prop(st:&mut Engine, dep:Rc<Box<EngineDep>>, loc:&Arc<Loc>) -> AdaptonResult<EngineRes> {
+    struct Work {
+        dep   : Rc<Box<EngineDep>>,
+        loc   : Arc<Loc>,
+        succs : Vec<Succ>,
+        next  : usize, // The next succ to consider
+    }
+    let mut todo : Vec<Work> = Vec::new() ;
+    let mut on_todo : HashSet<Arc<Loc>> = HashSet::new() ; // The locs of todo, to detect cycles.
+    match try!(dep.change_prop_begin(st, loc)) {
+        ChangePropStep::Done(res) => return Ok(res),
+        ChangePropStep::Succs(succs) => {
+            on_todo.insert(loc.clone()) ;
+            todo.push(Work{dep:dep, loc:loc.clone(), succs:succs, next:0})
+        },
+    } ;
+    let mut succ_changed = false ;
+    loop {
+        let succ : Option<Succ> = {
+            let work = match todo.last_mut() { None => unreachable!(), Some(work) => work } ;
+            if succ_changed { None } else {
+                while work.next < work.succs.len() && !work.succs[work.next].dirty { work.next += 1 } ;
+                if work.next < work.succs.len() {
+                    work.next += 1 ;
+                    Some(work.succs[work.next - 1].clone())
+                } else { None }
+            }} ;
+        let done = match succ {
+            Some(ref succ) if on_todo.contains(&succ.loc) => {
+                succ_changed = try!(succ.dep.change_prop_cycle(st, &succ.loc)).changed ;
+                true
+            },
+            Some(succ) => match try!(succ.dep.change_prop_begin(st, &succ.loc)) {
+                ChangePropStep::Done(res) => { succ_changed = res.changed ; true },
+                ChangePropStep::Succs(succs) => {
+                    on_todo.insert(succ.loc.clone()) ;
+                    todo.push(Work{dep:succ.dep.clone(), loc:succ.loc.clone(), succs:succs, next:0}) ;
                     false
-                }} ;
-            if !stop {
-                dirty_pred_observers(st,&pred_loc);
-            } else { info!("{} dirty_pred_observers: already dirty", engineMsg(Some(stackLen))) }
+                },
+            },
+            None => {
+                let work = match todo.pop() { None => unreachable!(), Some(work) => work } ;
+                on_todo.remove(&work.loc) ;
+                let res = try!(work.dep.change_prop_end(st, &work.loc, succ_changed)) ;
+                // The last succ considered changed, and work.loc re-produced:
+                if succ_changed { record_cause(st, &work.loc, &work.succs[work.next - 1]) } ;
+                if todo.is_empty() { return Ok(res) } ;
+                succ_changed = res.changed ;
+                true
+            },
+        } ;
+        if done && !succ_changed {
+            // The succ is consistent with the value observed by its
+            // edge, so a later force need not re-validate it:
+            let work = match todo.last() { None => unreachable!(), Some(work) => work } ;
+            clean_succ(st, &work.loc, work.next - 1, &work.succs[work.next - 1])
         }
     }
 }
 
-fn dirty_alloc(st:&mut Engine, loc:&Rc<Loc>) {
-    info!("{} dirty_alloc: {:?}", engineMsg!(st), loc);
-    dirty_pred_observers(st, loc);
-    let stackLen = st.stack.len() ;
-    let pred_locs : Vec<Rc<Loc>> = lookup_abs(st, loc).preds_alloc() ;
-    for pred_loc in pred_locs {
-        if st.root.eq (&pred_loc) { panic!("root in preds") }
-        else {
-            let stop : bool = {
-                // The stop bit communicates information from st for use below.
-                info!("{} dirty_alloc: edge {:?} --> {:?} ...", engineMsg(Some(stackLen)), &pred_loc, &loc);
-                let succ = get_succ_mut(st, &pred_loc, Effect::Allocate, &loc) ;
-                if succ.dirty { true } else {
-                    info!("{} dirty_alloc: edge {:?} --> {:?} marked dirty", engineMsg(Some(stackLen)), &pred_loc, &loc);
-                    replace(&mut succ.dirty, true);
-                    false
-                }} ;
-            if !stop {
-                dirty_pred_observers(st,&pred_loc);
-            } else { info!("{} dirty_alloc: early stop", engineMsg(Some(stackLen))) }
+// Cleans the idx-th succ of src_loc, the edge that change_prop just
+// validated.  (Other edges to the same loc, with the same effect, may
+// have observed other values.)  If src_loc re-produced meanwhile, its
+// succs are new, and idx names another edge, which stays as it is.
+fn clean_succ(st:&mut Engine, src_loc:&Arc<Loc>, idx:usize, validated:&Succ) {
+    let is_same = |succ:&Succ| succ.dirty && succ.effect == validated.effect && succ.loc == validated.loc ;
+    let is_dirty = match st.table.get_mut(src_loc) {
+        None => false,
+        Some(nd) => nd.succs_mut().get(idx).map_or(false, |succ| is_same(succ)),
+    } ;
+    if is_dirty {
+        match table_get_mut(st, src_loc) {
+            None => unreachable!(),
+            Some(nd) => nd.succs_mut()[idx].dirty = false,
         }
     }
 }
 
-fn do_set<T:Eq+Debug> (st:&mut Engine, cell:MutArt<T,Loc>, val:T) {
-    info!("{} do_set: {:?} <--- {:?}", engineMsg!(st), cell, val);
-    let changed : bool = {
-        let node = res_node_of_loc( st, &cell.loc ) ;
-        match **node {
-            Node::Mut(ref mut nd) => {
-                if nd.val == val {
-                    false
-                } else {
-                    replace(&mut nd.val, val) ;
-                    true
-                }},
-            _ => unreachable!(),
+// Re-validates the root at loc, when it holds a result that some
+// dirty succ may invalidate (see `Engine::propagate`).
+fn validate_root<T:'static+Eq+Debug+Clone>(st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<()> {
+    let cached : Option<T> = {
+        let node : &mut Node<T> = try!(res_node_of_loc(st, loc)) ;
+        match *node {
+            Node::Comp(ref nd) if nd.succs.iter().any(|succ| succ.dirty) => nd.res.clone(),
+            _ => None,
         }} ;
-    if changed {
-        dirty_alloc(st, &cell.loc)
+    match cached {
+        // Consistent, or not yet (or no longer) cached: no work.
+        None => Ok(()),
+        Some(res) => { try!(change_prop(st, Rc::new(Box::new(ProducerDep{res:res})), loc)) ; Ok(()) }
     }
-    else { }
 }
 
-impl Adapton for Engine {
-    type Name = Name;
-    type Loc  = Loc;
+fn do_propagate(st:&mut Engine) -> AdaptonResult<Cnt> {
+    info!("{} propagate: {} roots", engineMsg!(st), st.roots.len());
+    let roots = st.roots.clone() ;
+    let (res, cnt) = st.cnt(|st| -> AdaptonResult<()> {
+        for root in roots.iter() { try!((root.validate)(st, &root.loc)) } ;
+        Ok(())
+    }) ;
+    try!(res) ;
+    Ok(cnt)
+}
 
-    fn new () -> Engine {
-        let root = {
-            let path   = Rc::new(Path::Empty);
-            let symbol = Rc::new(NameSym::Root);
-            let hash   = my_hash(&symbol);
-            let name   = Name{symbol:symbol,hash:hash};
-            let id     = Rc::new(ArtId::Nominal(name));
-            let hash   = my_hash(&(&path,&id));
-            let loc    = Rc::new(Loc{path:path.clone(),id:id,hash:hash});
-            loc
-        } ;
-        let mut stack = Vec::new() ;
-        stack.push( Frame{loc:root.clone(),
-                          path:root.path.clone(),
-                          succs:Vec::new()} ) ;
-        Engine {
-            root  : root,
-            table : HashMap::new (),
-            stack : stack,
-            cnt   : Cnt::zero (),
-        }
-    }
+// ---------- Node implementation:
 
-    fn name_of_string (self:&mut Engine, sym:String) -> Name {
-        let h = my_hash(&sym);
-        let s = NameSym::String(sym) ;
-        Name{ hash:h, symbol:Rc::new(s) }
-    }
+fn revoke_succs<'x> (st:&mut Engine, src:&Arc<Loc>, succs:&Vec<Succ>) -> AdaptonResult<()> {
+    st.cnt.edge_remove += succs.len() ;
+    for succ in succs.iter() {
+        let succ_node : &mut Box<GraphNode> = try!(lookup_abs_mut(st, &succ.loc)) ;
+        succ_node.preds_remove(src)
+    } ;
+    Ok(())
+}
 
-    fn name_of_usize (self:&mut Engine, sym:usize) -> Name {
-        let h = my_hash(&sym) ;
-        let s = NameSym::Usize(sym) ;
-        Name{ hash:h, symbol:Rc::new(s) }
-    }
+// The path of the current frame, under which cells and thunks are
+// allocated: the path of the running thunk, extended by the
+// namespaces (`ns`) that it entered.
+fn frame_path(st:&Engine) -> Arc<Path> {
+    match st.stack.last() { None => unreachable!(), Some(frame) => frame.path.clone() }
+}
 
-    fn name_pair (self: &mut Engine, fst: Name, snd: Name) -> Name {
-        let h = my_hash( &(fst.hash,snd.hash) ) ;
-        let p = NameSym::Pair(fst.symbol, snd.symbol) ;
-        Name{ hash:h, symbol:Rc::new(p) }
+// The names of path, outermost first.
+fn path_names(path:&Path, names:&mut Vec<Name>) {
+    match *path {
+        Path::Empty => (),
+        Path::Child(ref parent, ref name, _) => { path_names(parent, names) ; names.push(name.clone()) },
     }
+}
 
-    fn name_fork (self:&mut Engine, nm:Name) -> (Name, Name) {
-        let h1 = my_hash( &(&nm, 11111111) ) ; // TODO-Later: make this hashing better.
-        let h2 = my_hash( &(&nm, 22222222) ) ;
-        ( Name{ hash:h1,
-                symbol:Rc::new(NameSym::ForkL(nm.symbol.clone())) } ,
-          Name{ hash:h2,
-                symbol:Rc::new(NameSym::ForkR(nm.symbol)) } )
+fn loc_of_id(path:Arc<Path>,id:Arc<ArtId<Name>>) -> Arc<Loc> {
+    let hash = fingerprint(&(&path,&id));
+    Arc::new(Loc{path:path,id:id,hash:hash})
+}
+
+// When checking for collisions, fails when another name has the
+// fingerprint of name.
+fn check_name(st:&mut Engine, name:&Name) -> AdaptonResult<()> {
+    let fingerprints = match st.fingerprints { None => return Ok(()), Some(ref mut fingerprints) => fingerprints } ;
+    match fingerprints.names.entry(name.hash) {
+        Entry::Vacant(e) => { e.insert(name.symbol.clone()) ; Ok(()) },
+        Entry::Occupied(e) =>
+            if **e.get() == *name.symbol { Ok(()) }
+            else { Err(AdaptonError::HashCollision{hash:format!("{}", name.hash),
+                                                   first:format!("{:?}", e.get()),
+                                                   second:format!("{:?}", name.symbol)}) },
     }
+}
 
-    fn ns<T,F> (self: &mut Self, nm:Name, body:F) -> T where F:FnOnce(&mut Self) -> T {
-        let path = match self.stack.last() { None => unreachable!(), Some(frame) => frame.path.clone() } ;
-        let path_body = Rc::new(Path::Child(path, nm)) ;
-        let path_pre = match self.stack.last_mut() { None => unreachable!(), Some(frame) => replace(&mut frame.path, path_body) } ;
-        let x = body(self) ;
-        let path_body = match self.stack.last_mut() { None => unreachable!(), Some(frame) => replace(&mut frame.path, path_pre) } ;
-        drop(path_body);
-        x
+// Like `check_name`, for paths.  (Paths compare by fingerprint; the
+// parents and names of paths that share one are compared, in turn.)
+fn check_path(st:&mut Engine, path:&Arc<Path>) -> AdaptonResult<()> {
+    let fingerprints = match st.fingerprints { None => return Ok(()), Some(ref mut fingerprints) => fingerprints } ;
+    let hash = match **path { Path::Empty => return Ok(()), Path::Child(_, _, hash) => hash } ;
+    match fingerprints.paths.entry(hash) {
+        Entry::Vacant(e) => { e.insert(path.clone()) ; Ok(()) },
+        Entry::Occupied(e) => {
+            let same = match (&**e.get(), &**path) {
+                (&Path::Child(ref p1, ref n1, _), &Path::Child(ref p2, ref n2, _)) => p1 == p2 && n1 == n2,
+                _ => false,
+            } ;
+            if same { Ok(()) }
+            else { Err(AdaptonError::HashCollision{hash:format!("{}", hash),
+                                                   first:format!("{:?}", e.get()),
+                                                   second:format!("{:?}", path)}) }
+        },
     }
+}
 
-    fn cnt<Res,F> (self: &mut Self, body:F) -> (Res,Cnt)
-        where F:FnOnce(&mut Self) -> Res
-    {
-        let c = self.cnt.clone() ;
-        let x = body(self) ;
-        let d = self.cnt.clone() - c ;
-        (x, d)
+// Like `check_name`, for locations.
+fn check_loc(st:&mut Engine, loc:&Loc) -> AdaptonResult<()> {
+    let fingerprints = match st.fingerprints { None => return Ok(()), Some(ref mut fingerprints) => fingerprints } ;
+    match fingerprints.locs.entry(loc.hash) {
+        Entry::Vacant(e) => { e.insert((loc.path.clone(), loc.id.clone())) ; Ok(()) },
+        Entry::Occupied(e) => {
+            let (ref path, ref id) = *e.get() ;
+            if *path == loc.path && *id == loc.id { Ok(()) }
+            else { Err(AdaptonError::HashCollision{hash:format!("{}", loc.hash),
+                                                   first:format!("{:?}{:?}", path, id),
+                                                   second:format!("{:?}", loc)}) }
+        },
     }
+}
 
-    fn put<T:Eq> (self:&mut Engine, x:T) -> Art<T,Self::Loc> { Art::Rc(Rc::new(x)) }
+// Like `check_name`, for a structural location, whose identity hashes
+// the program point and argument of its thunk: fails when the
+// existing thunk at loc has another program point or argument.
+fn check_structural<Arg:'static+Eq+Debug,Spurious:'static,Res:'static>
+    (st:&mut Engine, loc:&Arc<Loc>, prog_pt:&ProgPt, arg:&Arg) -> AdaptonResult<()>
+{
+    if st.fingerprints.is_none() { return Ok(()) } ;
+    let node = match st.table.get_mut(loc) { None => return Ok(()), Some(node) => node } ;
+    let first = format!("{:?}", node.prog_pt()) ;
+    let same = match node.as_any().downcast_mut::<Node<Res>>() {
+        Some(&mut Node::Comp(ref nd)) => match nd.producer.as_any().downcast_ref::<App<Arg,Spurious,Res>>() {
+            Some(app) => &app.prog_pt == prog_pt && &app.arg == arg,
+            None => false,
+        },
+        _ => false,
+    } ;
+    if same { Ok(()) }
+    else { Err(AdaptonError::HashCollision{hash:format!("{}", loc.hash),
+                                           first:first,
+                                           second:format!("Some({:?}) {:?}", prog_pt, arg)}) }
+}
 
-    fn cell<T:Eq+Debug+Clone
-        +'static // TODO-Later: Needed on T because of lifetime issues.
-        >
-        (self:&mut Engine, nm:Self::Name, val:T) -> MutArt<T,Self::Loc> {
-            let path = self.stack[0].path.clone();
-            let id   = Rc::new(ArtId::Nominal(nm));
-            let hash = my_hash(&(&path,&id));
-            let loc  = Rc::new(Loc{path:path,id:id,hash:hash});
-            info!("{} alloc cell: {:?} <--- {:?}", engineMsg!(self), &loc, &val);
-            let cell = match self.table.get_mut(&loc) {
-                None => None,
-                Some(ref mut _nd) => {
-                    Some(MutArt{loc:loc.clone(),
-                                phantom:PhantomData})
-                },
+// Returns the name with symbol sym, whose fingerprint is hash, and
+// the interned copy of sym; fails on a collision, when checking for
+// them.
+fn intern_name(st:&mut Engine, hash:Fingerprint, sym:NameSym) -> AdaptonResult<Name> {
+    if st.fingerprints.is_some() {
+        // Check sym itself, not the interned copy with its fingerprint.
+        let name = Name{ hash:hash, symbol:Arc::new(sym.clone()) } ;
+        try!(check_name(st, &name))
+    } ;
+    let symbol = match st.interned.syms.get(&hash).and_then(|sym| sym.upgrade()) {
+        Some(symbol) => symbol,
+        None => {
+            let symbol = Arc::new(sym) ;
+            st.interned.syms.insert(hash, Arc::downgrade(&symbol)) ;
+            st.cnt.intern += 1 ;
+            symbol
+        }
+    } ;
+    Ok(Name{ hash:hash, symbol:symbol })
+}
+
+// Like `intern_name`, for the child path of parent named name.
+fn intern_path(st:&mut Engine, parent:Arc<Path>, name:Name) -> AdaptonResult<Arc<Path>> {
+    let path = path_child(parent, name) ;
+    let hash = match path { Path::Empty => unreachable!(), Path::Child(_, _, hash) => hash } ;
+    if st.fingerprints.is_some() {
+        try!(check_path(st, &Arc::new(path.clone())))
+    } ;
+    match st.interned.paths.get(&hash).and_then(|path| path.upgrade()) {
+        Some(path) => Ok(path),
+        None => {
+            let path = Arc::new(path) ;
+            st.interned.paths.insert(hash, Arc::downgrade(&path)) ;
+            st.cnt.intern += 1 ;
+            Ok(path)
+        }
+    }
+}
+
+fn name_of_sym(st:&mut Engine, sym:NameSym) -> AdaptonResult<Name> {
+    let hash = fingerprint(&sym) ;
+    intern_name(st, hash, sym)
+}
+
+// Derived names hash the fingerprints of their parts, tagged by the
+// kind of derivation (rather than their whole symbols, which grow).
+fn do_name_pair(st:&mut Engine, fst:Name, snd:Name) -> AdaptonResult<Name> {
+    let hash = fingerprint( &("pair", fst.hash, snd.hash) ) ;
+    intern_name(st, hash, NameSym::Pair(fst.symbol, snd.symbol))
+}
+
+fn do_name_fork(st:&mut Engine, nm:Name) -> AdaptonResult<(Name, Name)> {
+    let h1 = fingerprint( &("fork_l", nm.hash) ) ;
+    let h2 = fingerprint( &("fork_r", nm.hash) ) ;
+    let nm1 = try!(intern_name(st, h1, NameSym::ForkL(nm.symbol.clone()))) ;
+    let nm2 = try!(intern_name(st, h2, NameSym::ForkR(nm.symbol))) ;
+    Ok((nm1, nm2))
+}
+
+// Runs body with path as the path of the current frame.
+fn in_ns<T,F>(st:&mut Engine, path:Arc<Path>, body:F) -> T where F:FnOnce(&mut Engine) -> T {
+    let path_pre = match st.stack.last_mut() { None => unreachable!(), Some(frame) => replace(&mut frame.path, path) } ;
+    let x = body(st) ;
+    let path_body = match st.stack.last_mut() { None => unreachable!(), Some(frame) => replace(&mut frame.path, path_pre) } ;
+    drop(path_body);
+    x
+}
+
+// Removes the recorded explanations of nodes that are gone.
+fn prune_reasons(st:&mut Engine) {
+    let gone : Vec<Loc> = match st.reasons {
+        None => return,
+        Some(ref reasons) => reasons.keys().filter(|loc| !st.table.contains_key(*loc)).map(|loc| loc.clone()).collect(),
+    } ;
+    match st.reasons {
+        None => (),
+        Some(ref mut reasons) => for loc in gone.iter() { reasons.remove(loc) ; },
+    }
+}
+
+// Removes the interned names and paths that nothing uses any more.
+fn prune_interned(st:&mut Engine) {
+    let syms : Vec<Fingerprint> = st.interned.syms.iter()
+        .filter(|&(_, sym)| sym.upgrade().is_none()).map(|(hash, _)| *hash).collect() ;
+    for hash in syms.iter() { st.interned.syms.remove(hash) ; } ;
+    let paths : Vec<Fingerprint> = st.interned.paths.iter()
+        .filter(|&(_, path)| path.upgrade().is_none()).map(|(hash, _)| *hash).collect() ;
+    for hash in paths.iter() { st.interned.paths.remove(hash) ; } ;
+}
+
+// Returns the table's own `Arc` for loc, when the node already exists.
+// Handing out (and storing) only these canonical `Arc`s lets the
+// collector use `Arc::strong_count` of a table key to detect
+// outstanding `Art` and `MutArt` handles to its node.
+fn canonical_loc(st:&mut Engine, loc:Arc<Loc>) -> Arc<Loc> {
+    match st.table.entry(loc.clone()) {
+        Entry::Occupied(e) => e.key().clone(),
+        Entry::Vacant(_)   => loc,
+    }
+}
+
+// Implement "sharing" of the dirty bit.
+// The succ edge is returned as a mutable borrow, to permit checking
+// and mutating the dirty bit.
+fn get_succ_mut<'r>(st:&'r mut Engine, src_loc:&Arc<Loc>, eff:Effect, tgt_loc:&Arc<Loc>) -> AdaptonResult<&'r mut Succ> {
+    let stackLen = st.stack.len() ;
+    let nd = try!(lookup_abs_mut( st, src_loc ));
+    info!("{} get_succ_mut: resolving {:?} --{:?}--dirty:?--> {:?}", engineMsg(Some(stackLen)), &src_loc, &eff, &tgt_loc);
+    for succ in nd.succs_mut().iter_mut() {
+        if (succ.effect == eff) && (&succ.loc == tgt_loc) {
+            info!("{} get_succ_mut: resolved {:?} --{:?}--dirty:{:?}--> {:?}", engineMsg(Some(stackLen)), &src_loc, &succ.effect, &succ.dirty, &tgt_loc);
+            return Ok(succ)
+        } else {}
+    } ;
+    Err(AdaptonError::DanglingEdge{src:format!("{:?}", src_loc), tgt:format!("{:?}", tgt_loc)})
+}
+
+// Dirties the observers of loc, transitively.  Uses a worklist,
+// rather than recursion, so that long chains of observers are limited
+// by heap memory, rather than by the native stack.
+fn dirty_pred_observers(st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<()> {
+    dirty_observers(st, vec![loc.clone()])
+}
+
+// Dirties the observers of each loc in todo, transitively; each node
+// is visited once, even when reached from several locs.
+fn dirty_observers(st:&mut Engine, mut todo:Vec<Arc<Loc>>) -> AdaptonResult<()> {
+    let mut visited : HashSet<Arc<Loc>> = HashSet::new() ;
+    while let Some(loc) = todo.pop() {
+        if !visited.insert(loc.clone()) { continue } ;
+        info!("{} dirty_pred_observers: {:?}", engineMsg!(st), loc);
+        st.cnt.dirty += 1 ;
+        match prog_pt_of(st, &loc) { None => (), Some(prog_pt) => st.cnt.prog_pt(&prog_pt).dirty += 1 } ;
+        let stackLen = st.stack.len() ;
+        let pred_locs : Vec<Arc<Loc>> = try!(lookup_abs( st, &loc )).preds_obs() ;
+        for pred_loc in pred_locs {
+            if st.root.eq (&pred_loc) { return Err(AdaptonError::RootInPreds{loc:format!("{:?}", loc)}) }
+            else {
+                let stop : bool = {
+                    // The stop bit communicates information from st for use below.
+                    info!("{} dirty_pred_observers: edge {:?} --> {:?} ...", engineMsg(Some(stackLen)), &pred_loc, &loc);
+                    let succ = try!(get_succ_mut(st, &pred_loc, Effect::Observe, &loc)) ;
+                    if succ.dirty { true } else {
+                        replace(&mut succ.dirty, true);
+                        info!("{} dirty_pred_observers: edge marked dirty: {:?} --{:?}--dirty:{:?}--> {:?}", engineMsg(Some(stackLen)), &pred_loc, &succ.effect, &succ.dirty, &loc);
+                        false
+                    }} ;
+                if !stop {
+                    let pred_prog_pt = observed_prog_pt(st, &pred_loc) ;
+                    notify(st, |o| o.edge_dirty(&pred_loc, pred_prog_pt.as_ref(), &Effect::Observe, &loc)) ;
+                    todo.push(pred_loc)
+                } else { info!("{} dirty_pred_observers: already dirty", engineMsg(Some(stackLen))) }
+            }
+        }
+    } ;
+    Ok(())
+}
+
+fn dirty_alloc(st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<()> {
+    dirty_allocs(st, vec![loc.clone()])
+}
+
+// Like `dirty_alloc`, for several locs at once, in a single pass over
+// their observers (see `Engine::batch`).
+fn dirty_allocs(st:&mut Engine, locs:Vec<Arc<Loc>>) -> AdaptonResult<()> {
+    let stackLen = st.stack.len() ;
+    let mut todo : Vec<Arc<Loc>> = locs.clone() ;
+    for loc in locs.iter() {
+        info!("{} dirty_alloc: {:?}", engineMsg(Some(stackLen)), loc);
+        // Allocators and writers: each asserted a value that may now be
+        // overwritten, so each must be re-validated on demand.
+        let pred_locs : Vec<(Effect,Arc<Loc>)> = {
+            let node = try!(lookup_abs(st, loc)) ;
+            let mut preds : Vec<(Effect,Arc<Loc>)> =
+                node.preds_alloc().into_iter().map(|pred| (Effect::Allocate, pred)).collect() ;
+            preds.extend(node.preds_write().into_iter().map(|pred| (Effect::Write, pred))) ;
+            preds
+        } ;
+        for (effect, pred_loc) in pred_locs {
+            if st.root.eq (&pred_loc) { return Err(AdaptonError::RootInPreds{loc:format!("{:?}", loc)}) }
+            else {
+                let stop : bool = {
+                    // The stop bit communicates information from st for use below.
+                    info!("{} dirty_alloc: edge {:?} --{:?}--> {:?} ...", engineMsg(Some(stackLen)), &pred_loc, &effect, &loc);
+                    let succ = try!(get_succ_mut(st, &pred_loc, effect.clone(), &loc)) ;
+                    if succ.dirty { true } else {
+                        info!("{} dirty_alloc: edge {:?} --> {:?} marked dirty", engineMsg(Some(stackLen)), &pred_loc, &loc);
+                        replace(&mut succ.dirty, true);
+                        false
+                    }} ;
+                if !stop {
+                    let pred_prog_pt = observed_prog_pt(st, &pred_loc) ;
+                    notify(st, |o| o.edge_dirty(&pred_loc, pred_prog_pt.as_ref(), &effect, loc)) ;
+                    todo.push(pred_loc)
+                } else { info!("{} dirty_alloc: early stop", engineMsg(Some(stackLen))) }
+            }
+        }
+    } ;
+    dirty_observers(st, todo)
+}
+
+// Retires the node at loc, so that a node of another kind can replace
+// it (a cell replaces a thunk, or vice versa).  Dirties the observers,
+// allocators and writers of the old node, revokes its succs, and returns its
+// preds, for the new node to inherit.
+fn retire_node(st:&mut Engine, loc:&Arc<Loc>) -> AdaptonResult<Vec<(Effect,Arc<Loc>)>> {
+    try!(dirty_alloc(st, loc)) ;
+    let mut node = match table_remove(st, loc) { None => unreachable!(), Some(node) => node } ;
+    if node.res_clear() { cache_forget(st, loc) } ;
+    if node.succs_def() { try!(revoke_succs(st, loc, node.succs_mut())) } ;
+    Ok(node.preds())
+}
+
+fn do_set<T:'static+Eq+Debug> (st:&mut Engine, cell:MutArt<T,Loc>, val:T) -> AdaptonResult<()> {
+    info!("{} do_set: {:?} <--- {:?}", engineMsg!(st), cell, val);
+    let recording = st.reasons.is_some() ;
+    let mut values = None ;
+    let changed : bool = {
+        let node : &mut Node<T> = try!(res_node_of_loc_mut( st, &cell.loc )) ;
+        let kind = node_kind(node) ;
+        match *node {
+            Node::Mut(ref mut nd) => {
+                if nd.val == val {
+                    false
+                } else {
+                    let old = replace(&mut nd.val, val) ;
+                    if recording { values = Some((format!("{:?}", old), format!("{:?}", nd.val))) } ;
+                    true
+                }},
+            _ => return Err(kind_mismatch(&cell.loc, "cell", kind)),
+        }} ;
+    match values { None => (), Some((old, new)) => record_reason(st, &cell.loc, old, new) } ;
+    notify(st, |o| o.cell_set(&cell.loc, changed)) ;
+    if changed {
+        dirty_alloc(st, &cell.loc)
+    }
+    else { Ok(()) }
+}
+
+// The outer layer has control when the stack holds only the root frame.
+fn is_outer_layer(st:&Engine) -> bool { st.stack.len() == 1 }
+
+// A write from within the running thunk: sets the cell, then records
+// the write as an effect of the thunk.  Writes dirty the cell's
+// observers, allocators and writers (see `dirty_alloc`), but not the
+// writing thunk itself, whose edges are not yet installed.
+fn do_set_inner<T:'static+Eq+Debug+Clone> (st:&mut Engine, cell:MutArt<T,Loc>, val:T) -> AdaptonResult<()> {
+    info!("{} do_set_inner: {:?} <--- {:?}", engineMsg!(st), cell, val);
+    let loc = cell.loc.clone() ;
+    try!(do_set(st, cell, val.clone())) ;
+    let succ =
+        Succ{loc:loc,
+             dep:Rc::new(Box::new(WriteDependency{val:val})),
+             effect:Effect::Write,
+             dirty:false};
+    push_succ(st, succ) ;
+    Ok(())
+}
+
+/// The writes of a batch, which `Engine::batch` applies when its body
+/// returns.
+pub struct Batch {
+    writes : Vec<Box<BatchWrite>>,
+}
+
+impl Batch {
+    /// Sets `cell` to `val`, as `Adapton::set` does, when the batch is
+    /// applied.  Of several writes to one cell, the last one wins.
+    pub fn set<T:'static+Eq+Debug> (self:&mut Self, cell:MutArt<T,Loc>, val:T) {
+        self.writes.push(Box::new(CellWrite{cell:cell, val:val}))
+    }
+}
+
+// A write of a batch, at a hidden type.
+trait BatchWrite {
+    fn loc (self:&Self) -> &Arc<Loc> ;
+    // Checks, before any write is applied, that the write is well-typed.
+    fn check (self:&Self, st:&mut Engine) -> AdaptonResult<()> ;
+    // Applies the write; false when the cell already holds the value.
+    fn apply (self:Box<Self>, st:&mut Engine) -> bool ;
+}
+
+struct CellWrite<T> {
+    cell : MutArt<T,Loc>,
+    val  : T,
+}
+
+impl<T:'static+Eq+Debug> BatchWrite for CellWrite<T> {
+    fn loc (self:&Self) -> &Arc<Loc> { &self.cell.loc }
+    fn check (self:&Self, st:&mut Engine) -> AdaptonResult<()> {
+        let node : &mut Node<T> = try!(res_node_of_loc( st, &self.cell.loc )) ;
+        let kind = node_kind(node) ;
+        match *node {
+            Node::Mut(_) => Ok(()),
+            _ => Err(kind_mismatch(&self.cell.loc, "cell", kind)),
+        }
+    }
+    fn apply (self:Box<Self>, st:&mut Engine) -> bool {
+        let CellWrite{ cell, val } = *self ;
+        let recording = st.reasons.is_some() ;
+        let mut values = None ;
+        let changed = match res_node_of_loc_mut::<T>( st, &cell.loc ) {
+            Ok(&mut Node::Mut(ref mut nd)) => if nd.val == val { false } else {
+                let old = replace(&mut nd.val, val) ;
+                if recording { values = Some((format!("{:?}", old), format!("{:?}", nd.val))) } ;
+                true
+            },
+            _ => unreachable!(), // Checked.
+        } ;
+        match values { None => (), Some((old, new)) => record_reason(st, &cell.loc, old, new) } ;
+        notify(st, |o| o.cell_set(&cell.loc, changed)) ;
+        changed
+    }
+}
+
+// Applies the writes, then dirties (in one pass) for those that change
+// their cells.  Fails before applying any write when one is ill-typed.
+fn do_batch(st:&mut Engine, batch:Batch) -> AdaptonResult<Cnt> {
+    info!("{} batch: {} writes", engineMsg!(st), batch.writes.len());
+    if !is_outer_layer(st) {
+        match batch.writes.first() {
+            None => (),
+            Some(write) => {
+                let thunk = match st.stack.last() { None => unreachable!(), Some(frame) => format!("{:?}", frame.loc) } ;
+                return Err(AdaptonError::SetInInnerLayer{loc:format!("{:?}", write.loc()), thunk:thunk})
+            }
+        }
+    } ;
+    for write in batch.writes.iter() { try!(write.check(st)) } ;
+    let (res, cnt) = st.cnt(|st| {
+        let mut changed : HashSet<Arc<Loc>> = HashSet::new() ;
+        for write in batch.writes.into_iter() {
+            let loc = write.loc().clone() ;
+            if write.apply(st) { changed.insert(loc) ; }
+        } ;
+        dirty_allocs(st, changed.into_iter().collect())
+    }) ;
+    try!(res) ;
+    Ok(cnt)
+}
+
+/// Why a node last changed (see `Engine::explain`): the chain of
+/// changed succ edges, from a thunk that re-produced its result back
+/// to the cell whose `set` started the change.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Explanation {
+    /// The first step is the explained node; each step's `effect` is
+    /// that of its edge to the next step.
+    pub steps : Vec<ExplanationStep>,
+}
+
+/// A node of an `Explanation`, with its values before and after the
+/// change, printed with `Debug`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ExplanationStep {
+    pub loc     : Loc,
+    pub prog_pt : Option<ProgPt>, // None for cells
+    pub effect  : Option<Effect>, // None for the last step
+    pub old     : String,
+    pub new     : String,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f:&mut Formatter) -> Result {
+        for step in self.steps.iter() {
+            match step.prog_pt {
+                None => try!(write!(f, "cell {:?}", step.loc)),
+                Some(ref prog_pt) => try!(write!(f, "{} {:?}", prog_pt.symbol, step.loc)),
             } ;
-            match cell {
-                Some(cell) => {
-                    let cell_loc = cell.loc.clone();
-                    do_set(self, cell, val.clone()) ;
+            try!(write!(f, ": {} ~> {}", step.old, step.new)) ;
+            match step.effect {
+                None => try!(writeln!(f, "")),
+                Some(ref effect) => try!(writeln!(f, ", via {:?} of:", effect)),
+            }
+        } ;
+        Ok(())
+    }
+}
+
+// Records that the node at loc changed from old to new (when
+// recording explanations).  A thunk's cause follows, by `record_cause`.
+fn record_reason(st:&mut Engine, loc:&Arc<Loc>, old:String, new:String) {
+    match st.reasons {
+        None => (),
+        Some(ref mut reasons) => { reasons.insert((**loc).clone(), Reason{ cause:None, old:old, new:new }) ; },
+    }
+}
+
+// Records that the change of succ made the thunk at loc re-produce.
+fn record_cause(st:&mut Engine, loc:&Arc<Loc>, succ:&Succ) {
+    match st.reasons {
+        None => (),
+        Some(ref mut reasons) => match reasons.get_mut(&**loc) {
+            None => (),
+            Some(reason) => reason.cause = Some((succ.effect.clone(), (*succ.loc).clone())),
+        },
+    }
+}
+
+fn do_explain(st:&Engine, loc:&Loc) -> Explanation {
+    let mut steps = Vec::new() ;
+    let reasons = match st.reasons { None => return Explanation{ steps:steps }, Some(ref reasons) => reasons } ;
+    let mut loc = loc.clone() ;
+    let mut seen : HashSet<Loc> = HashSet::new() ;
+    // Stops at a cell, at a thunk re-produced for another reason (e.g.,
+    // its result was evicted), or at a cycle.
+    while seen.insert(loc.clone()) {
+        let reason = match reasons.get(&loc) { None => break, Some(reason) => reason } ;
+        steps.push(ExplanationStep{ loc:loc.clone(),
+                                    prog_pt:match st.table.get(&loc) { None => None, Some(node) => node.prog_pt() },
+                                    effect:reason.cause.as_ref().map(|&(ref effect, _)| effect.clone()),
+                                    old:reason.old.clone(),
+                                    new:reason.new.clone() }) ;
+        match reason.cause { None => break, Some((_, ref succ)) => loc = succ.clone() }
+    } ;
+    Explanation{ steps:steps }
+}
+
+// Records an event on the cached result at loc.  When the result is
+// new, evicts other results until the cache is within its limit.
+fn cache_touch(st:&mut Engine, loc:&Arc<Loc>, event:CacheEvent, is_new:bool) {
+    let limit = match st.cache.limit { None => return, Some(limit) => limit } ;
+    st.cache.policy.touch(loc, event) ;
+    if is_new { st.cache.size += 1 } ;
+    while st.cache.size > limit {
+        match st.cache.policy.victim() {
+            None => break,
+            Some(victim) => {
+                let evicted = match table_get_mut(st, &Arc::new(victim.clone())) {
+                    None => false,
+                    Some(node) => node.res_clear()
+                } ;
+                if evicted {
+                    info!("{} cache: evicted {:?}", engineMsg!(st), &victim);
+                    st.cache.size -= 1
+                }
+            }
+        }
+    }
+}
+
+// Records that the cached result at loc is gone: cleared by a nominal
+// re-allocation, or freed by the collector.  Only results that the
+// policy tracked count toward the size.
+fn cache_forget(st:&mut Engine, loc:&Arc<Loc>) {
+    if st.cache.limit.is_none() { return } ;
+    if st.cache.policy.forget(loc) { st.cache.size -= 1 } ;
+}
+
+// Performs one round of collection; returns the number of nodes freed.
+// A node is a root when its table key has more strong references than
+// the engine itself holds (viz., in preds, succs and the stack); such
+// references come from `Art`s and `MutArt`s held outside the DCG, or
+// stored within the arguments and results of other nodes.
+fn collect_round(st:&mut Engine) -> usize {
+    let mut held : HashMap<*const Loc, usize> = HashMap::new() ;
+    {
+        let mut hold = |loc:&Arc<Loc>| { *held.entry(&**loc as *const Loc).or_insert(0) += 1 } ;
+        for frame in st.stack.iter() {
+            hold(&frame.loc) ;
+            for succ in frame.succs.iter() { hold(&succ.loc) }
+        } ;
+        for (_, node) in st.table.iter_mut() {
+            for (_, pred) in node.preds() { hold(&pred) } ;
+            if node.succs_def() {
+                for succ in node.succs_mut().iter() { hold(&succ.loc) }
+            }
+        } ;
+        // The prior states logged for `rollback` are also internal.
+        for undo in st.undo.log.iter_mut() {
+            match *undo {
+                Undo::Node(ref loc, None) => hold(loc),
+                Undo::Node(ref loc, Some(ref mut node)) => {
+                    hold(loc) ;
+                    for (_, pred) in node.preds() { hold(&pred) } ;
+                    if node.succs_def() {
+                        for succ in node.succs_mut().iter() { hold(&succ.loc) }
+                    }
                 },
-                None => {
-                    let node = Node::Mut(MutNode{
-                        preds:Vec::new(),
-                        val:val.clone(),
-                    }) ;
-                    self.table.insert(loc.clone(), Box::new(node));
+                Undo::Root(ref succs) => for succ in succs.iter() { hold(&succ.loc) },
+            }
+        }
+    } ;
+    // Roots: nodes with outside handles, and the nodes being produced
+    // by the frames above the root frame (whose succs are never re-used).
+    let mut todo : Vec<Arc<Loc>> = Vec::new() ;
+    for (loc, _) in st.table.iter() {
+        let internal = 1 + match held.get(&(&**loc as *const Loc)) { None => 0, Some(n) => *n } ;
+        if Arc::strong_count(loc) > internal { todo.push(loc.clone()) }
+    } ;
+    for frame in st.stack.iter().skip(1) {
+        todo.push(frame.loc.clone()) ;
+        for succ in frame.succs.iter() { todo.push(succ.loc.clone()) }
+    } ;
+    // Mark: everything reachable from a root, via succ edges, is live.
+    let mut live : HashSet<Arc<Loc>> = HashSet::new() ;
+    while let Some(loc) = todo.pop() {
+        if live.contains(&loc) { continue } ;
+        let succs : Vec<Arc<Loc>> = match st.table.get_mut(&loc) {
+            None => Vec::new(),
+            Some(node) => if node.succs_def() {
+                node.succs_mut().iter().map(|succ| succ.loc.clone()).collect()
+            } else { Vec::new() }
+        } ;
+        live.insert(loc) ;
+        todo.extend(succs)
+    } ;
+    // Sweep: unlink the succ edges of each dead node, then free it.
+    let dead : HashSet<Arc<Loc>> =
+        st.table.keys().filter(|loc| !live.contains(*loc)).map(|loc| loc.clone()).collect() ;
+    drop(live) ;
+    for loc in dead.iter() {
+        info!("{} collect: freeing {:?}", engineMsg!(st), loc);
+        let mut node = match table_remove(st, loc) { None => unreachable!(), Some(node) => node } ;
+        if node.res_clear() { cache_forget(st, loc) } ;
+        if node.succs_def() {
+            st.cnt.edge_remove += node.succs_mut().len() ;
+            for succ in node.succs_mut().iter() {
+                match table_get_mut(st, &succ.loc) { None => (), Some(succ_node) => succ_node.preds_remove(loc) }
+            }
+        }
+    } ;
+    undo_root(st) ;
+    st.stack[0].succs.retain(|succ| !dead.contains(&succ.loc)) ;
+    dead.len()
+}
+
+// ---------- Memory accounting:
+
+// The estimated memory of a node, in bytes; see `MemoryReport`.
+struct NodeMemory {
+    node   : usize,
+    succs  : usize,
+    preds  : usize,
+    result : usize,
+}
+
+// The size of an Rc (or Arc) allocation holding a T (with its two counts).
+fn rc_size<T>() -> usize { 2 * size_of::<usize>() + size_of::<T>() }
+
+fn succs_memory(succs:&Vec<Succ>) -> usize {
+    succs.iter().fold(succs.capacity() * size_of::<Succ>(), |sum, succ| {
+        sum + rc_size::<Box<EngineDep>>() + size_of_val(&**succ.dep)
+    })
+}
+
+// Paths and NameSyms are shared; seen holds the addresses of the
+// allocations already counted.
+fn path_memory(path:&Arc<Path>, seen:&mut HashSet<usize>) -> usize {
+    if !seen.insert(&**path as *const Path as usize) { return 0 } ;
+    rc_size::<Path>() + match **path {
+        Path::Empty => 0,
+        Path::Child(ref parent, ref name, _) => path_memory(parent, seen) + name_sym_memory(&name.symbol, seen),
+    }
+}
+
+fn name_sym_memory(sym:&Arc<NameSym>, seen:&mut HashSet<usize>) -> usize {
+    if !seen.insert(&**sym as *const NameSym as usize) { return 0 } ;
+    rc_size::<NameSym>() + match **sym {
+        NameSym::String(ref s) => s.capacity(),
+        NameSym::Pair(ref fst, ref snd) => name_sym_memory(fst, seen) + name_sym_memory(snd, seen),
+        NameSym::ForkL(ref sym) | NameSym::ForkR(ref sym) => name_sym_memory(sym, seen),
+        NameSym::Root | NameSym::Usize(_) => 0,
+    }
+}
+
+// ---------- Export of the DCG, for debugging:
+
+// A node, as exported by `Engine::export_dot` and `Engine::export_json`.
+struct ExportNode {
+    loc     : Arc<Loc>,
+    kind    : &'static str,
+    prog_pt : Option<ProgPt>,
+    succs   : Vec<(Effect,bool,Arc<Loc>)>, // Effect, dirty bit and target of each succ edge
+}
+
+// Gathers the nodes of the DCG, or only those reachable from `from`
+// via succ edges, ordered by location hash (for stable output).
+fn export_nodes(st:&mut Engine, from:Option<&Arc<Loc>>) -> Vec<ExportNode> {
+    let locs : Vec<Arc<Loc>> = match from {
+        None => st.table.keys().cloned().collect(),
+        Some(loc) => {
+            let mut seen : HashSet<Arc<Loc>> = HashSet::new() ;
+            let mut todo = vec![loc.clone()] ;
+            while let Some(loc) = todo.pop() {
+                if seen.contains(&loc) { continue } ;
+                match st.table.get_mut(&loc) {
+                    None => continue,
+                    Some(node) => if node.succs_def() {
+                        todo.extend(node.succs_mut().iter().map(|succ| succ.loc.clone()))
+                    }
+                } ;
+                seen.insert(loc) ;
+            } ;
+            seen.into_iter().collect()
+        }
+    } ;
+    let mut nodes : Vec<ExportNode> = locs.into_iter().map(|loc| {
+        let node = match st.table.get_mut(&loc) { None => unreachable!(), Some(node) => node } ;
+        let succs = if node.succs_def() {
+            node.succs_mut().iter().map(|succ| (succ.effect.clone(), succ.dirty, succ.loc.clone())).collect()
+        } else { Vec::new() } ;
+        ExportNode{ loc:loc.clone(), kind:node.variant(), prog_pt:node.prog_pt(), succs:succs }
+    }).collect() ;
+    nodes.sort_by(|a, b| a.loc.hash.cmp(&b.loc.hash)) ;
+    nodes
+}
+
+fn path_string(path:&Path) -> String {
+    match *path {
+        Path::Empty => String::new(),
+        Path::Child(ref parent, ref name, _) => format!("{}/{:?}", path_string(parent), name),
+    }
+}
+
+// Quotes s as a JSON string literal.
+fn quote(s:&str) -> String {
+    let mut q = String::from("\"") ;
+    for c in s.chars() {
+        match c {
+            '"'  => q.push_str("\\\""),
+            '\\' => q.push_str("\\\\"),
+            '\n' => q.push_str("\\n"),
+            c if (c as u32) < 0x20 => q.push_str(&format!("\\u{:04x}", c as u32)),
+            c => q.push(c),
+        }
+    } ;
+    q.push('"') ;
+    q
+}
+
+// Quotes s as a DOT label.  DOT strings escape only quotes; labels
+// also read backslashes as escapes, of which `\n` is a line break.
+// Other text, including non-ASCII, is written as raw UTF-8; other
+// control characters become spaces.
+fn quote_dot(s:&str) -> String {
+    let mut q = String::from("\"") ;
+    for c in s.chars() {
+        match c {
+            '"'  => q.push_str("\\\""),
+            '\\' => q.push_str("\\\\"),
+            '\n' => q.push_str("\\n"),
+            c if (c as u32) < 0x20 => q.push(' '),
+            c => q.push(c),
+        }
+    } ;
+    q.push('"') ;
+    q
+}
+
+fn write_dot(out:&mut io::Write, nodes:&Vec<ExportNode>) -> io::Result<()> {
+    try!(writeln!(out, "digraph dcg {{")) ;
+    for node in nodes.iter() {
+        let prog_pt = match node.prog_pt { None => "", Some(ref prog_pt) => prog_pt.symbol } ;
+        let label = format!("{}\n{}\n{}\n{:?}", node.kind, prog_pt, path_string(&node.loc.path), node.loc.id) ;
+        let shape = match node.kind { "Mut" => "box", "Pure" => "diamond", _ => "ellipse" } ;
+        try!(writeln!(out, "  n{} [label={}, shape={}];", node.loc.hash, quote_dot(&label), shape)) ;
+    } ;
+    for node in nodes.iter() {
+        for &(ref effect, dirty, ref tgt) in node.succs.iter() {
+            try!(writeln!(out, "  n{} -> n{} [label={}{}];", node.loc.hash, tgt.hash,
+                          quote_dot(&format!("{:?}", effect)),
+                          if dirty { ", color=red, style=dashed" } else { "" }))
+        }
+    } ;
+    writeln!(out, "}}")
+}
+
+fn write_json(out:&mut io::Write, nodes:&Vec<ExportNode>) -> io::Result<()> {
+    try!(write!(out, "{{\"nodes\":[")) ;
+    for (i, node) in nodes.iter().enumerate() {
+        let prog_pt = match node.prog_pt { None => "null".to_string(), Some(ref prog_pt) => quote(prog_pt.symbol) } ;
+        try!(write!(out, "{}{{\"id\":\"{}\",\"kind\":{},\"prog_pt\":{},\"path\":{},\"art_id\":{}}}",
+                    if i == 0 { "" } else { "," }, node.loc.hash, quote(node.kind), prog_pt,
+                    quote(&path_string(&node.loc.path)), quote(&format!("{:?}", node.loc.id))))
+    } ;
+    try!(write!(out, "],\"edges\":[")) ;
+    let mut first = true ;
+    for node in nodes.iter() {
+        for &(ref effect, dirty, ref tgt) in node.succs.iter() {
+            try!(write!(out, "{}{{\"src\":\"{}\",\"tgt\":\"{}\",\"effect\":\"{:?}\",\"dirty\":{}}}",
+                        if first { "" } else { "," }, node.loc.hash, tgt.hash, effect, dirty)) ;
+            first = false
+        }
+    } ;
+    writeln!(out, "]}}")
+}
+
+// ---------- Checkpoints (see `Engine::checkpoint` and `Engine::rollback`):
+
+// While a checkpoint is live, the first change to each node (and to the
+// outer layer's edges) since the latest checkpoint logs its prior
+// state.  A rollback restores the logged states, newest first, so
+// that it costs time in the number of nodes changed since the
+// checkpoint, and re-executes nothing.
+struct UndoLog {
+    log  : Vec<Undo>,
+    seen : HashSet<Loc>, // Logged since the latest checkpoint; the root's Loc stands for the outer layer's edges.
+    live : Vec<(usize, usize, rc::Weak<()>)>, // Checkpoints, oldest first: their ids, log lengths and handles.
+    next : usize, // The id of the next checkpoint.
+}
+impl Debug for UndoLog {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "UndoLog{{log:{}, live:{}}}", self.log.len(), self.live.len()) }
+}
+
+enum Undo {
+    Node(Arc<Loc>, Option<Box<GraphNode>>), // The node at loc; None when absent.
+    Root(Vec<Succ>),                       // The succs of the outer layer.
+}
+
+/// A state of an `Engine`, to which `Engine::rollback` returns it.
+/// The engine logs changes while a checkpoint is live; dropping every
+/// checkpoint stops the logging, and discards the log.
+#[derive(Debug)]
+pub struct Checkpoint {
+    id     : usize,
+    handle : Rc<()>, // The engine holds a `Weak` reference, to detect drops.
+}
+
+// True when a checkpoint is live.  Forgets the dropped checkpoints
+// that are newer than every live one.
+fn undo_active(st:&mut Engine) -> bool {
+    while match st.undo.live.last() { None => false, Some(&(_, _, ref handle)) => handle.upgrade().is_none() } {
+        st.undo.live.pop() ;
+    } ;
+    if st.undo.live.is_empty() && !st.undo.log.is_empty() {
+        st.undo.log.clear() ;
+        st.undo.seen.clear()
+    } ;
+    !st.undo.live.is_empty()
+}
+
+// Logs the node at loc (or its absence), before a change to it.
+fn undo_node(st:&mut Engine, loc:&Arc<Loc>) {
+    if !undo_active(st) || st.undo.seen.contains(&**loc) { return } ;
+    // Log the table key, which outside handles share (see `canonical_loc`).
+    let undo = match st.table.entry(loc.clone()) {
+        Entry::Occupied(e) => Undo::Node(e.key().clone(), Some(e.get().copy_node())),
+        Entry::Vacant(_)   => Undo::Node(loc.clone(), None),
+    } ;
+    st.undo.seen.insert((**loc).clone()) ;
+    st.undo.log.push(undo)
+}
+
+// Logs the succs of the outer layer, before a change to them.
+fn undo_root(st:&mut Engine) {
+    if !undo_active(st) || st.undo.seen.contains(&*st.root) { return } ;
+    let root = (*st.root).clone() ;
+    st.undo.seen.insert(root) ;
+    let succs = st.stack[0].succs.clone() ;
+    st.undo.log.push(Undo::Root(succs))
+}
+
+// Each change to the table goes through these, for `rollback`.
+fn table_get_mut<'r>(st:&'r mut Engine, loc:&Arc<Loc>) -> Option<&'r mut Box<GraphNode>> {
+    undo_node(st, loc) ;
+    st.table.get_mut(loc)
+}
+fn table_insert(st:&mut Engine, loc:Arc<Loc>, node:Box<GraphNode>) {
+    undo_node(st, &loc) ;
+    st.table.insert(loc, node) ;
+}
+fn table_remove(st:&mut Engine, loc:&Arc<Loc>) -> Option<Box<GraphNode>> {
+    undo_node(st, loc) ;
+    st.table.remove(loc)
+}
+
+fn checkpoint_layer(st:&Engine) -> AdaptonResult<()> {
+    if is_outer_layer(st) { return Ok(()) } ;
+    let thunk = match st.stack.last() { None => unreachable!(), Some(frame) => format!("{:?}", frame.loc) } ;
+    Err(AdaptonError::CheckpointInInnerLayer{thunk:thunk})
+}
+
+fn do_checkpoint(st:&mut Engine) -> AdaptonResult<Checkpoint> {
+    try!(checkpoint_layer(st)) ;
+    undo_active(st) ;
+    let handle = Rc::new(()) ;
+    let id = st.undo.next ;
+    st.undo.next += 1 ;
+    st.undo.live.push((id, st.undo.log.len(), Rc::downgrade(&handle))) ;
+    st.undo.seen.clear() ;
+    info!("{} checkpoint: {}", engineMsg!(st), id);
+    Ok(Checkpoint{id:id, handle:handle})
+}
+
+fn do_rollback(st:&mut Engine, checkpoint:Checkpoint) -> AdaptonResult<()> {
+    try!(checkpoint_layer(st)) ;
+    let pos = match st.undo.live.iter().position(|&(id, _, _)| id == checkpoint.id) {
+        None => return Err(AdaptonError::StaleCheckpoint),
+        Some(pos) => pos,
+    } ;
+    let mark = st.undo.live[pos].1 ;
+    // Later checkpoints are rolled back too.
+    st.undo.live.truncate(pos) ;
+    while st.undo.log.len() > mark {
+        match st.undo.log.pop() {
+            None => unreachable!(),
+            Some(Undo::Root(succs)) => st.stack[0].succs = succs,
+            Some(Undo::Node(loc, node)) => {
+                let was_cached = match st.table.remove(&loc) { None => false, Some(node) => node.res_def() } ;
+                let is_cached = match node {
+                    None => false,
+                    Some(node) => { let is_cached = node.res_def() ; st.table.insert(loc.clone(), node) ; is_cached }
+                } ;
+                if was_cached && !is_cached { cache_forget(st, &loc) } ;
+                if is_cached && !was_cached && st.cache.limit.is_some() {
+                    // Restored results count toward the limit, without evictions here.
+                    st.cache.policy.touch(&loc, CacheEvent::Produce) ;
+                    st.cache.size += 1
+                }
+            },
+        }
+    } ;
+    st.undo.seen.clear() ;
+    {   // Roots whose nodes are gone (viz., new since the checkpoint) are gone too.
+        let table = &st.table ;
+        st.roots.retain(|root| table.contains_key(&root.loc))
+    } ;
+    // The recorded explanations may describe undone changes.
+    match st.reasons { None => (), Some(ref mut reasons) => reasons.clear() } ;
+    info!("{} rollback: {}", engineMsg!(st), checkpoint.id);
+    Ok(())
+}
+
+// ---------- Persistence of the DCG (see `Engine::save` and `Engine::load`):
+
+/// Encodes and decodes the values that `Engine::save` persists: the
+/// arguments, spurious arguments and results of thunks, and the
+/// values of cells.
+pub trait Codec : Sized {
+    fn encode (self:&Self, enc:&mut Encoder) ;
+    fn decode (dec:&mut Decoder) -> io::Result<Self> ;
+}
+
+/// The output of `Codec::encode`.
+pub struct Encoder {
+    bytes : Vec<u8>,
+}
+
+impl Encoder {
+    pub fn write_u64 (self:&mut Self, n:u64) {
+        for i in 0..8 { self.bytes.push((n >> (8 * i)) as u8) }
+    }
+    /// Writes bytes, preceded by their length.
+    pub fn write_bytes (self:&mut Self, bytes:&[u8]) {
+        self.write_u64(bytes.len() as u64) ;
+        self.bytes.extend_from_slice(bytes)
+    }
+}
+
+/// The input of `Codec::decode`.
+pub struct Decoder<'a> {
+    bytes : &'a [u8],
+    locs  : HashMap<Loc,Arc<Loc>>, // One `Arc` per location, as in the table (see `canonical_loc`).
+    st    : &'a mut Engine,        // Interns the names and paths, and checks them for collisions.
+}
+
+impl<'a> Decoder<'a> {
+    pub fn read_u64 (self:&mut Self) -> io::Result<u64> {
+        if self.bytes.len() < 8 { return Err(persist_error("load: unexpected end of input".to_string())) } ;
+        let mut n = 0 ;
+        for i in 0..8 { n |= (self.bytes[i] as u64) << (8 * i) } ;
+        self.bytes = &self.bytes[8..] ;
+        Ok(n)
+    }
+    /// Reads bytes written by `Encoder::write_bytes`.
+    pub fn read_bytes (self:&mut Self) -> io::Result<Vec<u8>> {
+        let len = try!(self.read_u64()) as usize ;
+        if self.bytes.len() < len { return Err(persist_error("load: unexpected end of input".to_string())) } ;
+        let bytes = self.bytes[..len].to_vec() ;
+        self.bytes = &self.bytes[len..] ;
+        Ok(bytes)
+    }
+    fn loc (self:&mut Self) -> io::Result<Arc<Loc>> {
+        let loc = try!(Loc::decode(self)) ;
+        try!(check_loc(self.st, &loc).map_err(load_error)) ;
+        Ok(self.locs.entry(loc.clone()).or_insert_with(move || Arc::new(loc)).clone())
+    }
+    fn path (self:&mut Self) -> io::Result<Arc<Path>> {
+        match try!(self.read_u64()) {
+            0 => Ok(Arc::new(Path::Empty)),
+            _ => { let parent = try!(self.path()) ;
+                   let name = try!(Name::decode(self)) ;
+                   intern_path(self.st, parent, name).map_err(load_error) },
+        }
+    }
+}
+
+fn persist_error(msg:String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn load_error(err:AdaptonError) -> io::Error {
+    persist_error(format!("load: {}", err))
+}
+
+macro_rules! codec_int {
+    ( $( $t:ty ),* ) => { $(
+        impl Codec for $t {
+            fn encode (self:&Self, enc:&mut Encoder) { enc.write_u64(*self as u64) }
+            fn decode (dec:&mut Decoder) -> io::Result<Self> { Ok(try!(dec.read_u64()) as $t) }
+        }
+    )* }
+}
+codec_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize) ;
+
+macro_rules! codec_tuple {
+    ( $( $t:ident ),+ ) => {
+        impl<$( $t:Codec ),+> Codec for ( $( $t, )+ ) {
+            #[allow(non_snake_case)]
+            fn encode (self:&Self, enc:&mut Encoder) {
+                let ( $( ref $t, )+ ) = *self ;
+                $( $t.encode(enc) ; )+
+            }
+            fn decode (dec:&mut Decoder) -> io::Result<Self> {
+                Ok(( $( try!($t::decode(dec)), )+ ))
+            }
+        }
+    }
+}
+codec_tuple!(A) ;
+codec_tuple!(A, B) ;
+codec_tuple!(A, B, C) ;
+codec_tuple!(A, B, C, D) ;
+codec_tuple!(A, B, C, D, E) ;
+
+impl Codec for () {
+    fn encode (self:&Self, _enc:&mut Encoder) { }
+    fn decode (_dec:&mut Decoder) -> io::Result<Self> { Ok(()) }
+}
+
+impl Codec for bool {
+    fn encode (self:&Self, enc:&mut Encoder) { enc.write_u64(if *self { 1 } else { 0 }) }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> { Ok(try!(dec.read_u64()) != 0) }
+}
+
+impl Codec for String {
+    fn encode (self:&Self, enc:&mut Encoder) { enc.write_bytes(self.as_bytes()) }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        String::from_utf8(try!(dec.read_bytes())).map_err(|_| persist_error("load: invalid UTF-8".to_string()))
+    }
+}
+
+impl<T:Codec> Codec for Vec<T> {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        enc.write_u64(self.len() as u64) ;
+        for x in self.iter() { x.encode(enc) }
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        let len = try!(dec.read_u64()) ;
+        let mut xs = Vec::new() ;
+        for _ in 0..len { xs.push(try!(T::decode(dec))) } ;
+        Ok(xs)
+    }
+}
+
+impl<T:Codec> Codec for Option<T> {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        match *self { None => enc.write_u64(0), Some(ref x) => { enc.write_u64(1) ; x.encode(enc) } }
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        match try!(dec.read_u64()) { 0 => Ok(None), _ => Ok(Some(try!(T::decode(dec)))) }
+    }
+}
+
+impl<T:Codec> Codec for Art<T,Loc> {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        match *self {
+            Art::Rc(ref x)    => { enc.write_u64(0) ; (**x).encode(enc) },
+            Art::Loc(ref loc) => { enc.write_u64(1) ; (**loc).encode(enc) },
+        }
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        match try!(dec.read_u64()) {
+            0 => Ok(Art::Rc(Arc::new(try!(T::decode(dec))))),
+            _ => Ok(Art::Loc(try!(dec.loc()))),
+        }
+    }
+}
+
+impl<T> Codec for MutArt<T,Loc> {
+    fn encode (self:&Self, enc:&mut Encoder) { (*self.loc).encode(enc) }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        Ok(MutArt{ loc:try!(dec.loc()), phantom:PhantomData })
+    }
+}
+
+impl Codec for Fingerprint {
+    fn encode (self:&Self, enc:&mut Encoder) { enc.write_u64(self.0) ; enc.write_u64(self.1) }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        let hash0 = try!(dec.read_u64()) ;
+        Ok(Fingerprint(hash0, try!(dec.read_u64())))
+    }
+}
+
+impl Codec for NameSym {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        match *self {
+            NameSym::Root              => enc.write_u64(0),
+            NameSym::String(ref s)     => { enc.write_u64(1) ; s.encode(enc) },
+            NameSym::Usize(n)          => { enc.write_u64(2) ; n.encode(enc) },
+            NameSym::Pair(ref l,ref r) => { enc.write_u64(3) ; l.encode(enc) ; r.encode(enc) },
+            NameSym::ForkL(ref sym)    => { enc.write_u64(4) ; sym.encode(enc) },
+            NameSym::ForkR(ref sym)    => { enc.write_u64(5) ; sym.encode(enc) },
+        }
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        match try!(dec.read_u64()) {
+            0 => Ok(NameSym::Root),
+            1 => Ok(NameSym::String(try!(String::decode(dec)))),
+            2 => Ok(NameSym::Usize(try!(usize::decode(dec)))),
+            3 => { let l = try!(NameSym::decode(dec)) ;
+                   let r = try!(NameSym::decode(dec)) ;
+                   Ok(NameSym::Pair(Arc::new(l), Arc::new(r))) },
+            4 => Ok(NameSym::ForkL(Arc::new(try!(NameSym::decode(dec))))),
+            5 => Ok(NameSym::ForkR(Arc::new(try!(NameSym::decode(dec))))),
+            tag => Err(persist_error(format!("load: invalid name symbol: {}", tag))),
+        }
+    }
+}
+
+impl Codec for Name {
+    fn encode (self:&Self, enc:&mut Encoder) { self.hash.encode(enc) ; self.symbol.encode(enc) }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        let hash = try!(Fingerprint::decode(dec)) ;
+        let sym  = try!(NameSym::decode(dec)) ;
+        intern_name(dec.st, hash, sym).map_err(load_error)
+    }
+}
+
+// Paths decode by `Decoder::path`, which interns them.
+fn encode_path(path:&Path, enc:&mut Encoder) {
+    match *path {
+        Path::Empty => enc.write_u64(0),
+        Path::Child(ref path, ref name, _) => { enc.write_u64(1) ; encode_path(path, enc) ; name.encode(enc) },
+    }
+}
+
+impl Codec for Loc {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        self.hash.encode(enc) ;
+        encode_path(&self.path, enc) ;
+        match *self.id {
+            ArtId::Structural(hash) => { enc.write_u64(0) ; hash.encode(enc) },
+            ArtId::Nominal(ref name) => { enc.write_u64(1) ; name.encode(enc) },
+        }
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        let hash = try!(Fingerprint::decode(dec)) ;
+        let path = try!(dec.path()) ;
+        let id = match try!(dec.read_u64()) {
+            0 => ArtId::Structural(try!(Fingerprint::decode(dec))),
+            _ => ArtId::Nominal(try!(Name::decode(dec))),
+        } ;
+        Ok(Loc{ hash:hash, path:path, id:Arc::new(id) })
+    }
+}
+
+impl Codec for Effect {
+    fn encode (self:&Self, enc:&mut Encoder) {
+        enc.write_u64(match *self { Effect::Observe => 0, Effect::Allocate => 1, Effect::Write => 2 })
+    }
+    fn decode (dec:&mut Decoder) -> io::Result<Self> {
+        match try!(dec.read_u64()) {
+            0 => Ok(Effect::Observe),
+            1 => Ok(Effect::Allocate),
+            2 => Ok(Effect::Write),
+            tag => Err(persist_error(format!("load: invalid effect: {}", tag))),
+        }
+    }
+}
+
+/// Binds the `ProgPt` of each thunk to its producer, and each type of
+/// value to its `Codec`, for `Engine::save` and `Engine::load`.
+/// Types are identified by name, so that a DCG saved by one run of a
+/// program can be loaded by another run of the same program.
+pub struct Registry {
+    thunks : HashMap<&'static str, Box<ThunkCodec>>, // by ProgPt symbol
+    values : HashMap<&'static str, Box<ValueCodec>>, // by type name
+}
+
+impl Registry {
+    pub fn new () -> Registry {
+        Registry{ thunks:HashMap::new(), values:HashMap::new() }
+    }
+
+    /// Registers the values of type `T`, held by cells.
+    pub fn value<T:'static+Codec+Eq+Debug+Clone> (self:&mut Self) {
+        self.values.insert(NodeType::of::<T>().name, Box::new(ValueEntry::<T>{ phantom:PhantomData })) ;
+    }
+
+    /// Registers the producer of the thunks at `prog_pt`, and the type
+    /// of their results; `register_thunk!` registers the producers
+    /// that `thunk!` makes.
+    pub fn thunk<Arg:'static+Codec+Eq+Debug+Clone,
+                 Spurious:'static+Codec+Clone,
+                 Res:'static+Codec+Eq+Debug+Clone>
+        (self:&mut Self,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>)
+    {
+        self.value::<Res>() ;
+        self.thunks.insert(prog_pt.symbol, Box::new(ThunkEntry{ prog_pt:prog_pt, fn_box:fn_box })) ;
+    }
+}
+
+// The codec of the thunks at one ProgPt.
+trait ThunkCodec {
+    fn encode (self:&Self, node:&mut GraphNode, enc:&mut Encoder) -> io::Result<()> ;
+    fn decode (self:&Self, dec:&mut Decoder, preds:Vec<(Effect,Arc<Loc>)>, succs:Vec<Succ>) -> io::Result<Box<GraphNode>> ;
+}
+
+// The codec of the values of one type: of cells, and of the
+// dependencies of the edges that observe or write them.
+trait ValueCodec {
+    fn encode_node (self:&Self, node:&mut GraphNode, enc:&mut Encoder) -> io::Result<()> ;
+    fn decode_node (self:&Self, dec:&mut Decoder, variant:u64, preds:Vec<(Effect,Arc<Loc>)>) -> io::Result<Box<GraphNode>> ;
+    fn encode_dep  (self:&Self, dep:&EngineDep, enc:&mut Encoder) -> io::Result<()> ;
+    fn decode_dep  (self:&Self, dec:&mut Decoder, effect:&Effect) -> io::Result<Rc<Box<EngineDep>>> ;
+}
+
+struct ThunkEntry<Arg,Spurious,Res> {
+    prog_pt : ProgPt,
+    fn_box  : Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+}
+
+impl<Arg:'static+Codec+Eq+Debug+Clone,Spurious:'static+Codec+Clone,Res:'static+Codec+Eq+Debug+Clone>
+    ThunkCodec for ThunkEntry<Arg,Spurious,Res>
+{
+    fn encode (self:&Self, node:&mut GraphNode, enc:&mut Encoder) -> io::Result<()> {
+        match node.as_any().downcast_mut::<Node<Res>>() {
+            Some(&mut Node::Comp(ref nd)) => match nd.producer.as_any().downcast_ref::<App<Arg,Spurious,Res>>() {
+                Some(app) => {
+                    app.arg.encode(enc) ;
+                    app.spurious.encode(enc) ;
+                    nd.res.encode(enc) ;
+                    Ok(())
                 },
+                None => Err(persist_error(format!("save: {:?} is registered at other argument types", self.prog_pt))),
+            },
+            _ => Err(persist_error(format!("save: {:?} is registered at another result type", self.prog_pt))),
+        }
+    }
+    fn decode (self:&Self, dec:&mut Decoder, preds:Vec<(Effect,Arc<Loc>)>, succs:Vec<Succ>) -> io::Result<Box<GraphNode>> {
+        let arg      = try!(Arg::decode(dec)) ;
+        let spurious = try!(Spurious::decode(dec)) ;
+        let res      = try!(Option::<Res>::decode(dec)) ;
+        let producer : App<Arg,Spurious,Res> =
+            App{prog_pt:self.prog_pt.clone(),
+                fn_box:self.fn_box.clone(),
+                arg:arg,
+                spurious:spurious} ;
+        let node : CompNode<Res> = CompNode{
+            preds:preds,
+            succs:succs,
+            producer:Box::new(producer),
+            res:res,
+            running:false,
+            cycle:None,
+            res_eq:None,
+        } ;
+        Ok(Box::new(Node::Comp(node)))
+    }
+}
+
+struct ValueEntry<T> {
+    phantom : PhantomData<T>,
+}
+
+impl<T:'static+Codec+Eq+Debug+Clone> ValueCodec for ValueEntry<T> {
+    fn encode_node (self:&Self, node:&mut GraphNode, enc:&mut Encoder) -> io::Result<()> {
+        match node.as_any().downcast_mut::<Node<T>>() {
+            Some(&mut Node::Mut(ref nd))  => { nd.val.encode(enc) ; Ok(()) },
+            Some(&mut Node::Pure(ref nd)) => { nd.val.encode(enc) ; Ok(()) },
+            _ => Err(persist_error(format!("save: expected a cell of type {}", NodeType::of::<T>().name))),
+        }
+    }
+    fn decode_node (self:&Self, dec:&mut Decoder, variant:u64, preds:Vec<(Effect,Arc<Loc>)>) -> io::Result<Box<GraphNode>> {
+        let val = try!(T::decode(dec)) ;
+        let node : Node<T> =
+            if variant == PERSIST_MUT { Node::Mut(MutNode{ preds:preds, val:val }) }
+            else { Node::Pure(PureNode{ val:val }) } ;
+        Ok(Box::new(node))
+    }
+    fn encode_dep (self:&Self, dep:&EngineDep, enc:&mut Encoder) -> io::Result<()> {
+        match dep.as_any().downcast_ref::<ProducerDep<T>>() {
+            Some(dep) => { dep.res.encode(enc) ; return Ok(()) },
+            None => (),
+        } ;
+        match dep.as_any().downcast_ref::<WriteDependency<T>>() {
+            Some(dep) => { dep.val.encode(enc) ; Ok(()) },
+            None => Err(persist_error(format!("save: expected a dependency on a value of type {}", NodeType::of::<T>().name))),
+        }
+    }
+    fn decode_dep (self:&Self, dec:&mut Decoder, effect:&Effect) -> io::Result<Rc<Box<EngineDep>>> {
+        let val = try!(T::decode(dec)) ;
+        let dep : Box<EngineDep> = match *effect {
+            Effect::Write => Box::new(WriteDependency{val:val}),
+            _ => Box::new(ProducerDep{res:val}),
+        } ;
+        Ok(Rc::new(dep))
+    }
+}
+
+// Bump the version whenever the format changes.
+const PERSIST_MAGIC : &'static [u8] = b"adapton-dcg-2" ;
+// Node variants:
+const PERSIST_COMP : u64 = 0 ;
+const PERSIST_MUT  : u64 = 1 ;
+const PERSIST_PURE : u64 = 2 ;
+// Dependencies of succ edges:
+const PERSIST_NO_DEP    : u64 = 0 ; // NoDependency
+const PERSIST_ALLOC_DEP : u64 = 1 ; // AllocDependency, whose value is not used
+const PERSIST_VALUE_DEP : u64 = 2 ; // ProducerDep or WriteDependency, by the type name of its value
+
+fn value_codec<'r>(registry:&'r Registry, type_name:&str) -> io::Result<&'r Box<ValueCodec>> {
+    match registry.values.get(type_name) {
+        None => Err(persist_error(format!("no codec is registered for type {}", type_name))),
+        Some(codec) => Ok(codec),
+    }
+}
+
+fn encode_preds(preds:&Vec<(Effect,Arc<Loc>)>, enc:&mut Encoder) {
+    enc.write_u64(preds.len() as u64) ;
+    for &(ref effect, ref loc) in preds.iter() { effect.encode(enc) ; (**loc).encode(enc) }
+}
+
+fn decode_preds(dec:&mut Decoder) -> io::Result<Vec<(Effect,Arc<Loc>)>> {
+    let len = try!(dec.read_u64()) ;
+    let mut preds = Vec::new() ;
+    for _ in 0..len {
+        let effect = try!(Effect::decode(dec)) ;
+        preds.push((effect, try!(dec.loc())))
+    } ;
+    Ok(preds)
+}
+
+fn encode_succ(succ:&Succ, types:&HashMap<Arc<Loc>,&'static str>, registry:&Registry, enc:&mut Encoder) -> io::Result<()> {
+    succ.effect.encode(enc) ;
+    (*succ.loc).encode(enc) ;
+    succ.dirty.encode(enc) ;
+    if succ.dep.as_any().is::<NoDependency>() { enc.write_u64(PERSIST_NO_DEP) ; return Ok(()) } ;
+    if succ.effect == Effect::Allocate { enc.write_u64(PERSIST_ALLOC_DEP) ; return Ok(()) } ;
+    let type_name = match types.get(&succ.loc) {
+        None => return Err(persist_error(format!("save: dangling edge to {:?}", succ.loc))),
+        Some(type_name) => *type_name,
+    } ;
+    let codec = try!(value_codec(registry, type_name)) ;
+    enc.write_u64(PERSIST_VALUE_DEP) ;
+    type_name.to_string().encode(enc) ;
+    codec.encode_dep(&**succ.dep, enc)
+}
+
+fn decode_succ(dec:&mut Decoder, registry:&Registry) -> io::Result<Succ> {
+    let effect = try!(Effect::decode(dec)) ;
+    let loc    = try!(dec.loc()) ;
+    let dirty  = try!(bool::decode(dec)) ;
+    let dep : Rc<Box<EngineDep>> = match try!(dec.read_u64()) {
+        PERSIST_NO_DEP    => Rc::new(Box::new(NoDependency)),
+        PERSIST_ALLOC_DEP => Rc::new(Box::new(AllocDependency{val:()})),
+        PERSIST_VALUE_DEP => {
+            let type_name = try!(String::decode(dec)) ;
+            try!(try!(value_codec(registry, &type_name)).decode_dep(dec, &effect))
+        },
+        tag => return Err(persist_error(format!("load: invalid dependency: {}", tag))),
+    } ;
+    Ok(Succ{ effect:effect, dep:dep, loc:loc, dirty:dirty })
+}
+
+// The format: the magic bytes, the number of nodes, and then each
+// node (ordered by location hash): its location, variant, and key (the
+// ProgPt symbol of a thunk, or the type name of a cell), its preds,
+// its succs (thunks only), and then its contents, via its codec.
+fn save_dcg(st:&mut Engine, registry:&Registry) -> io::Result<Vec<u8>> {
+    if ! is_outer_layer(st) { return Err(persist_error("save: thunks are running".to_string())) } ;
+    let types : HashMap<Arc<Loc>,&'static str> =
+        st.table.iter().map(|(loc, node)| (loc.clone(), node.res_type().name)).collect() ;
+    let mut locs : Vec<Arc<Loc>> = st.table.keys().cloned().collect() ;
+    locs.sort_by(|l1, l2| l1.hash.cmp(&l2.hash)) ;
+    let mut enc = Encoder{ bytes:Vec::new() } ;
+    enc.write_bytes(PERSIST_MAGIC) ;
+    enc.write_u64(locs.len() as u64) ;
+    for loc in locs.iter() {
+        let node : &mut Box<GraphNode> = match st.table.get_mut(loc) { None => unreachable!(), Some(node) => node } ;
+        (**loc).encode(&mut enc) ;
+        match node.variant() {
+            "Comp" => {
+                let prog_pt = match node.prog_pt() { None => unreachable!(), Some(prog_pt) => prog_pt } ;
+                let codec = match registry.thunks.get(prog_pt.symbol) {
+                    None => return Err(persist_error(format!("save: no producer is registered for {:?}", prog_pt))),
+                    Some(codec) => codec,
+                } ;
+                enc.write_u64(PERSIST_COMP) ;
+                prog_pt.symbol.to_string().encode(&mut enc) ;
+                encode_preds(&node.preds(), &mut enc) ;
+                let succs = node.succs_mut().clone() ;
+                enc.write_u64(succs.len() as u64) ;
+                for succ in succs.iter() { try!(encode_succ(succ, &types, registry, &mut enc)) } ;
+                try!(codec.encode(&mut **node, &mut enc))
+            },
+            variant => {
+                let type_name = node.res_type().name ;
+                let codec = try!(value_codec(registry, type_name)) ;
+                let preds = if variant == "Mut" { node.preds() } else { Vec::new() } ;
+                enc.write_u64(if variant == "Mut" { PERSIST_MUT } else { PERSIST_PURE }) ;
+                type_name.to_string().encode(&mut enc) ;
+                encode_preds(&preds, &mut enc) ;
+                try!(codec.encode_node(&mut **node, &mut enc))
+            },
+        }
+    } ;
+    Ok(enc.bytes)
+}
+
+fn load_dcg(st:&mut Engine, bytes:&[u8], registry:&Registry) -> io::Result<()> {
+    let mut dec = Decoder{ bytes:bytes, locs:HashMap::new(), st:st } ;
+    if &try!(dec.read_bytes())[..] != PERSIST_MAGIC {
+        return Err(persist_error("load: not a saved DCG".to_string()))
+    } ;
+    let count = try!(dec.read_u64()) ;
+    for _ in 0..count {
+        let loc     = try!(dec.loc()) ;
+        let variant = try!(dec.read_u64()) ;
+        let key     = try!(String::decode(&mut dec)) ;
+        let preds   = try!(decode_preds(&mut dec)) ;
+        let node : Box<GraphNode> =
+            if variant == PERSIST_COMP {
+                let len = try!(dec.read_u64()) ;
+                let mut succs = Vec::new() ;
+                for _ in 0..len { succs.push(try!(decode_succ(&mut dec, registry))) } ;
+                match registry.thunks.get(&*key) {
+                    None => return Err(persist_error(format!("load: no producer is registered for {:?}", key))),
+                    Some(codec) => try!(codec.decode(&mut dec, preds, succs)),
+                }
+            } else {
+                try!(try!(value_codec(registry, &key)).decode_node(&mut dec, variant, preds))
+            } ;
+        let st = &mut *dec.st ;
+        if st.table.contains_key(&loc) {
+            return Err(persist_error(format!("load: {:?} is saved twice", loc)))
+        } ;
+        info!("{} load: {:?}", engineMsg!(st), &loc);
+        let is_cached = node.res_def() ;
+        st.table.insert(loc.clone(), node) ;
+        // Loaded results count toward the cache limit, like produced ones:
+        if is_cached { cache_touch(st, &loc, CacheEvent::Produce, true) } ;
+    } ;
+    Ok(())
+}
+
+impl Engine {
+    /// Creates an engine with the given configuration; `Adapton::new`
+    /// uses `EngineConfig::default()`.
+    pub fn with_config (config:EngineConfig) -> Engine {
+        let root = {
+            let path   = Arc::new(Path::Empty);
+            let symbol = Arc::new(NameSym::Root);
+            let hash   = fingerprint(&symbol);
+            let name   = Name{symbol:symbol,hash:hash};
+            let id     = Arc::new(ArtId::Nominal(name));
+            let hash   = fingerprint(&(&path,&id));
+            let loc    = Arc::new(Loc{path:path.clone(),id:id,hash:hash});
+            loc
+        } ;
+        let mut stack = Vec::new() ;
+        stack.push( Frame{loc:root.clone(),
+                          path:root.path.clone(),
+                          succs:Vec::new()} ) ;
+        let policy : Box<EvictionPolicy> = match config.eviction {
+            Eviction::LeastRecentlyUsed   => Box::new(Recency{forced_only:false, clock:0, stamps:HashMap::new(), order:BTreeMap::new()}),
+            Eviction::LeastRecentlyForced => Box::new(Recency{forced_only:true,  clock:0, stamps:HashMap::new(), order:BTreeMap::new()}),
+            Eviction::Policy(policy)      => policy,
+        } ;
+        Engine {
+            root  : root,
+            table : HashMap::new (),
+            stack : stack,
+            cnt   : Cnt::zero (),
+            cache : Cache{ limit:config.cache_limit, size:0, policy:policy },
+            error : None,
+            observers : Vec::new(),
+            roots : Vec::new(),
+            undo  : UndoLog{ log:Vec::new(), seen:HashSet::new(), live:Vec::new(), next:0 },
+            fingerprints : if config.check_collisions {
+                Some(Fingerprints{ names:HashMap::new(), paths:HashMap::new(), locs:HashMap::new() })
+            } else { None },
+            interned : Interned{ syms:HashMap::new(), paths:HashMap::new() },
+            reasons : if config.record_explanations { Some(HashMap::new()) } else { None },
+        }
+    }
+
+    /// Forces `art`, permitting a cycle: when `art` is a thunk that is
+    /// already running (viz., it forces itself, directly or through
+    /// other thunks), returns an approximation of its result, rather
+    /// than failing with a `Cycle` error.  The approximation is the
+    /// thunk's prior result, or else `init`.  When the thunk finishes
+    /// with another result than the one approximated, it runs again,
+    /// until its result is a fixed point; the producers of the cycle
+    /// must ensure that one exists (e.g., by being monotone over a
+    /// finite domain).  Each force within the cycle should use
+    /// `force_fix`.
+    pub fn force_fix<T:'static+Eq+Debug+Clone> (self:&mut Self, art:&Art<T,Loc>, init:T) -> T {
+        match do_force(self, art, Some(init)) { Ok(res) => res, Err(err) => fail(self, err) }
+    }
+
+    pub fn try_force_fix<T:'static+Eq+Debug+Clone> (self:&mut Self, art:&Art<T,Loc>, init:T) -> AdaptonResult<T> {
+        recover(self, |st| do_force(st, art, Some(init)))
+    }
+
+    /// Estimates the memory that the DCG uses, in bytes: its nodes,
+    /// edges, cached results, names and table keys.
+    pub fn memory_report (self:&Self) -> MemoryReport {
+        let mut report = MemoryReport::zero() ;
+        let mut seen : HashSet<usize> = HashSet::new() ;
+        for (loc, node) in self.table.iter() {
+            let mem = node.memory() ;
+            let key = size_of::<(Arc<Loc>,Box<GraphNode>)>() + size_of::<u64>() // The table slot, and its hash
+                + rc_size::<Loc>() + rc_size::<ArtId<Name>>() ;
+            report.nodes   += mem.node ;
+            report.succs   += mem.succs ;
+            report.preds   += mem.preds ;
+            report.results += mem.result ;
+            report.keys    += key ;
+            report.names   += path_memory(&loc.path, &mut seen) ;
+            match *loc.id {
+                ArtId::Nominal(ref name) => report.names += name_sym_memory(&name.symbol, &mut seen),
+                ArtId::Structural(_) => (),
             } ;
-            let stackLen = self.stack.len() ;
-            match self.stack.last_mut() { None => (), Some(frame) => {
-                let succ =
-                    Succ{loc:loc.clone(),
-                         dep:Rc::new(Box::new(AllocDependency{val:val})),
-                         effect:Effect::Allocate,
-                         dirty:false};
-                info!("{} alloc cell: edge: {:?} --> {:?}", engineMsg(Some(stackLen)), &frame.loc, &loc);
-                frame.succs.push(succ)
-            }} ;
-            MutArt{loc:loc,phantom:PhantomData}
+            let total = mem.node + mem.succs + mem.preds + mem.result + key ;
+            *report.by_kind.entry(node.variant()).or_insert(0) += total ;
+            match node.prog_pt() {
+                None => (),
+                Some(prog_pt) => *report.by_prog_pt.entry(prog_pt.symbol).or_insert(0) += total,
+            }
+        } ;
+        report
+    }
+
+    /// Explains why the node of `art` last changed: when it is a
+    /// thunk that change propagation re-produced, the chain of changed
+    /// edges back to the cell whose `set` started the change, with the
+    /// values of each node before and after.  Requires
+    /// `EngineConfig::record_explanations`; otherwise (or when nothing
+    /// is recorded for `art`) the explanation has no steps.
+    pub fn explain<T> (self:&Self, art:&Art<T,Loc>) -> Explanation {
+        match *art {
+            Art::Rc(_) => Explanation{ steps:Vec::new() },
+            Art::Loc(ref loc) => do_explain(self, loc),
         }
+    }
 
-    fn set<T:Eq+Debug> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) {
-        assert!( self.stack.is_empty() ); // => outer layer has control.
-        do_set(self, cell, val);
+    /// Returns the names of the current path, outermost first: the
+    /// namespaces that scope the cells and thunks allocated now.
+    /// Within a thunk, these are the namespaces of its own allocation,
+    /// followed by those that it entered.
+    pub fn current_path (self:&Self) -> Vec<Name> {
+        let mut names = Vec::new() ;
+        path_names(&frame_path(self), &mut names) ;
+        names
     }
 
-    fn thunk<Arg:Eq+Hash+Debug+Clone+'static,Spurious:'static+Clone,Res:Eq+Debug+Clone+'static>
-        (self:&mut Engine,
-         id:ArtIdChoice<Self::Name>,
-         prog_pt:ProgPt,
-         fn_box:Rc<Box<Fn(&mut Engine, Arg, Spurious) -> Res>>,
-         arg:Arg, spurious:Spurious)
-         -> Art<Res,Self::Loc>
+    /// Registers an observer, which receives the events of this
+    /// engine from now on (see `EngineObserver`).
+    pub fn add_observer (self:&mut Self, observer:Box<EngineObserver>) {
+        self.observers.push(observer)
+    }
+
+    /// Registers `art` as a root of `propagate`, which keeps it
+    /// consistent eagerly.  A root is live for `collect` until it is
+    /// removed (see `remove_root`).
+    pub fn add_root<T:'static+Eq+Debug+Clone> (self:&mut Self, art:&Art<T,Loc>) {
+        match *art {
+            Art::Rc(_) => (), // Never changes.
+            Art::Loc(ref loc) => {
+                if ! self.roots.iter().any(|root| &root.loc == loc) {
+                    self.roots.push(Root{loc:loc.clone(), validate:validate_root::<T>})
+                }
+            }
+        }
+    }
+
+    pub fn remove_root<T> (self:&mut Self, art:&Art<T,Loc>) {
+        match *art {
+            Art::Rc(_) => (),
+            Art::Loc(ref loc) => self.roots.retain(|root| &root.loc != loc),
+        }
+    }
+
+    /// Eager (push-based) change propagation: re-validates the dirty
+    /// nodes below the roots (see `add_root`), deepest first, via the
+    /// change propagation of `force`, so that later forces of these
+    /// roots, and of their consistent succs, are cache hits.  Roots
+    /// that were never forced remain so.  Used by the outer layer,
+    /// after `set`s; returns the work done.
+    pub fn propagate (self:&mut Self) -> Cnt {
+        match do_propagate(self) { Ok(cnt) => cnt, Err(err) => fail(self, err) }
+    }
+
+    pub fn try_propagate (self:&mut Self) -> AdaptonResult<Cnt> {
+        recover(self, |st| do_propagate(st))
+    }
+
+    /// Sets several cells at once: `body` records writes (see
+    /// `Batch::set`), which are applied only if it returns `Ok`.
+    /// Writes of the values that cells already hold are dropped, and
+    /// the others are dirtied in a single pass, which visits each
+    /// observer once.  Used by the outer layer; returns the work
+    /// done, where `Cnt::dirty` counts the nodes dirtied.
+    pub fn batch<E,F> (self:&mut Self, body:F) -> ::std::result::Result<Cnt,E>
+        where F:FnOnce(&mut Batch) -> ::std::result::Result<(),E>
     {
-        match id {
-            ArtIdChoice::Eager => {
-                Art::Rc(Rc::new(fn_box(self,arg,spurious)))
-            },
-            
-            ArtIdChoice::Structural => {
-                let hash = my_hash (&(&prog_pt, &arg)) ;
-                let loc = loc_of_id(self.stack[0].path.clone(),
-                                    Rc::new(ArtId::Structural(hash)));
-                if false {
-                    info!("{} alloc thunk: Structural {:?}\n{} ;; {:?}\n{} ;; {:?}",
-                             engineMsg!(self), &loc,
-                             engineMsg!(self), &prog_pt.symbol,
-                             engineMsg!(self), &arg);
-                } ;
-                {   // If the node exists, return early.
-                    let node = self.table.get_mut(&loc);
-                    match node { None    => { },
-                                 Some(_) => { return Art::Loc(loc) }, // Nothing to do; it already exists.
-                    }
-                } ;
-                // assert: node does not exist.
-                match self.stack.last_mut() {
-                    None => (),
-                    Some(frame) => {
-                        let pred = frame.loc.clone();
-                        let succ =
-                            Succ{loc:loc.clone(),
-                                 dep:Rc::new(Box::new(NoDependency)),
-                                 effect:Effect::Allocate,
-                                 dirty:false};
-                        frame.succs.push(succ)
-                    }};
-                let producer : Box<Producer<Res>> =
-                    Box::new(App{prog_pt:prog_pt,
-                                 fn_box:fn_box,
-                                 arg:arg.clone(),
-                                 spurious:spurious.clone()})
-                    ;
-                let node : CompNode<Res> = CompNode{
-                    preds:Vec::new(),
-                    succs:Vec::new(),
-                    producer:producer,
-                    res:None,
+        let mut batch = Batch{ writes:Vec::new() } ;
+        try!(body(&mut batch)) ;
+        match do_batch(self, batch) { Ok(cnt) => Ok(cnt), Err(err) => fail(self, err) }
+    }
+
+    /// Captures the state of the DCG, for `rollback`.  Used by the
+    /// outer layer.
+    pub fn checkpoint (self:&mut Self) -> Checkpoint {
+        match do_checkpoint(self) { Ok(checkpoint) => checkpoint, Err(err) => fail(self, err) }
+    }
+
+    pub fn try_checkpoint (self:&mut Self) -> AdaptonResult<Checkpoint> {
+        recover(self, |st| do_checkpoint(st))
+    }
+
+    /// Like `name_of_string`, `name_of_usize`, `name_pair`, `name_fork`
+    /// and `ns`, returning a `HashCollision` error (when checking for
+    /// collisions) rather than panicking.
+    pub fn try_name_of_string (self:&mut Self, sym:String) -> AdaptonResult<Name> {
+        name_of_sym(self, NameSym::String(sym))
+    }
+
+    pub fn try_name_of_usize (self:&mut Self, sym:usize) -> AdaptonResult<Name> {
+        name_of_sym(self, NameSym::Usize(sym))
+    }
+
+    pub fn try_name_pair (self:&mut Self, fst:Name, snd:Name) -> AdaptonResult<Name> {
+        do_name_pair(self, fst, snd)
+    }
+
+    pub fn try_name_fork (self:&mut Self, nm:Name) -> AdaptonResult<(Name, Name)> {
+        do_name_fork(self, nm)
+    }
+
+    pub fn try_ns<T,F> (self:&mut Self, nm:Name, body:F) -> AdaptonResult<T> where F:FnOnce(&mut Self) -> T {
+        let path = frame_path(self) ;
+        let path_body = try!(intern_path(self, path, nm)) ;
+        Ok(in_ns(self, path_body, body))
+    }
+
+    /// Restores the DCG to its state at `checkpoint`: the values of
+    /// cells, the cached results of thunks, and the edges, including
+    /// their dirty flags.  Re-executes nothing.  Checkpoints taken
+    /// since `checkpoint` become stale, as do the `Art`s and `MutArt`s
+    /// allocated since (at new names).  Used by the outer layer.
+    pub fn rollback (self:&mut Self, checkpoint:Checkpoint) {
+        match do_rollback(self, checkpoint) { Ok(()) => (), Err(err) => fail(self, err) }
+    }
+
+    pub fn try_rollback (self:&mut Self, checkpoint:Checkpoint) -> AdaptonResult<()> {
+        recover(self, |st| do_rollback(st, checkpoint))
+    }
+
+    /// Reclaims every DCG node that is unreachable: it has no
+    /// outstanding `Art` or `MutArt` handle, and no live predecessor
+    /// that may demand it again.  Unlinks the succ edges of the freed
+    /// nodes and counts them in `Cnt::collected`.  Returns the number
+    /// of nodes freed.
+    pub fn collect (self:&mut Self) -> usize {
+        let mut freed = 0 ;
+        loop {
+            // Freeing a node drops the arguments and results it holds,
+            // which may release the last handles of other nodes.
+            let round = collect_round(self) ;
+            if round == 0 { break } else { freed += round }
+        } ;
+        // Reasons hold locs, and so paths; prune them first.
+        prune_reasons(self) ;
+        prune_interned(self) ;
+        self.cnt.collected += freed ;
+        freed
+    }
+
+    /// Returns the number of name symbols and paths that the engine
+    /// interns (see `Cnt::intern`); `collect` drops those no longer in use.
+    pub fn interned_size (self:&Self) -> usize {
+        self.interned.syms.len() + self.interned.paths.len()
+    }
+
+    /// Writes the DCG in Graphviz DOT format.  Each node is labeled
+    /// with its kind (Comp, Mut or Pure), its program point (for Comp
+    /// nodes), its path and its identity; each succ edge is labeled
+    /// with its effect, and dirty edges are drawn red and dashed.
+    pub fn export_dot (self:&mut Self, out:&mut io::Write) -> io::Result<()> {
+        let nodes = export_nodes(self, None) ;
+        write_dot(out, &nodes)
+    }
+
+    /// Like `export_dot`, restricted to the nodes reachable from `art`.
+    pub fn export_dot_from<T> (self:&mut Self, art:&Art<T,Loc>, out:&mut io::Write) -> io::Result<()> {
+        let nodes = match *art { Art::Rc(_) => Vec::new(), Art::Loc(ref loc) => export_nodes(self, Some(loc)) } ;
+        write_dot(out, &nodes)
+    }
+
+    /// Writes the DCG as JSON, with the same content as `export_dot`:
+    /// `{"nodes":[{"id","kind","prog_pt","path","art_id"}..],
+    ///   "edges":[{"src","tgt","effect","dirty"}..]}`, where ids are
+    /// strings, and `prog_pt` is null for Mut and Pure nodes.
+    pub fn export_json (self:&mut Self, out:&mut io::Write) -> io::Result<()> {
+        let nodes = export_nodes(self, None) ;
+        write_json(out, &nodes)
+    }
+
+    /// Like `export_json`, restricted to the nodes reachable from `art`.
+    pub fn export_json_from<T> (self:&mut Self, art:&Art<T,Loc>, out:&mut io::Write) -> io::Result<()> {
+        let nodes = match *art { Art::Rc(_) => Vec::new(), Art::Loc(ref loc) => export_nodes(self, Some(loc)) } ;
+        write_json(out, &nodes)
+    }
+
+    /// Saves the DCG to the file at `path`: its nodes and edges, with
+    /// their names and paths, the cached results of thunks, and the
+    /// values of cells.  The producer of each thunk, and the type of
+    /// each value, must be registered (see `Registry`).  Used by the
+    /// outer layer.
+    pub fn save<P:AsRef<::std::path::Path>> (self:&mut Self, path:P, registry:&Registry) -> io::Result<()> {
+        let bytes = try!(save_dcg(self, registry)) ;
+        let mut file = try!(File::create(path)) ;
+        file.write_all(&bytes)
+    }
+
+    /// Loads a DCG saved by `save`, rebinding each thunk to the
+    /// producer that `registry` gives for its `ProgPt`.  A re-run of
+    /// the program that saved the DCG re-allocates its nodes (by name,
+    /// or structurally), rather than creating them anew, so that
+    /// after an edit, forces only re-execute what changed.  (Until
+    /// then, the nodes have no outstanding handles, so `collect` would
+    /// reclaim them.)  The engine has the default configuration.
+    pub fn load<P:AsRef<::std::path::Path>> (path:P, registry:&Registry) -> io::Result<Engine> {
+        Engine::load_with_config(path, registry, EngineConfig::default())
+    }
+
+    /// Like `load`, into an engine with the given configuration: its
+    /// cache limit bounds the loaded results, and when it checks for
+    /// collisions, it checks the loaded names, paths and locations.
+    pub fn load_with_config<P:AsRef<::std::path::Path>> (path:P, registry:&Registry, config:EngineConfig) -> io::Result<Engine> {
+        let mut bytes = Vec::new() ;
+        try!(File::open(path).and_then(|mut file| file.read_to_end(&mut bytes))) ;
+        let mut st = Engine::with_config(config) ;
+        try!(load_dcg(&mut st, &bytes, registry)) ;
+        Ok(st)
+    }
+}
+
+// Reports an error raised by an infallible operation: records it for
+// the nearest enclosing fallible operation (see `recover`), and panics.
+fn fail<T>(st:&mut Engine, err:AdaptonError) -> T {
+    let msg = format!("{}", err) ;
+    st.error = Some(err) ;
+    panic!("adapton::engine: {}", msg)
+}
+
+// Performs a fallible operation on behalf of a `try_` method.  The
+// infallible operations nested within it (e.g., the `force`s performed
+// by producers) panic on error; this recovers their error, and pops
+// the frames of the producers that they aborted.  Other panics (e.g.,
+// from the producers themselves) abort the same frames, and resume.
+fn recover<T,F>(st:&mut Engine, body:F) -> AdaptonResult<T>
+    where F:FnOnce(&mut Engine) -> AdaptonResult<T>
+{
+    // An error left by a panic that was caught elsewhere is stale:
+    st.error = None ;
+    let depth = st.stack.len() ;
+    let result = {
+        let st_body = &mut *st ;
+        catch_unwind(AssertUnwindSafe(move || body(st_body)))
+    } ;
+    match result {
+        Ok(result) => result,
+        Err(payload) => {
+            let err = st.error.take() ;
+            while st.stack.len() > depth {
+                let frame = st.stack.pop().unwrap() ;
+                info!("{} recover: aborted {:?}: {:?}", engineMsg!(st), &frame.loc, &err);
+                abort_produce(st, &frame.loc)
+            } ;
+            match err {
+                None => resume_unwind(payload), // Not an engine error.
+                Some(err) => Err(err),
+            }
+        }
+    }
+}
+
+fn do_cell<T:Eq+Debug+Clone
+    +'static // TODO-Later: Needed on T because of lifetime issues.
+    >
+    (st:&mut Engine, nm:Name, val:T) -> AdaptonResult<MutArt<T,Loc>> {
+        let path = frame_path(st);
+        let id   = Arc::new(ArtId::Nominal(nm));
+        let hash = fingerprint(&(&path,&id));
+        let loc  = canonical_loc(st, Arc::new(Loc{path:path,id:id,hash:hash}));
+        try!(check_loc(st, &loc)) ;
+        info!("{} alloc cell: {:?} <--- {:?}", engineMsg!(st), &loc, &val);
+        let kind = match st.table.get_mut(&loc) {
+            None => None,
+            Some(node) => {
+                let node_type = node.res_type() ;
+                if node_type != NodeType::of::<T>() {
+                    return Err(type_mismatch(&loc, NodeType::of::<T>(), node_type))
                 } ;
-                self.table.insert(loc.clone(),
-                                  Box::new(Node::Comp(node)));
-                Art::Loc(loc)
+                Some(node.kind())
             },
-            
-            ArtIdChoice::Nominal(nm) => {
-                let loc = loc_of_id(self.stack[0].path.clone(),
-                                    Rc::new(ArtId::Nominal(nm)));
-                info!("{} alloc thunk: Nominal {:?}\n{} ;; {:?}\n{} ;; {:?}",
-                         engineMsg!(self), &loc,
-                         engineMsg!(self), &prog_pt.symbol,
-                         engineMsg!(self), &arg);
-                let producer : App<Arg,Spurious,Res> =
-                    App{prog_pt:prog_pt,
-                        fn_box:fn_box,
-                        arg:arg.clone(),
-                        spurious:spurious.clone(),
-                    }
+        } ;
+        let preds = match kind {
+            Some("cell") => {
+                let cell = MutArt{loc:loc.clone(), phantom:PhantomData} ;
+                try!(do_set(st, cell, val.clone())) ;
+                None
+            },
+            Some(_) => {
+                // Case: A thunk in a prior run; it becomes a cell.
+                info!("{} alloc cell: replacing thunk {:?}", engineMsg!(st), &loc);
+                Some(try!(retire_node(st, &loc)))
+            },
+            None => Some(Vec::new()),
+        } ;
+        match preds {
+            None => (),
+            Some(preds) => {
+                let node = Node::Mut(MutNode{
+                    preds:preds,
+                    val:val.clone(),
+                }) ;
+                table_insert(st, loc.clone(), Box::new(node));
+                st.cnt.create += 1 ;
+            },
+        } ;
+        let succ =
+            Succ{loc:loc.clone(),
+                 dep:Rc::new(Box::new(AllocDependency{val:val})),
+                 effect:Effect::Allocate,
+                 dirty:false};
+        info!("{} alloc cell: edge: --> {:?}", engineMsg!(st), &loc);
+        push_succ(st, succ) ;
+        Ok(MutArt{loc:loc,phantom:PhantomData})
+    }
+
+fn do_thunk<Arg:Eq+Hash+Debug+Clone+'static,Spurious:'static+Clone,Res:Eq+Debug+Clone+'static>
+    (st:&mut Engine,
+     id:ArtIdChoice<Name>,
+     prog_pt:ProgPt,
+     fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+     arg:Arg, spurious:Spurious,
+     res_eq:Option<ResEq<Res>>)
+     -> AdaptonResult<Art<Res,Loc>>
+{
+    match id {
+        ArtIdChoice::Eager => {
+            Ok(Art::Rc(Arc::new(fn_box(st,arg,spurious))))
+        },
+        
+        ArtIdChoice::Structural => {
+            let hash = fingerprint (&(&prog_pt, &arg)) ;
+            let loc = loc_of_id(frame_path(st),
+                                Arc::new(ArtId::Structural(hash)));
+            let loc = canonical_loc(st, loc);
+            try!(check_loc(st, &loc)) ;
+            if false {
+                info!("{} alloc thunk: Structural {:?}\n{} ;; {:?}\n{} ;; {:?}",
+                         engineMsg!(st), &loc,
+                         engineMsg!(st), &prog_pt.symbol,
+                         engineMsg!(st), &arg);
+            } ;
+            if st.table.contains_key(&loc) {
+                // Nothing to do; it already exists.
+                try!(check_structural::<Arg,Spurious,Res>(st, &loc, &prog_pt, &arg)) ;
+                // Except for res_eq, which a loaded node lacks (see `Engine::load`):
+                if res_eq.is_some() {
+                    let node : &mut Node<Res> = try!(res_node_of_loc_mut(st, &loc)) ;
+                    match *node { Node::Comp(ref mut nd) => nd.res_eq = res_eq, _ => () }
+                } ;
+                return Ok(Art::Loc(loc))
+            } ;
+            // assert: node does not exist.
+            let succ =
+                Succ{loc:loc.clone(),
+                     dep:Rc::new(Box::new(NoDependency)),
+                     effect:Effect::Allocate,
+                     dirty:false};
+            push_succ(st, succ) ;
+            st.cnt.create += 1 ;
+            st.cnt.prog_pt(&prog_pt).create += 1 ;
+            let producer : Box<Producer<Res>> =
+                Box::new(App{prog_pt:prog_pt,
+                             fn_box:fn_box,
+                             arg:arg.clone(),
+                             spurious:spurious.clone()})
                 ;
-                let stackLen = self.stack.len() ;
-                let (do_dirty, do_insert) = { match self.table.get_mut( &loc ) {
-                    None => {
-                        // do_dirty=false; do_insert=true
-                        (false, true)
-                    },
+            let node : CompNode<Res> = CompNode{
+                preds:Vec::new(),
+                succs:Vec::new(),
+                producer:producer,
+                res:None,
+                running:false,
+                cycle:None,
+                res_eq:res_eq,
+            } ;
+            table_insert(st, loc.clone(), Box::new(Node::Comp(node)));
+            Ok(Art::Loc(loc))
+        },
+        
+        ArtIdChoice::Nominal(nm) => {
+            let loc = loc_of_id(frame_path(st),
+                                Arc::new(ArtId::Nominal(nm)));
+            let loc = canonical_loc(st, loc);
+            try!(check_loc(st, &loc)) ;
+            info!("{} alloc thunk: Nominal {:?}\n{} ;; {:?}\n{} ;; {:?}",
+                     engineMsg!(st), &loc,
+                     engineMsg!(st), &prog_pt.symbol,
+                     engineMsg!(st), &arg);
+            let producer : App<Arg,Spurious,Res> =
+                App{prog_pt:prog_pt,
+                    fn_box:fn_box,
+                    arg:arg.clone(),
+                    spurious:spurious.clone(),
+                }
+            ;
+            let stackLen = st.stack.len() ;
+            let preds = {
+                let was_cell = match st.table.get_mut( &loc ) {
+                    None => false,
                     Some(node) => {
-                        let node: &mut Box<GraphNode> = node ;
-                        let res_nd: &mut Box<Node<Res>> = unsafe { transmute::<_,_>( node ) } ;
-                        let comp_nd: &mut CompNode<Res> = match ** res_nd {                            
+                        let node_type = node.res_type() ;
+                        if node_type != NodeType::of::<Res>() {
+                            return Err(type_mismatch(&loc, NodeType::of::<Res>(), node_type))
+                        } ;
+                        node.kind() == "cell"
+                    }
+                } ;
+                if was_cell {
+                    // Case: A cell in a prior run; it becomes a thunk.
+                    info!("{} alloc thunk: replacing cell {:?}", engineMsg!(st), &loc);
+                    try!(retire_node(st, &loc))
+                } else { Vec::new() }
+            } ;
+            let (do_dirty, do_insert, do_replace, cleared) = { match table_get_mut( st, &loc ) {
+                None => {
+                    // do_dirty=false; do_insert=true; do_replace=false
+                    (false, true, false, false)
+                },
+                Some(node) => {
+                    let node: &mut Box<GraphNode> = node ;
+                    let node_type = node.res_type() ;
+                    let comp_nd: &mut CompNode<Res> = match node.as_any().downcast_mut::<Node<Res>>() {
+                        None => return Err(type_mismatch(&loc, NodeType::of::<Res>(), node_type)),
+                        Some(res_nd) => match *res_nd {
                             Node::Pure(_)=> unreachable!(),
-                            Node::Mut(_) => panic!("TODO-Sometime"),
+                            Node::Mut(_) => unreachable!(), // Retired, above.
                             Node::Comp(ref mut comp) => comp,
                             _ => unreachable!(),
-                        } ;
-                        let equal_producer_prog_pts : bool =
-                            comp_nd.producer.prog_pt().eq( producer.prog_pt() ) ;
-                        info!("{} alloc thunk: Nominal match: equal_producer_prog_pts: {:?}",
-                                 engineMsg(Some(stackLen)), equal_producer_prog_pts);
-                        if equal_producer_prog_pts { // => safe cast to Box<Consumer<Arg>>
-                            let app: &mut Box<App<Arg,Spurious,Res>> =
-                                unsafe { transmute::<_,_>( &mut comp_nd.producer ) }
-                            ;
-                            info!("{} alloc thunk: Nominal match: app: {:?}", engineMsg(Some(stackLen)), app);
-                            if app.get_arg() == arg {
-                                // Case: Same argument; Nothing else to do:
-                                // do_dirty=false; do_insert=false
-                                (false, false)
-                            }
-                            else { // Case: Not the same argument:
-                                app.consume(arg.clone()); // overwrite the old argument
-                                comp_nd.res = None ; // clear the cache
-                                // do_dirty=true; do_insert=false
-                                (true, false)
-                            }}
-                        else {
-                            panic!("TODO-Sometime: producers not equal!")
-                        }
+                        }} ;
+                    comp_nd.res_eq = res_eq.clone() ;
+                    let equal_producer_prog_pts : bool =
+                        comp_nd.producer.prog_pt().eq( producer.prog_pt() ) ;
+                    info!("{} alloc thunk: Nominal match: equal_producer_prog_pts: {:?}",
+                             engineMsg(Some(stackLen)), equal_producer_prog_pts);
+                    let same_app = if equal_producer_prog_pts { // => checked cast to App<Arg,Spurious,Res>
+                        match comp_nd.producer.as_any_mut().downcast_mut::<App<Arg,Spurious,Res>>() {
+                            None => None,
+                            Some(app) => {
+                                info!("{} alloc thunk: Nominal match: app: {:?}", engineMsg(Some(stackLen)), app);
+                                if app.get_arg() == arg {
+                                    // Case: Same argument; Nothing else to do:
+                                    Some(false)
+                                }
+                                else { // Case: Not the same argument:
+                                    app.consume(arg.clone()); // overwrite the old argument
+                                    Some(true)
+                                }}
+                        }} else { None } ;
+                    match same_app {
+                        Some(false) => {
+                            // do_dirty=false; do_insert=false; do_replace=false
+                            (false, false, false, false)
+                        },
+                        Some(true) => {
+                            let cleared = comp_nd.res.take().is_some() ; // clear the cache
+                            // do_dirty=true; do_insert=false; do_replace=false
+                            (true, false, false, cleared)
+                        },
+                        None => {
+                            // Case: A different producer (or the same prog_pt, at
+                            // different Arg or Spurious types) reuses the name.
+                            info!("{} alloc thunk: Nominal match: replacing producer {:?} (Arg={:?}, Spurious={:?}) with {:?} (Arg={:?}, Spurious={:?})",
+                                  engineMsg(Some(stackLen)),
+                                  comp_nd.producer.prog_pt(), comp_nd.producer.arg_type(), comp_nd.producer.spurious_type(),
+                                  &producer.prog_pt, NodeType::of::<Arg>(), NodeType::of::<Spurious>());
+                            let cleared = comp_nd.res.take().is_some() ; // clear the cache
+                            // do_dirty=true; do_insert=false; do_replace=true
+                            (true, false, true, cleared)
+                        },
                     }
-                } } ;
-                if do_dirty {
-                    info!("{} alloc thunk: dirty_alloc {:?}.", engineMsg!(self), &loc);
-                    dirty_alloc(self, &loc)
-                } else {
-                    info!("{} alloc thunk: No dirtying.", engineMsg!(self))
-                } ;
-                match self.stack.last_mut() { None => (), Some(frame) => {
-                    let pred = frame.loc.clone();
-                    info!("{} alloc thunk: edge {:?} --> {:?}", engineMsg(Some(stackLen)), &pred, &loc);
-                    let succ =
-                        Succ{loc:loc.clone(),
-                             dep:Rc::new(Box::new(AllocDependency{val:arg.clone()})),
-                             effect:Effect::Allocate,
-                             dirty:false};
-                    frame.succs.push(succ)
-                }};
-                if do_insert {
-                    let node : CompNode<Res> = CompNode{
-                        preds:Vec::new(),
-                        succs:Vec::new(),
-                        producer:Box::new(producer),
-                        res:None,
-                    } ;
-                    self.table.insert(loc.clone(),
-                                      Box::new(Node::Comp(node)));
-                    Art::Loc(loc)
                 }
-                else {
-                    Art::Loc(loc)
+            } } ;
+            if cleared { cache_forget(st, &loc) } ;
+            if do_dirty {
+                info!("{} alloc thunk: dirty_alloc {:?}.", engineMsg!(st), &loc);
+                try!(dirty_alloc(st, &loc))
+            } else {
+                info!("{} alloc thunk: No dirtying.", engineMsg!(st))
+            } ;
+            if do_insert {
+                st.cnt.create += 1 ;
+                st.cnt.prog_pt(&producer.prog_pt).create += 1 ;
+            } else {
+                notify(st, |o| o.realloc(&loc, &producer.prog_pt, do_dirty)) ;
+                if do_dirty {
+                    st.cnt.realloc += 1 ;
+                    st.cnt.prog_pt(&producer.prog_pt).realloc += 1 ;
                 }
+            } ;
+            info!("{} alloc thunk: edge --> {:?}", engineMsg(Some(stackLen)), &loc);
+            let succ =
+                Succ{loc:loc.clone(),
+                     dep:Rc::new(Box::new(AllocDependency{val:arg.clone()})),
+                     effect:Effect::Allocate,
+                     dirty:false};
+            push_succ(st, succ) ;
+            if do_insert {
+                let node : CompNode<Res> = CompNode{
+                    preds:preds,
+                    succs:Vec::new(),
+                    producer:Box::new(producer),
+                    res:None,
+                    running:false,
+                    cycle:None,
+                    res_eq:res_eq,
+                } ;
+                table_insert(st, loc.clone(), Box::new(Node::Comp(node)));
+                Ok(Art::Loc(loc))
+            }
+            else if do_replace {
+                // The node keeps its preds (which are now dirty), and its
+                // succs, which the new producer revokes when it runs.
+                let node : &mut Node<Res> = try!(res_node_of_loc_mut(st, &loc)) ;
+                match *node {
+                    Node::Comp(ref mut comp) => { comp.producer = Box::new(producer) },
+                    _ => unreachable!(),
+                } ;
+                Ok(Art::Loc(loc))
+            }
+            else {
+                Ok(Art::Loc(loc))
             }
         }
     }
+}
 
-    fn force<T:'static+Eq+Debug+Clone> (self:&mut Engine,
-                                        art:&Art<T,Self::Loc>) -> T
-    {
-        match *art {
-            Art::Rc(ref v) => (**v).clone(),
-            Art::Loc(ref loc) => {
-                let (is_comp, cached_result) : (bool, Option<T>) = {
-                    let node : &mut Node<T> = res_node_of_loc(self, &loc) ;
+// Returns the pure node that holds val, keyed by the type and content
+// hash of val; creates the node when it is new.
+fn do_put_shared<T:'static+Eq+Hash+Debug+Clone> (st:&mut Engine, val:T) -> AdaptonResult<Art<T,Loc>> {
+    let hash = fingerprint(&(NodeType::of::<T>().name, &val)) ;
+    let loc  = loc_of_id(st.root.path.clone(), Arc::new(ArtId::Structural(hash))) ;
+    let loc  = canonical_loc(st, loc) ;
+    try!(check_loc(st, &loc)) ;
+    let checking = st.fingerprints.is_some() ;
+    let exists = match st.table.get_mut(&loc) {
+        None => false,
+        Some(node) => {
+            let node_type = node.res_type() ;
+            match node.as_any().downcast_mut::<Node<T>>() {
+                None => return Err(type_mismatch(&loc, NodeType::of::<T>(), node_type)),
+                Some(&mut Node::Pure(ref nd)) => {
+                    // Values with equal hashes are assumed equal, unless checking.
+                    if checking && nd.val != val {
+                        return Err(AdaptonError::HashCollision{hash:format!("{}", hash),
+                                                               first:format!("{:?}", nd.val),
+                                                               second:format!("{:?}", val)})
+                    } ;
+                    true
+                },
+                Some(node) => return Err(kind_mismatch(&loc, "pure node", node_kind(node))),
+            }
+        }
+    } ;
+    if !exists {
+        info!("{} put shared: {:?} <--- {:?}", engineMsg!(st), &loc, &val);
+        table_insert(st, loc.clone(), Box::new(Node::Pure(PureNode{ val:val }))) ;
+        st.cnt.create += 1 ;
+    } ;
+    Ok(Art::Loc(loc))
+}
+
+// Forces art; fix is the initial value of a cyclic force, when
+// cycles are permitted (see `Engine::force_fix`).
+fn do_force<T:'static+Eq+Debug+Clone> (st:&mut Engine,
+                                    art:&Art<T,Loc>, fix:Option<T>) -> AdaptonResult<T>
+{
+    match *art {
+        Art::Rc(ref v) => Ok((**v).clone()),
+        Art::Loc(ref loc) => {
+            let (is_comp, cached_result, running) : (bool, Option<T>, bool) = {
+                let node : &mut Node<T> = try!(res_node_of_loc(st, &loc)) ;
+                match *node {
+                    Node::Pure(ref mut nd) => (false, Some(nd.val.clone()), false),
+                    Node::Mut(ref mut nd)  => (false, Some(nd.val.clone()), false),
+                    Node::Comp(ref mut nd) => (true,  nd.res.clone(), nd.running),
+                    _ => panic!("undefined")
+                }
+            } ;
+            if running {
+                if fix.is_none() { return Err(cycle_error(st, &loc)) } ;
+                let approx : T = {
+                    let node : &mut Node<T> = try!(res_node_of_loc_mut(st, &loc)) ;
                     match *node {
-                        Node::Pure(ref mut nd) => (false, Some(nd.val.clone())),
-                        Node::Mut(ref mut nd)  => (false, Some(nd.val.clone())),
-                        Node::Comp(ref mut nd) => (true,  nd.res.clone()),
-                        _ => panic!("undefined")
-                    }
-                } ;
-                let result = match cached_result {
-                    None => {
-                        info!("{} force {:?}: cache empty", engineMsg!(self), &loc);
-                        assert!(is_comp);
-                        produce(self, &loc)
-                    },
-                    Some(ref res) => {
-                        if is_comp {
-                            info!("{} force {:?}: cache holds {:?}.  Using change propagation.", engineMsg!(self), &loc, &res);
-                            // ProducerDep change-propagation precondition:
-                            // loc is a computational node:
-                            let res = ProducerDep{res:res.clone()}.change_prop(self, &loc) ;
-                            info!("{} force {:?}: result changed?: {}", engineMsg!(self), &loc, res.changed) ;
-                            let node : &mut Node<T> = res_node_of_loc(self, &loc) ;
+                        Node::Comp(ref mut nd) => match cycle_approx(nd, fix) { None => unreachable!(), Some(approx) => approx },
+                        _ => unreachable!(),
+                    }} ;
+                info!("{} force {:?}: cyclic; approximated by {:?}", engineMsg!(st), &loc, &approx);
+                let succ =
+                    Succ{loc:loc.clone(),
+                         dep:Rc::new(Box::new(ProducerDep{res:approx.clone()})),
+                         effect:Effect::Observe,
+                         dirty:false};
+                push_succ(st, succ) ;
+                return Ok(approx)
+            } ;
+            let result = match cached_result {
+                None => {
+                    info!("{} force {:?}: cache empty", engineMsg!(st), &loc);
+                    assert!(is_comp);
+                    try!(produce(st, &loc))
+                },
+                Some(ref res) => {
+                    if is_comp {
+                        info!("{} force {:?}: cache holds {:?}.  Using change propagation.", engineMsg!(st), &loc, &res);
+                        // ProducerDep change-propagation precondition:
+                        // loc is a computational node:
+                        let prop = try!(change_prop(st, Rc::new(Box::new(ProducerDep{res:res.clone()})), &loc)) ;
+                        info!("{} force {:?}: result changed?: {}", engineMsg!(st), &loc, prop.changed) ;
+                        let cached = {
+                            let node : &mut Node<T> = try!(res_node_of_loc(st, &loc)) ;
                             match *node {
-                                Node::Comp(ref nd) => match nd.res {
-                                    None => unreachable!(),
-                                    Some(ref res) =>
-                                        // Testing: Reached by `pure_caching` tests
-                                        res.clone()
-                                },
+                                Node::Comp(ref nd) => nd.res.clone(),
                                 _ => unreachable!(),
-                            }}
-                        else {
-                            info!("{} force {:?}: no change prop necessary.", engineMsg!(self), &loc);
-                            res.clone()
-                        }
+                            }} ;
+                        match cached {
+                            // Testing: Reached by `pure_caching` tests
+                            Some(res) => {
+                                // A hit only when change propagation validated the result:
+                                if !prop.reproduced {
+                                    match observed_prog_pt(st, &loc) { None => (), Some(prog_pt) => notify(st, |o| o.cache_hit(&loc, &prog_pt)) }
+                                } ;
+                                res
+                            },
+                            // The result was evicted during change propagation:
+                            None => try!(produce(st, &loc)),
+                        }}
+                    else {
+                        info!("{} force {:?}: no change prop necessary.", engineMsg!(st), &loc);
+                        res.clone()
                     }
-                } ;
-                match self.stack.last_mut() { None => (), Some(frame) => {
-                    let succ =
-                        Succ{loc:loc.clone(),
-                             dep:Rc::new(Box::new(ProducerDep{res:result.clone()})),
-                             effect:Effect::Observe,
-                             dirty:false};
-                    frame.succs.push(succ);
-                }} ;
-                result
-            }
-        }}
+                }
+            } ;
+            if is_comp { cache_touch(st, &loc, CacheEvent::Force, false) } ;
+            let succ =
+                Succ{loc:loc.clone(),
+                     dep:Rc::new(Box::new(ProducerDep{res:result.clone()})),
+                     effect:Effect::Observe,
+                     dirty:false};
+            push_succ(st, succ) ;
+            Ok(result)
+        }
+    }}
+
+impl Adapton for Engine {
+    type Name = Name;
+    type Loc  = Loc;
+
+    fn new () -> Engine {
+        Engine::with_config(EngineConfig::default())
+    }
+
+    fn name_of_string (self:&mut Engine, sym:String) -> Name {
+        match name_of_sym(self, NameSym::String(sym)) { Ok(name) => name, Err(err) => fail(self, err) }
+    }
+
+    fn name_of_usize (self:&mut Engine, sym:usize) -> Name {
+        match name_of_sym(self, NameSym::Usize(sym)) { Ok(name) => name, Err(err) => fail(self, err) }
+    }
+
+    fn name_pair (self: &mut Engine, fst: Name, snd: Name) -> Name {
+        match do_name_pair(self, fst, snd) { Ok(name) => name, Err(err) => fail(self, err) }
+    }
+
+    fn name_fork (self:&mut Engine, nm:Name) -> (Name, Name) {
+        match do_name_fork(self, nm) { Ok(names) => names, Err(err) => fail(self, err) }
+    }
+
+    fn ns<T,F> (self: &mut Self, nm:Name, body:F) -> T where F:FnOnce(&mut Self) -> T {
+        let path = frame_path(self) ;
+        let path_body = match intern_path(self, path, nm) { Ok(path) => path, Err(err) => fail(self, err) } ;
+        in_ns(self, path_body, body)
+    }
+
+    fn cnt<Res,F> (self: &mut Self, body:F) -> (Res,Cnt)
+        where F:FnOnce(&mut Self) -> Res
+    {
+        let c = self.cnt.clone() ;
+        self.cnt.stack_max = self.stack.len() ; // measures the body, alone
+        let x = body(self) ;
+        self.cnt.table_size = self.table.len() ;
+        let d = self.cnt.clone() - c.clone() ;
+        if c.stack_max > self.cnt.stack_max { self.cnt.stack_max = c.stack_max } ;
+        (x, d)
+    }
+
+    fn put<T:Eq+Send+Sync> (self:&mut Engine, x:T) -> Art<T,Self::Loc> { Art::Rc(Arc::new(x)) }
+
+    fn put_shared<T:'static+Eq+Hash+Debug+Clone+Send+Sync> (self:&mut Engine, x:T) -> Art<T,Self::Loc> {
+        match do_put_shared(self, x) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_put_shared<T:'static+Eq+Hash+Debug+Clone+Send+Sync> (self:&mut Engine, x:T) -> AdaptonResult<Art<T,Self::Loc>> {
+        recover(self, |st| do_put_shared(st, x))
+    }
+
+    fn cell<T:Eq+Debug+Clone+Send+Sync
+        +'static // TODO-Later: Needed on T because of lifetime issues.
+        >
+        (self:&mut Engine, nm:Self::Name, val:T) -> MutArt<T,Self::Loc> {
+            match do_cell(self, nm, val) { Ok(cell) => cell, Err(err) => fail(self, err) }
+        }
+
+    fn try_cell<T:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut Engine, nm:Self::Name, val:T) -> AdaptonResult<MutArt<T,Self::Loc>> {
+            recover(self, |st| do_cell(st, nm, val))
+        }
+
+    fn set<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) {
+        match self.try_set(cell, val) { Ok(()) => (), Err(err) => fail(self, err) }
+    }
+
+    fn try_set<T:'static+Eq+Debug+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) -> AdaptonResult<()> {
+        if !is_outer_layer(self) {
+            let thunk = match self.stack.last() { None => unreachable!(), Some(frame) => format!("{:?}", frame.loc) } ;
+            return Err(AdaptonError::SetInInnerLayer{loc:format!("{:?}", cell.loc), thunk:thunk})
+        } ;
+        recover(self, |st| do_set(st, cell, val))
+    }
+
+    fn set_inner<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) {
+        match self.try_set_inner(cell, val) { Ok(()) => (), Err(err) => fail(self, err) }
+    }
+
+    fn try_set_inner<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) -> AdaptonResult<()> {
+        if is_outer_layer(self) {
+            return Err(AdaptonError::SetInnerInOuterLayer{loc:format!("{:?}", cell.loc)})
+        } ;
+        recover(self, |st| do_set_inner(st, cell, val))
+    }
+
+    fn thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut Engine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious)
+         -> Art<Res,Self::Loc>
+    {
+        match do_thunk(self, id, prog_pt, fn_box, arg, spurious, None) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut Engine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious)
+         -> AdaptonResult<Art<Res,Self::Loc>>
+    {
+        recover(self, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, None))
+    }
+
+    fn thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut Engine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> Art<Res,Self::Loc>
+    {
+        match do_thunk(self, id, prog_pt, fn_box, arg, spurious, Some(res_eq)) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut Engine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut Engine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> AdaptonResult<Art<Res,Self::Loc>>
+    {
+        recover(self, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, Some(res_eq)))
+    }
+
+    fn force<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Engine,
+                                        art:&Art<T,Self::Loc>) -> T
+    {
+        match do_force(self, art, None) { Ok(res) => res, Err(err) => fail(self, err) }
+    }
+
+    fn try_force<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Engine,
+                                            art:&Art<T,Self::Loc>) -> AdaptonResult<T>
+    {
+        recover(self, |st| do_force(st, art, None))
+    }
 }
 
 pub fn main () { }
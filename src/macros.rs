@@ -52,11 +52,43 @@ macro_rules! prog_pt {
 
 #[macro_export]
 macro_rules! thunk {
+    // With `; eq = res_eq`, change propagation compares the results by
+    // res_eq (see `Adapton::thunk_with_eq`):
+    ( $st:expr , $nm:expr =>> $f:ident , $( $lab:ident : $arg:expr ),* ; eq = $res_eq:expr ) => {{
+        ($st).thunk_with_eq
+            (ArtIdChoice::Nominal($nm),
+             prog_pt!(stringify!($f)),
+             Arc::new(Box::new(
+                 |st, args, _|{
+                     let ($( $lab ),*, _) = args ;
+                     $f ( st, $( $lab ),* )
+                 })),
+             ( $( $arg ),*, () ),
+             (),
+             $res_eq
+             )
+    }}
+    ;
+    ( $st:expr , $f:path , $( $lab:ident : $arg:expr ),* ; eq = $res_eq:expr ) => {{
+        ($st).thunk_with_eq
+            (ArtIdChoice::Structural,
+             prog_pt!(stringify!($f)),
+             Arc::new(Box::new(
+                 |st, args, _|{
+                     let ($( $lab ),*, _) = args ;
+                     $f ( st, $( $lab ),* )
+                 })),
+             ( $( $arg ),*, () ),
+             (),
+             $res_eq
+             )
+    }}
+    ;
     ( $st:expr , $nm:expr =>> $f:ident :: < $( $ty:ty ),* > , $( $lab:ident : $arg:expr ),* ) => {{
         ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -70,7 +102,7 @@ macro_rules! thunk {
         ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f ( st, $( $lab ),* )
@@ -84,7 +116,7 @@ macro_rules! thunk {
         ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -98,7 +130,7 @@ macro_rules! thunk {
         ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f ( st, $( $lab ),* )
@@ -116,7 +148,7 @@ macro_rules! memo {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -131,7 +163,7 @@ macro_rules! memo {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f ( st, $( $lab ),* )
@@ -146,7 +178,7 @@ macro_rules! memo {
         let t = ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -161,7 +193,7 @@ macro_rules! memo {
         let t = ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f ( st, $( $lab ),* )
@@ -176,7 +208,7 @@ macro_rules! memo {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args1, args2|{
                      let ($( $lab1 ),*, _) = args1 ;
                      let ($( $lab2 ),*, _) = args2 ;
@@ -197,7 +229,7 @@ macro_rules! eager {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -213,7 +245,7 @@ macro_rules! eager {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f ( st, $( $lab ),* )
@@ -229,7 +261,7 @@ macro_rules! eager {
         let t = ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*) = args ;
                      $f :: < $( $ty ),* >( st, $( $lab ),* )
@@ -245,7 +277,7 @@ macro_rules! eager {
         let t = ($st).thunk
             (ArtIdChoice::Structural,
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args, _|{
                      let ($( $lab ),*, _) = args ;
                      $f ( st, $( $lab ),* )
@@ -261,7 +293,7 @@ macro_rules! eager {
         let t = ($st).thunk
             (ArtIdChoice::Nominal($nm),
              prog_pt!(stringify!($f)),
-             Rc::new(Box::new(
+             Arc::new(Box::new(
                  |st, args1, args2|{
                      let ($( $lab1 ),*, _) = args1 ;
                      let ($( $lab2 ),*, _) = args2 ;
@@ -276,6 +308,22 @@ macro_rules! eager {
     ;
 }
 
+// Registers the producer of the thunks that `thunk!` makes for `$f`,
+// given the types of its arguments; see `engine::Registry`.
+#[macro_export]
+macro_rules! register_thunk {
+    ( $reg:expr , $f:path , $( $lab:ident : $ty:ty ),* ) => {{
+        ($reg).thunk
+            (prog_pt!(stringify!($f)),
+             Arc::new(Box::new(
+                 |st, args:( $( $ty ),*, () ), _:()|{
+                     let ($( $lab ),*, _) = args ;
+                     $f ( st, $( $lab ),* )
+                 })))
+    }}
+    ;
+}
+
 
 // https://doc.rust-lang.org/book/macros.html
 //
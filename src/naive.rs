@@ -2,9 +2,10 @@ use std::fmt::Debug;
 use macros::*;
 use adapton_sigs::*;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::hash::{Hash,Hasher};
 use std::fmt::{Formatter,Result};
-use std::mem::transmute;
+use std::mem::{transmute,size_of};
 use std::marker::PhantomData;
 use std::num::Zero;
 
@@ -18,15 +19,59 @@ trait Void : Debug { }
 pub struct AdaptonFromScratch {
     /// need a store; the Adapton trait provides a store semantics (viz., see `set` and `force`).
     store : Vec<Box<Void>>,
+    memory : Vec<EntryMemory>, // for each entry of the store (see `memory_report`)
     cnt : Cnt,
 }
 
+// The estimated memory of an entry of the store, in bytes.  The types
+// of the entry are known only when it is stored, not from its `Void`.
+struct EntryMemory {
+    kind    : &'static str, // As in `MemoryReport::by_kind`
+    prog_pt : Option<&'static str>,
+    node    : usize,
+    value   : usize,
+}
+
+impl EntryMemory {
+    fn of_cell<T> () -> EntryMemory {
+        EntryMemory{ kind:"Mut", prog_pt:None,
+                     node:size_of::<Box<Void>>() + size_of::<Val<T>>(),
+                     value:2 * size_of::<usize>() + size_of::<T>() } // The value's Rc
+    }
+    fn of_thunk<Arg,Spurious,Res> (prog_pt:&ProgPt) -> EntryMemory {
+        EntryMemory{ kind:"Comp", prog_pt:Some(prog_pt.symbol),
+                     node:size_of::<Box<Void>>() + size_of::<App<Arg,Spurious,Res>>(),
+                     value:0 }
+    }
+}
+
 impl Hash  for     AdaptonFromScratch { fn hash<H>(&self, _state: &mut H) where H: Hasher { unimplemented!() }}
 impl Debug for     AdaptonFromScratch { fn fmt(&self, _f:&mut Formatter) -> Result { unimplemented!() } }
 impl Eq    for     AdaptonFromScratch { }
 impl PartialEq for AdaptonFromScratch { fn eq(&self, _other:&Self) -> bool { unimplemented!() } }
 impl Clone for     AdaptonFromScratch { fn clone(&self) -> Self { unimplemented!() } }
 
+impl AdaptonFromScratch {
+    /// Estimates the memory that `store` uses, in bytes, for
+    /// comparison with `Engine::memory_report`.  Each entry of the
+    /// store counts as a node, and the values of cells as results;
+    /// there are no edges, names or keys.
+    pub fn memory_report (self:&Self) -> MemoryReport {
+        let mut report = MemoryReport::zero() ;
+        report.nodes = (self.store.capacity() - self.store.len()) * size_of::<Box<Void>>() ;
+        for entry in self.memory.iter() {
+            report.nodes   += entry.node ;
+            report.results += entry.value ;
+            *report.by_kind.entry(entry.kind).or_insert(0) += entry.node + entry.value ;
+            match entry.prog_pt {
+                None => (),
+                Some(symbol) => *report.by_prog_pt.entry(symbol).or_insert(0) += entry.node + entry.value,
+            }
+        } ;
+        report
+    }
+}
+
 impl Adapton for AdaptonFromScratch {
     type Name = Name;
     type Loc  = Loc;
@@ -34,6 +79,7 @@ impl Adapton for AdaptonFromScratch {
     fn new () -> AdaptonFromScratch {
         AdaptonFromScratch {
             store : Vec::new(),
+            memory : Vec::new(),
             cnt : Cnt::zero(),
         }
     }
@@ -46,40 +92,60 @@ impl Adapton for AdaptonFromScratch {
     fn cnt<Res,F> (self: &mut AdaptonFromScratch, body:F) -> (Res,Cnt) where F:FnOnce(&mut AdaptonFromScratch) -> Res {
         let c = self.cnt.clone();
         let x = body(self);
+        self.cnt.table_size = self.store.len();
         let d = self.cnt.clone() - c;
         (x,d)
     }
-    fn put<T:Eq> (self:&mut AdaptonFromScratch, x:T) -> Art<T,Loc> { Art::Rc(Rc::new(x)) }
+    fn put<T:Eq+Send+Sync> (self:&mut AdaptonFromScratch, x:T) -> Art<T,Loc> { Art::Rc(Arc::new(x)) }
 
-    fn cell<T:Eq+Debug+Clone
+    fn put_shared<T:Eq+Hash+Send+Sync> (self:&mut AdaptonFromScratch, x:T) -> Art<T,Loc> { Art::Rc(Arc::new(x)) }
+
+    fn try_put_shared<T:Eq+Hash+Send+Sync> (self:&mut AdaptonFromScratch, x:T) -> AdaptonResult<Art<T,Loc>> { Ok(Art::Rc(Arc::new(x))) }
+
+    fn cell<T:Eq+Debug+Clone+Send+Sync
         +'static // TODO-Later: Needed on T because of lifetime issues.
         >
         (self:&mut AdaptonFromScratch, _nm:Name, val:T) -> MutArt<T,Loc>
     {
         let val : Box<Producer<T>> = Box::new( Val{val:Rc::new(val)} ) ;
         let val : Box<Void> = unsafe { transmute::<_,_>( val ) } ;
+        self.cnt.create += 1;
+        self.memory.push( EntryMemory::of_cell::<T>() );
         MutArt{loc:{ self.store.push( val );
-                     Rc::new(self.store.len()-1)},
+                     Arc::new(self.store.len()-1)},
                phantom:PhantomData
         }
     }
 
-    fn set<T:'static+Eq+Debug+Clone>
+    fn set<T:'static+Eq+Debug+Clone+Send+Sync>
         (self:&mut AdaptonFromScratch, cell:MutArt<T,Loc>, val:T)
     {
         let val : Box<Producer<T>> = Box::new( Val{val:Rc::new( val )} ) ;
         let val : Box<Void>  = unsafe { transmute::<_,_>( val ) } ;
-        self.store.insert( *cell.loc, val );
+        // Overwrite the cell; inserting would shift every later loc.
+        self.store[ *cell.loc ] = val ;
+        self.memory[ *cell.loc ] = EntryMemory::of_cell::<T>() ;
+    }
+
+    // There are no layers here: every force re-runs its thunk, so a
+    // write from within a thunk is simply a write.
+    fn set_inner<T:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut AdaptonFromScratch, cell:MutArt<T,Loc>, val:T)
+    {
+        self.set(cell, val)
     }
 
-    fn thunk<Arg:Eq+Hash+Debug+Clone+'static,Spurious:'static+Clone,Res:Eq+Debug+Clone+'static>
+    fn thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
         (self:&mut AdaptonFromScratch,
          _id:ArtIdChoice<Name>,
          prog_pt:ProgPt,
-         fn_box:Rc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res>>,
+         fn_box:Arc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res + Send + Sync>>,
          arg:Arg, spurious:Spurious)
          -> Art<Res,Loc>
     {
+        self.cnt.create += 1;
+        self.cnt.prog_pt(&prog_pt).create += 1;
+        self.memory.push( EntryMemory::of_thunk::<Arg,Spurious,Res>(&prog_pt) );
         let producer : Box<Producer<Res>> =
             Box::new(App{prog_pt:prog_pt,
                          fn_box:fn_box,
@@ -95,10 +161,10 @@ impl Adapton for AdaptonFromScratch {
         self.store.push( producer );
         let index = self.store.len()-1;
         //println!("allocated {} as {:?}", index, arg);
-        Art::Loc( Rc::new( index ) )
+        Art::Loc( Arc::new( index ) )
     }
 
-    fn force<Res:'static+Eq+Debug+Clone> (self:&mut AdaptonFromScratch,
+    fn force<Res:'static+Eq+Debug+Clone+Send+Sync> (self:&mut AdaptonFromScratch,
                                           art:&Art<Res,Loc>) -> Res
     {
         match *art {
@@ -119,6 +185,75 @@ impl Adapton for AdaptonFromScratch {
             Art::Rc(ref rc) => (**rc).clone(),
         }
     }
+
+    // The only misuse detected here is a dangling location; names
+    // are not meaningful, and types are justified by the phantom
+    // types of `Art` and `MutArt` (see `force`).
+
+    fn try_cell<T:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut AdaptonFromScratch, nm:Name, val:T) -> AdaptonResult<MutArt<T,Loc>>
+    {
+        Ok(self.cell(nm, val))
+    }
+
+    fn try_set<T:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut AdaptonFromScratch, cell:MutArt<T,Loc>, val:T) -> AdaptonResult<()>
+    {
+        if *cell.loc < self.store.len() { Ok(self.set(cell, val)) }
+        else { Err(AdaptonError::DanglingLoc{loc:format!("{:?}", cell.loc)}) }
+    }
+
+    fn try_set_inner<T:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut AdaptonFromScratch, cell:MutArt<T,Loc>, val:T) -> AdaptonResult<()>
+    {
+        self.try_set(cell, val)
+    }
+
+    // Every force re-runs its thunk, so results are never compared.
+    fn thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut AdaptonFromScratch,
+         id:ArtIdChoice<Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         _res_eq:ResEq<Res>)
+         -> Art<Res,Loc>
+    {
+        self.thunk(id, prog_pt, fn_box, arg, spurious)
+    }
+
+    fn try_thunk_with_eq<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut AdaptonFromScratch,
+         id:ArtIdChoice<Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> AdaptonResult<Art<Res,Loc>>
+    {
+        Ok(self.thunk_with_eq(id, prog_pt, fn_box, arg, spurious, res_eq))
+    }
+
+    fn try_thunk<Arg:Eq+Hash+Debug+Clone+Send+Sync+'static,Spurious:'static+Clone+Send+Sync,Res:Eq+Debug+Clone+Send+Sync+'static>
+        (self:&mut AdaptonFromScratch,
+         id:ArtIdChoice<Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious)
+         -> AdaptonResult<Art<Res,Loc>>
+    {
+        Ok(self.thunk(id, prog_pt, fn_box, arg, spurious))
+    }
+
+    fn try_force<Res:'static+Eq+Debug+Clone+Send+Sync> (self:&mut AdaptonFromScratch,
+                                              art:&Art<Res,Loc>) -> AdaptonResult<Res>
+    {
+        match *art {
+            Art::Loc(ref index) if **index >= self.store.len() =>
+                Err(AdaptonError::DanglingLoc{loc:format!("{:?}", index)}),
+            _ => Ok(self.force(art)),
+        }
+    }
 }
 
 // Produce a value of type Res.
@@ -146,7 +281,7 @@ impl<Res:Clone+'static>
 #[derive(Clone)]
 struct App<Arg,Spurious,Res> {
     prog_pt:  ProgPt,
-    fn_box:   Rc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res>>,
+    fn_box:   Arc<Box<Fn(&mut AdaptonFromScratch, Arg, Spurious) -> Res + Send + Sync>>,
     arg:      Arg,
     spurious: Spurious,
 }
@@ -170,6 +305,7 @@ impl<Arg:'static+PartialEq+Eq+Clone,Spurious:'static+Clone,Res:'static>
     fn produce(self:&Self, st:&mut AdaptonFromScratch) -> Res {
         let f = self.fn_box.clone() ;
         st.cnt.eval += 1;
+        st.cnt.prog_pt(&self.prog_pt).eval += 1;
         f (st,self.arg.clone(),self.spurious.clone())
     }
     fn copy(self:&Self) -> Box<Producer<Res>> {
@@ -0,0 +1,1511 @@
+// A DCG that threads share.
+//
+// An `Engine` is used through `&mut Engine`, by one thread at a time.
+// A `SyncEngine` is a handle on a DCG that many threads share: each
+// thread uses a handle of its own (see `Clone`), and the handles force
+// thunks concurrently.  The table is sharded by location, and each
+// node has a lock of its own, which is never held while a producer
+// runs.  To produce or validate a thunk, a handle claims it; other
+// handles that demand the thunk meanwhile wait for its result, rather
+// than produce it again.
+//
+// The outer layer's writes are serialized against its forces, by an
+// epoch lock: forces take it shared, while `set`, and allocations that
+// may overwrite a node (`cell`, and nominal `thunk`) take it
+// exclusively.  So, each force sees all of a write, or none of it.
+// The inner layer's writes (`set_inner`, and the re-allocations of
+// producers) take no lock: producers on several threads that write
+// and observe the same cells race, as in any shared store.
+//
+// Optionally (see `SyncEngineConfig::threads`), change propagation
+// validates the independent dirty succs of a thunk concurrently, on a
+// pool of threads that the DCG owns; see `fork`.
+//
+// As an `Engine` does, a `SyncEngine` frees unreachable nodes in
+// `collect`, and may bound its cached results (see
+// `SyncEngineConfig::cache_limit`).  The collector counts the handles
+// on a node, so it runs with the epoch lock held exclusively; for it,
+// every allocation of the outer layer takes the lock, shared or not.
+
+use std::fmt::Debug;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::mem::replace;
+use std::any::{Any,TypeId};
+use std::intrinsics::type_name;
+use std::sync::{Arc,Mutex,MutexGuard,Condvar};
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::sync::mpsc::{channel,Sender,Receiver};
+use std::thread;
+use std::marker::PhantomData;
+use std::fmt::{Formatter,Result};
+use std::hash::{Hash,Hasher};
+use std::num::Zero;
+use std::panic::{catch_unwind,resume_unwind,AssertUnwindSafe};
+
+use macros::*;
+use adapton_sigs::*;
+use engine::Effect;
+
+macro_rules! syncMsg {
+    ( $st:expr ) => {{
+        format!("adapton::sync_engine:{}:{}:", $st.id, $st.stack.len())
+    }}
+}
+
+// Names provide a symbolic way to identify nodes.  Unlike those of an
+// `Engine`, the names and locations of a `SyncEngine` are compared by
+// their symbols, and hashes only hasten the comparison.
+#[derive(Clone)]
+pub struct Name {
+    hash : u64, // hash of symbol
+    symbol : Arc<NameSym>,
+}
+impl Debug for Name {
+    fn fmt(&self, f:&mut Formatter) -> Result { self.symbol.fmt(f) }
+}
+impl PartialEq for Name {
+    fn eq(&self, other:&Name) -> bool { self.hash == other.hash && self.symbol == other.symbol }
+}
+impl Eq for Name { }
+impl Hash for Name {
+    fn hash<H>(&self, state:&mut H) where H:Hasher { self.hash.hash(state) }
+}
+
+#[derive(Hash,Debug,PartialEq,Eq,Clone)]
+enum NameSym {
+    String(String),
+    Usize(usize),
+    Pair(Arc<NameSym>,Arc<NameSym>),
+    ForkL(Arc<NameSym>),
+    ForkR(Arc<NameSym>),
+}
+
+// Paths are built implicitly via the Adapton::ns command.
+#[derive(Debug,Clone)]
+enum Path {
+    Empty,
+    Child(Arc<Path>,Name,u64), // Hash of (parent, name)
+}
+impl PartialEq for Path {
+    fn eq(&self, other:&Path) -> bool {
+        match (self, other) {
+            (&Path::Empty, &Path::Empty) => true,
+            (&Path::Child(ref p1, ref n1, h1), &Path::Child(ref p2, ref n2, h2)) =>
+                h1 == h2 && n1 == n2 && (&**p1 as *const Path == &**p2 as *const Path || p1 == p2),
+            _ => false,
+        }
+    }
+}
+impl Eq for Path { }
+
+fn path_hash(path:&Path) -> u64 {
+    match *path { Path::Empty => 0, Path::Child(_, _, hash) => hash }
+}
+
+fn path_child(parent:Arc<Path>, name:Name) -> Path {
+    let hash = my_hash(&("child", path_hash(&parent), name.hash)) ;
+    Path::Child(parent, name, hash)
+}
+
+// Each location identifies a node in the DCG.
+#[derive(Clone)]
+pub struct Loc {
+    hash : u64, // hash of (path,id)
+    path : Arc<Path>,
+    id   : ArtId,
+}
+impl Debug for Loc {
+    fn fmt(&self, f:&mut Formatter) -> Result { try!(self.path.fmt(f)) ; self.id.fmt(f) }
+}
+impl PartialEq for Loc {
+    fn eq(&self, other:&Loc) -> bool { self.hash == other.hash && self.id == other.id && self.path == other.path }
+}
+impl Eq for Loc { }
+impl Hash for Loc {
+    fn hash<H>(&self, state:&mut H) where H:Hasher { self.hash.hash(state) }
+}
+
+#[derive(Hash,Debug,PartialEq,Eq,Clone)]
+enum ArtId {
+    Structural(u64), // Identifies an Art::Loc based on hashing content.
+    Nominal(Name),   // Identifies an Art::Loc based on a programmer-chosen name.
+}
+
+fn loc_of_id(path:Arc<Path>, id:ArtId) -> Arc<Loc> {
+    let hash = my_hash(&(path_hash(&path), &id)) ;
+    Arc::new(Loc{hash:hash, path:path, id:id})
+}
+
+/// A handle on a DCG that threads share.  `Adapton::new` makes a new
+/// DCG; `clone` makes another handle on the same DCG, for another
+/// thread.  Handles are equal when they share a DCG.
+///
+/// Each handle counts the work that it does (see `Adapton::cnt`).
+/// Unreachable nodes are freed by `collect`.
+pub struct SyncEngine {
+    shared : Arc<Shared>,
+    id     : usize,        // Identifies the handle, as the holder of claims.
+    stack  : Vec<Frame>,   // The producers that the handle runs; empty in the outer layer.
+    path   : Arc<Path>,    // The path of the outer layer (see `ns`).
+    epoch  : Option<bool>, // Whether the handle holds the epoch lock, and if so, whether exclusively.
+    cnt    : Cnt,
+    error  : Option<AdaptonError>, // Raised by an infallible operation; recovered by `recover`.
+}
+
+/// Configuration for a `SyncEngine`; see `SyncEngine::with_config`.
+#[derive(Debug,Clone)]
+pub struct SyncEngineConfig {
+    pub threads : usize, // Threads that validate independent dirty succs concurrently; 0 validates them one after another.
+    pub cache_limit : Option<usize>, // Maximum number of cached thunk results, least recently used first out; None means no limit.
+}
+
+impl Default for SyncEngineConfig {
+    fn default () -> SyncEngineConfig {
+        SyncEngineConfig{ threads:0, cache_limit:None }
+    }
+}
+
+const SHARDS : usize = 16 ;
+
+struct Shared {
+    table   : Vec<Mutex<HashMap<Arc<Loc>, Arc<Node>>>>, // Sharded by the hash of each loc.
+    epoch   : Mutex<Epoch>,
+    epoch_changed : Condvar,
+    claims  : Mutex<Claims>,
+    claims_changed : Condvar, // Signalled when a claim is released, or a handle joins a helper.
+    handles : AtomicUsize, // The number of handles made, to number them.
+    pool    : Option<Mutex<Sender<Arc<Job>>>>, // The jobs of the pool's threads, if any; they stop with the DCG.
+    cache   : Mutex<Cache>,
+}
+
+// The cached results of thunks, in the order of their last use, when
+// their number is limited.  Locations are kept by value, so that the
+// cache never keeps a node alive (see `collect`).  The cache is locked
+// before nodes, and never while one is locked.
+struct Cache {
+    limit  : Option<usize>,
+    clock  : u64,
+    stamps : HashMap<Loc,u64>,
+    order  : BTreeMap<u64,Loc>,
+}
+
+// The epoch lock; writers take precedence over new readers.
+struct Epoch {
+    readers : usize,
+    writer  : bool,
+    writers_waiting : usize,
+}
+
+// Claims are few (one per thunk being produced or validated), so they
+// share one lock, and the graph of handles that wait for each other
+// is consistent, for detecting cycles among threads.
+struct Claims {
+    holders : HashMap<Arc<Loc>, usize>, // The handle that claims each thunk.
+    waiting : HashMap<usize, Wait>,     // What each blocked handle waits for.
+}
+
+enum Wait {
+    Claim(Arc<Loc>), // The claim on a thunk.
+    Join(usize),     // The job of a helper handle (see `fork`).
+}
+
+// A claim on the thunk at loc; released when dropped, including by a
+// panic that aborts its producer.
+struct Claim {
+    shared : Arc<Shared>,
+    loc    : Arc<Loc>,
+}
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut claims = lock(&self.shared.claims) ;
+        claims.holders.remove(&self.loc) ;
+        self.shared.claims_changed.notify_all()
+    }
+}
+
+struct Frame {
+    loc   : Arc<Loc>,  // The currently-executing node
+    path  : Arc<Path>, // The current path for creating new nodes
+    succs : Vec<Succ>, // The currently-executing node's effects
+}
+
+#[derive(Clone)]
+struct Succ {
+    effect : Effect,
+    dep    : Dep,
+    loc    : Arc<Loc>, // Target of the effect
+    dirty  : bool,     // mutated to dirty when loc changes, or any of its successors change
+}
+
+// The value of a node, of the type that its NodeType records.
+type Value = Arc<Any+Send+Sync>;
+
+// What an edge depends on, to decide whether its target changed.
+#[derive(Clone)]
+enum Dep {
+    Alloc,          // Allocations change whenever they are dirtied.
+    Observe(Value), // The value observed by force.
+    Write(Value),   // The value written by set_inner.
+}
+
+struct Node {
+    state : Mutex<NodeState>,
+}
+
+struct NodeState {
+    res_type : NodeType,
+    eq       : fn(&Any, &Any) -> bool, // Eq, at res_type
+    kind     : Kind,
+    preds    : Vec<(Effect, Arc<Loc>)>,
+}
+
+enum Kind {
+    Pure(Value),
+    Cell(Value),
+    Comp(Comp),
+}
+
+struct Comp {
+    producer : Arc<Producer>,
+    res      : Option<Value>,
+    succs    : Vec<Succ>,
+    gen      : usize, // Counts re-allocations with another producer.
+}
+
+#[derive(Clone,Copy)]
+struct NodeType {
+    id   : TypeId,
+    name : &'static str,
+}
+impl NodeType {
+    fn of<T:'static> () -> NodeType {
+        NodeType{ id:TypeId::of::<T>(), name:unsafe { type_name::<T>() } }
+    }
+}
+impl PartialEq for NodeType {
+    fn eq(&self, other:&Self) -> bool { self.id == other.id }
+}
+impl Debug for NodeType {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "{}", self.name) }
+}
+
+fn value_eq<T:'static+Eq>(v1:&Any, v2:&Any) -> bool {
+    v1.downcast_ref::<T>() == v2.downcast_ref::<T>()
+}
+
+fn node_state<T:'static+Eq>(kind:Kind) -> NodeState {
+    NodeState{ res_type:NodeType::of::<T>(), eq:value_eq::<T>, kind:kind, preds:Vec::new() }
+}
+
+fn kind_name(kind:&Kind) -> &'static str {
+    match *kind {
+        Kind::Pure(_) => "pure node",
+        Kind::Cell(_) => "cell",
+        Kind::Comp(_) => "thunk",
+    }
+}
+
+fn check_type(loc:&Arc<Loc>, state:&NodeState, expected:NodeType) -> AdaptonResult<()> {
+    if state.res_type == expected { Ok(()) }
+    else { Err(AdaptonError::TypeMismatch{ loc:format!("{:?}", loc),
+                                           expected:format!("{:?}", expected),
+                                           found:format!("{:?}", state.res_type) }) }
+}
+
+fn kind_mismatch(loc:&Arc<Loc>, expected:&'static str, found:&'static str) -> AdaptonError {
+    AdaptonError::KindMismatch{ loc:format!("{:?}", loc), expected:expected, found:found }
+}
+
+fn value_of<T:'static+Clone>(loc:&Arc<Loc>, value:&Value) -> AdaptonResult<T> {
+    let any : &Any = &**value ;
+    match any.downcast_ref::<T>() {
+        Some(val) => Ok(val.clone()),
+        None => Err(AdaptonError::TypeMismatch{ loc:format!("{:?}", loc),
+                                                expected:format!("{:?}", NodeType::of::<T>()),
+                                                found:"another type".to_string() }),
+    }
+}
+
+fn same_value<T:'static+Eq>(value:&Value, val:&T) -> bool {
+    let any : &Any = &**value ;
+    any.downcast_ref::<T>() == Some(val)
+}
+
+trait Producer : Send+Sync {
+    fn produce (self:&Self, st:&mut SyncEngine) -> Value ;
+    fn prog_pt<'r> (self:&'r Self) -> &'r ProgPt ;
+    // Whether the result changed from old to new, by res_eq, or else by Eq.
+    fn changed (self:&Self, old:&Value, new:&Value) -> bool ;
+    fn as_any<'r> (self:&'r Self) -> &'r Any ;
+}
+
+struct App<Arg,Spurious,Res> {
+    prog_pt  : ProgPt,
+    fn_box   : Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+    arg      : Arg,
+    spurious : Spurious,
+    res_eq   : Option<ResEq<Res>>,
+}
+
+impl<Arg:'static+Eq+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Send+Sync> Producer
+    for App<Arg,Spurious,Res>
+{
+    fn produce (self:&Self, st:&mut SyncEngine) -> Value {
+        Arc::new((self.fn_box)(st, self.arg.clone(), self.spurious.clone()))
+    }
+    fn prog_pt<'r> (self:&'r Self) -> &'r ProgPt { &self.prog_pt }
+    fn changed (self:&Self, old:&Value, new:&Value) -> bool {
+        let old : &Any = &**old ;
+        let new : &Any = &**new ;
+        match (old.downcast_ref::<Res>(), new.downcast_ref::<Res>()) {
+            (Some(old), Some(new)) => match self.res_eq { None => old != new, Some(ref res_eq) => !res_eq.eq(old, new) },
+            _ => true,
+        }
+    }
+    fn as_any<'r> (self:&'r Self) -> &'r Any { self }
+}
+
+// Locks m, whether or not a panic poisoned it: the engine's own
+// updates never panic midway.
+fn lock<'r,T>(m:&'r Mutex<T>) -> MutexGuard<'r,T> {
+    match m.lock() { Ok(guard) => guard, Err(poisoned) => poisoned.into_inner() }
+}
+
+fn wait<'r,T>(cv:&Condvar, guard:MutexGuard<'r,T>) -> MutexGuard<'r,T> {
+    match cv.wait(guard) { Ok(guard) => guard, Err(poisoned) => poisoned.into_inner() }
+}
+
+impl Debug for SyncEngine {
+    fn fmt(&self, f:&mut Formatter) -> Result { write!(f, "SyncEngine{{id:{}, stack:{}}}", self.id, self.stack.len()) }
+}
+impl Hash for SyncEngine {
+    fn hash<H>(&self, state:&mut H) where H:Hasher { (&*self.shared as *const Shared as usize).hash(state) }
+}
+impl PartialEq for SyncEngine {
+    fn eq(&self, other:&Self) -> bool { &*self.shared as *const Shared == &*other.shared as *const Shared }
+}
+impl Eq for SyncEngine { }
+impl Clone for SyncEngine {
+    fn clone(&self) -> Self { handle(self.shared.clone()) }
+}
+
+fn handle(shared:Arc<Shared>) -> SyncEngine {
+    let id = shared.handles.fetch_add(1, Ordering::SeqCst) ;
+    SyncEngine{ shared:shared, id:id, stack:Vec::new(), path:Arc::new(Path::Empty),
+                epoch:None, cnt:Cnt::zero(), error:None }
+}
+
+// ---------- Table, epoch and claims:
+
+fn shard<'r>(st:&'r SyncEngine, loc:&Loc) -> &'r Mutex<HashMap<Arc<Loc>, Arc<Node>>> {
+    &st.shared.table[(loc.hash as usize) % SHARDS]
+}
+
+fn lookup(st:&SyncEngine, loc:&Arc<Loc>) -> AdaptonResult<Arc<Node>> {
+    match lock(shard(st, loc)).get(loc) {
+        None => Err(AdaptonError::DanglingLoc{loc:format!("{:?}", loc)}),
+        Some(node) => Ok(node.clone()),
+    }
+}
+
+// The node at loc, with the table's copy of loc; inserts a node with
+// the state that new makes, when there is none.  True when inserted.
+fn table_entry<F>(st:&SyncEngine, loc:Arc<Loc>, new:F) -> (Arc<Loc>, Arc<Node>, bool)
+    where F:FnOnce() -> NodeState
+{
+    let mut table = lock(shard(st, &loc)) ;
+    match table.entry(loc) {
+        Entry::Occupied(e) => (e.key().clone(), e.get().clone(), false),
+        Entry::Vacant(e) => {
+            let loc  = e.key().clone() ;
+            let node = Arc::new(Node{ state:Mutex::new(new()) }) ;
+            e.insert(node.clone()) ;
+            (loc, node, true)
+        },
+    }
+}
+
+fn table_size(st:&SyncEngine) -> usize {
+    st.shared.table.iter().fold(0, |size, table| size + lock(table).len())
+}
+
+fn epoch_enter(shared:&Shared, exclusive:bool) {
+    let mut epoch = lock(&shared.epoch) ;
+    if exclusive {
+        epoch.writers_waiting += 1 ;
+        while epoch.writer || epoch.readers > 0 { epoch = wait(&shared.epoch_changed, epoch) } ;
+        epoch.writers_waiting -= 1 ;
+        epoch.writer = true ;
+    } else {
+        while epoch.writer || epoch.writers_waiting > 0 { epoch = wait(&shared.epoch_changed, epoch) } ;
+        epoch.readers += 1 ;
+    }
+}
+
+fn epoch_exit(shared:&Shared, exclusive:bool) {
+    let mut epoch = lock(&shared.epoch) ;
+    if exclusive { epoch.writer = false } else { epoch.readers -= 1 } ;
+    shared.epoch_changed.notify_all()
+}
+
+// The outer layer has control when no producer runs on the handle.
+fn is_outer_layer(st:&SyncEngine) -> bool { st.stack.is_empty() }
+
+// Performs an operation of the outer layer, holding the epoch lock:
+// shared, or exclusive.  (An eager thunk's body holds it already; to
+// write, it exchanges a shared hold for an exclusive one.)  Operations
+// of the inner layer run within the force that holds it.
+fn outer<T,F>(st:&mut SyncEngine, exclusive:bool, body:F) -> T
+    where F:FnOnce(&mut SyncEngine) -> T
+{
+    let held = st.epoch ;
+    if !is_outer_layer(st) || held == Some(true) || (held == Some(false) && !exclusive) {
+        return body(st)
+    } ;
+    if held.is_some() { epoch_exit(&st.shared, false) } ;
+    epoch_enter(&st.shared, exclusive) ;
+    st.epoch = Some(exclusive) ;
+    let result = {
+        let st_body = &mut *st ;
+        catch_unwind(AssertUnwindSafe(move || body(st_body)))
+    } ;
+    epoch_exit(&st.shared, exclusive) ;
+    st.epoch = held ;
+    if held.is_some() { epoch_enter(&st.shared, false) } ;
+    match result { Ok(result) => result, Err(payload) => resume_unwind(payload) }
+}
+
+// Claims the thunk at loc, to produce or validate it; waits while
+// another handle claims it.  Fails on a cycle: when this handle claims
+// it already, or when its holder waits for this handle, transitively.
+fn claim_thunk(st:&mut SyncEngine, loc:&Arc<Loc>) -> AdaptonResult<Claim> {
+    let shared = st.shared.clone() ;
+    let mut claims = lock(&shared.claims) ;
+    loop {
+        if !claims.holders.contains_key(loc) { break } ;
+        match wait_chain(&claims, st.id, loc) {
+            Some(chain) => {
+                claims.waiting.remove(&st.id) ;
+                drop(claims) ;
+                return Err(cycle_error(st, chain))
+            },
+            None => (),
+        } ;
+        info!("{} claim {:?}: waiting for handle {:?}", syncMsg!(st), loc, claims.holders.get(loc));
+        claims.waiting.insert(st.id, Wait::Claim(loc.clone())) ;
+        claims = wait(&shared.claims_changed, claims) ;
+    } ;
+    claims.waiting.remove(&st.id) ;
+    claims.holders.insert(loc.clone(), st.id) ;
+    Ok(Claim{ shared:shared.clone(), loc:loc.clone() })
+}
+
+// The claimed thunks that handle id would wait for, transitively, by
+// waiting for loc, up to one that id claims; None when there is none.
+// A handle that joins a helper waits for what the helper waits for.
+fn wait_chain(claims:&Claims, id:usize, loc:&Arc<Loc>) -> Option<Vec<Arc<Loc>>> {
+    let mut chain = vec![loc.clone()] ;
+    let mut holder = match claims.holders.get(loc) { None => return None, Some(&holder) => holder } ;
+    let mut steps = 0 ;
+    while holder != id {
+        // A cycle among other handles, which they report:
+        if steps > claims.waiting.len() { return None } ;
+        steps += 1 ;
+        holder = match claims.waiting.get(&holder) {
+            None => return None,
+            Some(&Wait::Join(helper)) => helper,
+            Some(&Wait::Claim(ref next)) => {
+                chain.push(next.clone()) ;
+                match claims.holders.get(next) { None => return None, Some(&holder) => holder }
+            },
+        }
+    } ;
+    Some(chain)
+}
+
+fn loc_name(st:&SyncEngine, loc:&Arc<Loc>) -> String {
+    let prog_pt = lookup(st, loc).ok().and_then(|node| match lock(&node.state).kind {
+        Kind::Comp(ref nd) => Some(nd.producer.prog_pt().symbol),
+        _ => None,
+    }) ;
+    match prog_pt { None => format!("{:?}", loc), Some(symbol) => format!("{} {:?}", symbol, loc) }
+}
+
+// The cycle closes at the last thunk of chain, which this handle
+// claims: it is running, or its validation demanded the first.
+fn cycle_error(st:&SyncEngine, chain:Vec<Arc<Loc>>) -> AdaptonError {
+    let last = match chain.last() { None => unreachable!(), Some(last) => last.clone() } ;
+    let start = match st.stack.iter().position(|frame| frame.loc == last) { None => 0, Some(start) => start } ;
+    let mut path : Vec<String> = st.stack[start..].iter().map(|frame| loc_name(st, &frame.loc)).collect() ;
+    path.extend(chain.iter().map(|loc| loc_name(st, loc))) ;
+    AdaptonError::Cycle{path:path}
+}
+
+// ---------- Cache and collection:
+
+fn cache_untrack(cache:&mut Cache, loc:&Loc) -> bool {
+    match cache.stamps.remove(loc) {
+        None => false,
+        Some(stamp) => { cache.order.remove(&stamp) ; true }
+    }
+}
+
+// Records a use of the result of the thunk at loc: produced, validated
+// or forced.  Evicts the least recently used results, beyond the limit;
+// an evicted thunk keeps its edges, and re-produces on demand.
+fn cache_touch(st:&SyncEngine, loc:&Arc<Loc>) {
+    let mut cache = lock(&st.shared.cache) ;
+    let limit = match cache.limit { None => return, Some(limit) => limit } ;
+    cache_untrack(&mut cache, loc) ;
+    cache.clock += 1 ;
+    let clock = cache.clock ;
+    cache.stamps.insert((**loc).clone(), clock) ;
+    cache.order.insert(clock, (**loc).clone()) ;
+    while cache.stamps.len() > limit {
+        let victim = match cache.order.iter().next() { None => break, Some((_, victim)) => victim.clone() } ;
+        cache_untrack(&mut cache, &victim) ;
+        let node = match lock(shard(st, &victim)).get(&victim) { None => continue, Some(node) => node.clone() } ;
+        let mut state = lock(&node.state) ;
+        match state.kind {
+            Kind::Comp(ref mut nd) => { info!("{} cache: evicted {:?}", syncMsg!(st), &victim); nd.res = None },
+            _ => (),
+        }
+    }
+}
+
+// Forgets the result at loc: cleared by a re-allocation, or freed.
+fn cache_forget(st:&SyncEngine, loc:&Loc) {
+    let mut cache = lock(&st.shared.cache) ;
+    if cache.limit.is_some() { cache_untrack(&mut cache, loc) ; }
+}
+
+// Performs one round of collection, as `Engine` does, and returns the
+// number of nodes freed.  A node is a root when its table key has more
+// strong references than the DCG itself holds (in the table, here, and
+// in preds and succs); the others come from `Art`s and `MutArt`s held
+// outside the DCG, or in the arguments and results of other nodes.
+// No producer runs: the epoch lock is held exclusively.
+fn collect_round(st:&mut SyncEngine) -> usize {
+    let mut nodes : HashMap<Arc<Loc>, Arc<Node>> = HashMap::new() ;
+    for table in st.shared.table.iter() {
+        for (loc, node) in lock(table).iter() { nodes.insert(loc.clone(), node.clone()) ; }
+    } ;
+    let mut held : HashMap<*const Loc, usize> = HashMap::new() ;
+    for (_, node) in nodes.iter() {
+        let state = lock(&node.state) ;
+        for &(_, ref pred) in state.preds.iter() { *held.entry(&**pred as *const Loc).or_insert(0) += 1 } ;
+        match state.kind {
+            Kind::Comp(ref nd) => for succ in nd.succs.iter() { *held.entry(&*succ.loc as *const Loc).or_insert(0) += 1 },
+            _ => (),
+        }
+    } ;
+    let mut todo : Vec<Arc<Loc>> = Vec::new() ;
+    for (loc, _) in nodes.iter() {
+        let internal = 2 + match held.get(&(&**loc as *const Loc)) { None => 0, Some(n) => *n } ;
+        if Arc::strong_count(loc) > internal { todo.push(loc.clone()) }
+    } ;
+    // Mark: everything reachable from a root, via succ edges, is live.
+    let mut live : HashSet<Arc<Loc>> = HashSet::new() ;
+    while let Some(loc) = todo.pop() {
+        if live.contains(&loc) { continue } ;
+        match nodes.get(&loc) {
+            None => (),
+            Some(node) => match lock(&node.state).kind {
+                Kind::Comp(ref nd) => todo.extend(nd.succs.iter().map(|succ| succ.loc.clone())),
+                _ => (),
+            },
+        } ;
+        live.insert(loc) ;
+    } ;
+    // Sweep: free each dead node, then unlink it from the preds of the live.
+    let mut dead : HashSet<Arc<Loc>> = HashSet::new() ;
+    for (loc, node) in nodes.iter() {
+        if live.contains(loc) { continue } ;
+        info!("{} collect: freeing {:?}", syncMsg!(st), loc);
+        lock(shard(st, loc)).remove(loc) ;
+        cache_forget(st, loc) ;
+        match lock(&node.state).kind { Kind::Comp(ref nd) => st.cnt.edge_remove += nd.succs.len(), _ => () } ;
+        dead.insert(loc.clone()) ;
+    } ;
+    if dead.is_empty() { return 0 } ;
+    for (loc, node) in nodes.iter() {
+        if live.contains(loc) { lock(&node.state).preds.retain(|&(_, ref pred)| !dead.contains(pred)) }
+    } ;
+    dead.len()
+}
+
+// ---------- Change propagation:
+
+// Records a new edge from the running thunk; the outer layer records none.
+fn push_succ(st:&mut SyncEngine, succ:Succ) {
+    match st.stack.last_mut() {
+        None => (),
+        Some(frame) => { st.cnt.edge_create += 1 ; frame.succs.push(succ) },
+    }
+}
+
+fn frame_path(st:&SyncEngine) -> Arc<Path> {
+    match st.stack.last() { None => st.path.clone(), Some(frame) => frame.path.clone() }
+}
+
+// The value of a node, when it is consistent: the value of a pure
+// node or cell, or the result of a thunk with no dirty succs.
+fn ready(state:&NodeState) -> Option<Value> {
+    match state.kind {
+        Kind::Pure(ref val) | Kind::Cell(ref val) => Some(val.clone()),
+        Kind::Comp(ref nd) => if nd.succs.iter().any(|succ| succ.dirty) { None } else { nd.res.clone() },
+    }
+}
+
+// Counts a thunk that change propagation visits, and whether its
+// result was found consistent.
+fn count_change_prop(st:&mut SyncEngine, prog_pt:&ProgPt, hit:bool) {
+    let hits = if hit { 1 } else { 0 } ;
+    st.cnt.change_prop += 1 ;
+    st.cnt.hit += hits ;
+    let cnt = st.cnt.prog_pt(prog_pt) ;
+    cnt.change_prop += 1 ;
+    cnt.hit += hits ;
+}
+
+// The next step of making a node consistent; see `begin`.
+enum Step {
+    Done(Value),                // The node is consistent, with this value.
+    Succs(Claim, Vec<Succ>),    // The claimed thunk's dirty succs come first.
+}
+
+// Begins to make the node at loc consistent: done when its value is
+// consistent, or once this handle (re-)produces it; else, the thunk
+// is claimed, and its dirty succs are to be validated.
+fn begin(st:&mut SyncEngine, loc:&Arc<Loc>, node:&Arc<Node>) -> AdaptonResult<Step> {
+    let hit = {
+        let state = lock(&node.state) ;
+        match (ready(&state), &state.kind) {
+            (Some(val), &Kind::Comp(ref nd)) => Some((val, Some(nd.producer.prog_pt().clone()))),
+            (Some(val), _) => Some((val, None)),
+            (None, _) => None,
+        }} ;
+    match hit {
+        Some((val, prog_pt)) => {
+            match prog_pt { None => (), Some(prog_pt) => count_change_prop(st, &prog_pt, true) } ;
+            return Ok(Step::Done(val))
+        },
+        None => (),
+    } ;
+    let claim = try!(claim_thunk(st, loc)) ;
+    // Another handle may have made the node consistent meanwhile:
+    let (res, succs, prog_pt) = {
+        let state = lock(&node.state) ;
+        match state.kind {
+            Kind::Comp(ref nd) => (nd.res.clone(), nd.succs.clone(), Some(nd.producer.prog_pt().clone())),
+            _ => (ready(&state), Vec::new(), None),
+        }} ;
+    match (res, prog_pt) {
+        (Some(res), None) => Ok(Step::Done(res)),
+        (None, _) => Ok(Step::Done(try!(produce(st, loc, node, claim)))),
+        (Some(res), Some(prog_pt)) => {
+            if succs.iter().any(|succ| succ.dirty) {
+                info!("{} change_prop begin: {:?}", syncMsg!(st), loc);
+                count_change_prop(st, &prog_pt, false) ;
+                Ok(Step::Succs(claim, succs))
+            } else {
+                count_change_prop(st, &prog_pt, true) ;
+                Ok(Step::Done(res))
+            }
+        },
+    }
+}
+
+// Makes the node at loc consistent, and returns its value.  Traverses
+// the dirty sub-DCG below loc with a worklist, as `Engine` does: each
+// thunk ends after its dirty succs, deepest first, and re-produces at
+// the first succ whose value changed.  The worklist holds the claims
+// of the thunks that it validates.  With a pool, `fork` may validate
+// the dirty succs of a thunk at once, instead.
+fn consistent(st:&mut SyncEngine, loc:&Arc<Loc>, node:&Arc<Node>) -> AdaptonResult<Value> {
+    struct Work {
+        loc   : Arc<Loc>,
+        node  : Arc<Node>,
+        claim : Claim,
+        succs : Vec<Succ>,
+        next  : usize, // The next succ to consider
+    }
+    let mut todo : Vec<Work> = match try!(begin(st, loc, node)) {
+        Step::Done(val) => return Ok(val),
+        Step::Succs(claim, succs) => vec![Work{loc:loc.clone(), node:node.clone(), claim:claim, succs:succs, next:0}],
+    } ;
+    let mut changed = false ;
+    loop {
+        let succ : Option<Succ> = {
+            let work = match todo.last_mut() { None => unreachable!(), Some(work) => work } ;
+            if !changed && work.next == 0 {
+                match try!(fork(st, &work.node, &work.succs)) {
+                    None => (),
+                    Some(succ_changed) => { changed = succ_changed ; work.next = work.succs.len() },
+                }
+            } ;
+            if changed { None } else {
+                while work.next < work.succs.len() && !work.succs[work.next].dirty { work.next += 1 } ;
+                if work.next < work.succs.len() {
+                    work.next += 1 ;
+                    Some(work.succs[work.next - 1].clone())
+                } else { None }
+            }} ;
+        let (tgt, val) : (Arc<Node>, Value) = match succ {
+            Some(ref succ) if succ.effect == Effect::Allocate => { changed = true ; continue },
+            Some(succ) => {
+                let tgt = try!(lookup(st, &succ.loc)) ;
+                match try!(begin(st, &succ.loc, &tgt)) {
+                    Step::Done(val) => (tgt, val),
+                    Step::Succs(claim, succs) => {
+                        todo.push(Work{loc:succ.loc.clone(), node:tgt, claim:claim, succs:succs, next:0}) ;
+                        continue
+                    },
+                }
+            },
+            None => {
+                let work = match todo.pop() { None => unreachable!(), Some(work) => work } ;
+                let val = try!(end(st, &work.loc, &work.node, work.claim, changed)) ;
+                if todo.is_empty() { return Ok(val) } ;
+                (work.node, val)
+            },
+        } ;
+        let work = match todo.last() { None => unreachable!(), Some(work) => work } ;
+        let succ = &work.succs[work.next - 1] ;
+        changed = dep_changed(&tgt, &succ.dep, &val) ;
+        // The succ is consistent with the value observed by its edge,
+        // so a later force need not re-validate it:
+        if !changed { clean_succ(&work.node, work.next - 1, succ) }
+    }
+}
+
+// Ends the validation of the claimed thunk at loc: re-produces it
+// when a succ changed, else its result is consistent.
+fn end(st:&mut SyncEngine, loc:&Arc<Loc>, node:&Arc<Node>, claim:Claim, succ_changed:bool) -> AdaptonResult<Value> {
+    if succ_changed {
+        info!("{} change_prop end: {:?} has a changed succ dependency. Begin re-production:", syncMsg!(st), loc);
+        return produce(st, loc, node, claim)
+    } ;
+    info!("{} change_prop end: {:?} is clean.", syncMsg!(st), loc);
+    let (res, prog_pt) = {
+        let state = lock(&node.state) ;
+        match state.kind {
+            Kind::Comp(ref nd) => (nd.res.clone(), Some(nd.producer.prog_pt().clone())),
+            _ => (ready(&state), None),
+        }} ;
+    match (res, prog_pt) {
+        (Some(res), None) => Ok(res),
+        (Some(res), Some(_)) => { st.cnt.hit += 1 ; cache_touch(st, loc) ; Ok(res) },
+        // A re-allocation by another handle cleared the result:
+        (None, _) => produce(st, loc, node, claim),
+    }
+}
+
+// Whether the value that an edge observed (or wrote) differs from the
+// value of its target, val.
+fn dep_changed(tgt:&Node, dep:&Dep, val:&Value) -> bool {
+    let (eq, producer) = {
+        let state = lock(&tgt.state) ;
+        (state.eq, match state.kind { Kind::Comp(ref nd) => Some(nd.producer.clone()), _ => None })
+    } ;
+    match *dep {
+        Dep::Alloc => true,
+        Dep::Observe(ref old) | Dep::Write(ref old) => match producer {
+            Some(producer) => producer.changed(old, val),
+            None => !eq(&**old, &**val),
+        },
+    }
+}
+
+// Cleans the idx-th succ of node, the edge that change propagation
+// just validated; the claim on node keeps its succs as they were.
+fn clean_succ(node:&Node, idx:usize, validated:&Succ) {
+    let mut state = lock(&node.state) ;
+    match state.kind {
+        Kind::Comp(ref mut nd) => match nd.succs.get_mut(idx) {
+            Some(succ) if succ.effect == validated.effect && succ.loc == validated.loc => succ.dirty = false,
+            _ => (),
+        },
+        _ => (),
+    }
+}
+
+// ---------- Parallel change propagation:
+
+// A job validates a dirty succ on a helper handle, for the handle that
+// forks it.  The handle joins its jobs in order: it waits for those
+// that the pool's threads run, and runs the others itself, so that no
+// job waits for a thread while the pool's threads wait for it.
+struct Job {
+    helper : usize, // The id of the helper handle.
+    slot   : Mutex<Slot>,
+    done   : Condvar,
+}
+
+enum Slot {
+    Pending(SyncEngine, Arc<Loc>, Arc<Node>),
+    Running,
+    Done(SyncEngine, thread::Result<AdaptonResult<Value>>),
+}
+
+// Runs the jobs that the pool receives, until the DCG is dropped.
+fn worker(queue:&Mutex<Receiver<Arc<Job>>>) {
+    loop {
+        let job = lock(queue).recv() ;
+        match job {
+            Err(_) => return,
+            Ok(job) => match take_job(&job) {
+                None => (), // Its handle runs it.
+                Some((helper, loc, node)) => run_job(&job, helper, loc, node),
+            },
+        }
+    }
+}
+
+fn take_job(job:&Job) -> Option<(SyncEngine, Arc<Loc>, Arc<Node>)> {
+    let mut slot = lock(&job.slot) ;
+    match *slot { Slot::Pending(..) => (), _ => return None } ;
+    match replace(&mut *slot, Slot::Running) {
+        Slot::Pending(helper, loc, node) => Some((helper, loc, node)),
+        _ => unreachable!(),
+    }
+}
+
+fn run_job(job:&Job, mut helper:SyncEngine, loc:Arc<Loc>, node:Arc<Node>) {
+    let result = {
+        let helper = &mut helper ;
+        catch_unwind(AssertUnwindSafe(move || recover(helper, |st| consistent(st, &loc, &node))))
+    } ;
+    *lock(&job.slot) = Slot::Done(helper, result) ;
+    job.done.notify_all()
+}
+
+// Waits for a job, or runs it, and returns its helper and result.
+// Meanwhile, the handle waits for the helper, for detecting cycles.
+fn join(st:&mut SyncEngine, job:&Job) -> (SyncEngine, thread::Result<AdaptonResult<Value>>) {
+    {
+        let mut claims = lock(&st.shared.claims) ;
+        claims.waiting.insert(st.id, Wait::Join(job.helper)) ;
+        st.shared.claims_changed.notify_all()
+    } ;
+    match take_job(job) { None => (), Some((helper, loc, node)) => run_job(job, helper, loc, node) } ;
+    let done = {
+        let mut slot = lock(&job.slot) ;
+        while let Slot::Running = *slot { slot = wait(&job.done, slot) } ;
+        replace(&mut *slot, Slot::Running)
+    } ;
+    lock(&st.shared.claims).waiting.remove(&st.id) ;
+    match done { Slot::Done(helper, result) => (helper, result), _ => unreachable!() }
+}
+
+// Adds the counts of a helper's work to those of st.
+fn absorb_cnt(st:&mut SyncEngine, cnt:Cnt) {
+    st.cnt.dirty += cnt.dirty ;
+    st.cnt.eval += cnt.eval ;
+    st.cnt.change_prop += cnt.change_prop ;
+    st.cnt.hit += cnt.hit ;
+    st.cnt.create += cnt.create ;
+    st.cnt.realloc += cnt.realloc ;
+    st.cnt.edge_create += cnt.edge_create ;
+    st.cnt.edge_remove += cnt.edge_remove ;
+    if st.stack.len() + cnt.stack_max > st.cnt.stack_max { st.cnt.stack_max = st.stack.len() + cnt.stack_max } ;
+    for (symbol, c) in cnt.by_prog_pt.into_iter() {
+        let sum = st.cnt.by_prog_pt.entry(symbol).or_insert(ProgPtCnt::zero()) ;
+        sum.dirty += c.dirty ;
+        sum.eval += c.eval ;
+        sum.change_prop += c.change_prop ;
+        sum.hit += c.hit ;
+        sum.create += c.create ;
+        sum.realloc += c.realloc ;
+    }
+}
+
+// Validates the dirty succs of the claimed thunk node at once, when at
+// least two of them are thunks to validate, and returns whether one
+// changed; None leaves them to the worklist.  Each such thunk is a job
+// for the pool; the others are validated here.  The results merge in
+// order, as the worklist would consider them: the first succ that
+// changed decides, up to the first dirty allocation, and the errors of
+// the later ones are dropped.  (Their validation is speculative, as
+// the thunk's re-production may not demand them; but their results
+// are consistent, so they are kept.)
+fn fork(st:&mut SyncEngine, node:&Arc<Node>, succs:&Vec<Succ>) -> AdaptonResult<Option<bool>> {
+    if st.shared.pool.is_none() { return Ok(None) } ;
+    let mut dirty : Vec<(usize, Arc<Node>, bool)> = Vec::new() ;
+    let mut alloc = false ;
+    for (idx, succ) in succs.iter().enumerate() {
+        if !succ.dirty { continue } ;
+        if succ.effect == Effect::Allocate { alloc = true ; break } ;
+        let tgt = try!(lookup(st, &succ.loc)) ;
+        let is_ready = ready(&lock(&tgt.state)).is_some() ;
+        dirty.push((idx, tgt, is_ready))
+    } ;
+    if dirty.iter().filter(|&&(_, _, is_ready)| !is_ready).count() < 2 { return Ok(None) } ;
+    info!("{} fork: {} dirty succs", syncMsg!(st), dirty.len());
+    let jobs : Vec<Option<Arc<Job>>> = dirty.iter().map(|&(idx, ref tgt, is_ready)| {
+        if is_ready { return None } ;
+        let helper = handle(st.shared.clone()) ;
+        let job = Arc::new(Job{ helper:helper.id, slot:Mutex::new(Slot::Pending(helper, succs[idx].loc.clone(), tgt.clone())),
+                                done:Condvar::new() }) ;
+        // When the pool has stopped, the job waits for its handle:
+        match st.shared.pool { None => unreachable!(), Some(ref pool) => lock(pool).send(job.clone()).ok() } ;
+        Some(job)
+    }).collect() ;
+    // Every job ends before any result merges, as the helpers run
+    // within the epoch that st holds:
+    let mut results = Vec::new() ;
+    for job in jobs.iter() {
+        results.push(match *job {
+            None => None,
+            Some(ref job) => {
+                let (helper, result) = join(st, job) ;
+                absorb_cnt(st, helper.cnt) ;
+                Some(result)
+            },
+        })
+    } ;
+    for ((idx, tgt, _), result) in dirty.into_iter().zip(results.into_iter()) {
+        let succ = &succs[idx] ;
+        let val = match result {
+            None => try!(consistent(st, &succ.loc, &tgt)),
+            Some(Err(payload)) => resume_unwind(payload), // Not an engine error.
+            Some(Ok(result)) => try!(result),
+        } ;
+        if dep_changed(&tgt, &succ.dep, &val) { return Ok(Some(true)) } ;
+        clean_succ(node, idx, succ)
+    } ;
+    Ok(Some(alloc))
+}
+
+// Runs the producer of the claimed thunk at loc, and caches its result.
+fn produce(st:&mut SyncEngine, loc:&Arc<Loc>, node:&Arc<Node>, claim:Claim) -> AdaptonResult<Value> {
+    info!("{} produce begin: {:?}", syncMsg!(st), loc);
+    let (producer, gen, succs) = {
+        let mut state = lock(&node.state) ;
+        let kind = kind_name(&state.kind) ;
+        match state.kind {
+            Kind::Comp(ref mut nd) => {
+                nd.res = None ;
+                (nd.producer.clone(), nd.gen, replace(&mut nd.succs, Vec::new()))
+            },
+            _ => return Err(kind_mismatch(loc, "thunk", kind)),
+        }} ;
+    try!(revoke_succs(st, loc, &succs)) ;
+    st.cnt.eval += 1 ;
+    st.cnt.prog_pt(producer.prog_pt()).eval += 1 ;
+    st.stack.push(Frame{loc:loc.clone(), path:loc.path.clone(), succs:Vec::new()}) ;
+    if st.stack.len() > st.cnt.stack_max { st.cnt.stack_max = st.stack.len() } ;
+    // A panic leaves the frame to `recover`, and releases the claim as
+    // it unwinds; the result stays empty.
+    let res = producer.produce(st) ;
+    let frame = match st.stack.pop() { None => unreachable!(), Some(frame) => frame } ;
+    assert!( &frame.loc == loc );
+    for succ in frame.succs.iter() {
+        let tgt = try!(lookup(st, &succ.loc)) ;
+        lock(&tgt.state).preds.push((succ.effect.clone(), loc.clone())) ;
+    } ;
+    let (cached, orphans) = {
+        let mut state = lock(&node.state) ;
+        match state.kind {
+            Kind::Comp(ref mut nd) => {
+                // Unless another handle re-allocated the thunk meanwhile,
+                // with another producer:
+                let cached = nd.gen == gen ;
+                if cached { nd.res = Some(res.clone()) } ;
+                nd.succs = frame.succs ;
+                (cached, Vec::new())
+            },
+            // Another handle replaced the thunk by a cell meanwhile:
+            _ => (false, frame.succs),
+        }} ;
+    try!(revoke_succs(st, loc, &orphans)) ;
+    if cached { cache_touch(st, loc) } ;
+    drop(claim) ;
+    info!("{} produce end: {:?}", syncMsg!(st), loc);
+    Ok(res)
+}
+
+fn revoke_succs(st:&mut SyncEngine, src:&Arc<Loc>, succs:&Vec<Succ>) -> AdaptonResult<()> {
+    st.cnt.edge_remove += succs.len() ;
+    for succ in succs.iter() {
+        let node = try!(lookup(st, &succ.loc)) ;
+        lock(&node.state).preds.retain(|&(_, ref pred)| pred != src) ;
+    } ;
+    Ok(())
+}
+
+// Marks the edges from src to tgt with the given effect dirty; true
+// when one of them was clean.
+fn dirty_edge(st:&SyncEngine, src:&Arc<Loc>, effect:&Effect, tgt:&Arc<Loc>) -> AdaptonResult<bool> {
+    let node = try!(lookup(st, src)) ;
+    let mut state = lock(&node.state) ;
+    let mut fresh = false ;
+    match state.kind {
+        Kind::Comp(ref mut nd) => for succ in nd.succs.iter_mut() {
+            if &succ.effect == effect && &succ.loc == tgt && !succ.dirty { succ.dirty = true ; fresh = true }
+        },
+        _ => (),
+    } ;
+    Ok(fresh)
+}
+
+// Dirties the observers of each loc in todo, transitively, locking one
+// node at a time.
+fn dirty_observers(st:&mut SyncEngine, mut todo:Vec<Arc<Loc>>) -> AdaptonResult<()> {
+    let mut visited : HashSet<Arc<Loc>> = HashSet::new() ;
+    while let Some(loc) = todo.pop() {
+        if !visited.insert(loc.clone()) { continue } ;
+        info!("{} dirty_observers: {:?}", syncMsg!(st), loc);
+        let node = try!(lookup(st, &loc)) ;
+        let (preds, prog_pt) = {
+            let state = lock(&node.state) ;
+            (state.preds.iter().filter(|&&(ref effect, _)| effect == &Effect::Observe)
+             .map(|&(_, ref pred)| pred.clone()).collect::<Vec<_>>(),
+             match state.kind { Kind::Comp(ref nd) => Some(nd.producer.prog_pt().clone()), _ => None })
+        } ;
+        st.cnt.dirty += 1 ;
+        match prog_pt { None => (), Some(prog_pt) => st.cnt.prog_pt(&prog_pt).dirty += 1 } ;
+        for pred in preds {
+            if try!(dirty_edge(st, &pred, &Effect::Observe, &loc)) { todo.push(pred) }
+        }
+    } ;
+    Ok(())
+}
+
+// Dirties the allocators and writers of loc, which asserted a value
+// that may now be overwritten, and the observers of all of them.
+fn dirty_alloc(st:&mut SyncEngine, loc:&Arc<Loc>) -> AdaptonResult<()> {
+    info!("{} dirty_alloc: {:?}", syncMsg!(st), loc);
+    let node = try!(lookup(st, loc)) ;
+    let preds = lock(&node.state).preds.clone() ;
+    let mut todo = vec![loc.clone()] ;
+    for (effect, pred) in preds {
+        if effect != Effect::Observe && try!(dirty_edge(st, &pred, &effect, loc)) { todo.push(pred) }
+    } ;
+    dirty_observers(st, todo)
+}
+
+// ---------- Operations:
+
+// Reports an error raised by an infallible operation: records it for
+// the nearest enclosing fallible operation (see `recover`), and panics.
+fn fail<T>(st:&mut SyncEngine, err:AdaptonError) -> T {
+    let msg = format!("{}", err) ;
+    st.error = Some(err) ;
+    panic!("adapton::sync_engine: {}", msg)
+}
+
+// Performs a fallible operation on behalf of a `try_` method, as
+// `Engine` does.  The claims of the producers that a panic aborts are
+// released as it unwinds; this pops their frames.
+fn recover<T,F>(st:&mut SyncEngine, body:F) -> AdaptonResult<T>
+    where F:FnOnce(&mut SyncEngine) -> AdaptonResult<T>
+{
+    st.error = None ;
+    let depth = st.stack.len() ;
+    let result = {
+        let st_body = &mut *st ;
+        catch_unwind(AssertUnwindSafe(move || body(st_body)))
+    } ;
+    match result {
+        Ok(result) => result,
+        Err(payload) => {
+            st.stack.truncate(depth) ;
+            match st.error.take() {
+                None => resume_unwind(payload), // Not an engine error.
+                Some(err) => Err(err),
+            }
+        }
+    }
+}
+
+fn do_put_shared<T:'static+Eq+Hash+Debug+Clone+Send+Sync> (st:&mut SyncEngine, val:T) -> AdaptonResult<Art<T,Loc>> {
+    let hash = my_hash(&(NodeType::of::<T>().name, &val)) ;
+    let loc  = loc_of_id(Arc::new(Path::Empty), ArtId::Structural(hash)) ;
+    let (loc, node, is_new) = table_entry(st, loc, || node_state::<T>(Kind::Pure(Arc::new(val.clone())))) ;
+    if is_new {
+        info!("{} put shared: {:?} <--- {:?}", syncMsg!(st), &loc, &val);
+        st.cnt.create += 1 ;
+        return Ok(Art::Loc(loc))
+    } ;
+    let pure = {
+        let state = lock(&node.state) ;
+        try!(check_type(&loc, &state, NodeType::of::<T>())) ;
+        match state.kind {
+            Kind::Pure(ref pure) => pure.clone(),
+            ref kind => return Err(kind_mismatch(&loc, "pure node", kind_name(kind))),
+        }} ;
+    if same_value(&pure, &val) { return Ok(Art::Loc(loc)) } ;
+    let first : T = try!(value_of(&loc, &pure)) ;
+    Err(AdaptonError::HashCollision{hash:format!("{:016x}", hash),
+                                    first:format!("{:?}", first),
+                                    second:format!("{:?}", val)})
+}
+
+fn do_cell<T:'static+Eq+Debug+Clone+Send+Sync> (st:&mut SyncEngine, nm:Name, val:T) -> AdaptonResult<MutArt<T,Loc>> {
+    let loc = loc_of_id(frame_path(st), ArtId::Nominal(nm)) ;
+    info!("{} alloc cell: {:?} <--- {:?}", syncMsg!(st), &loc, &val);
+    let (loc, node, is_new) = table_entry(st, loc, || node_state::<T>(Kind::Cell(Arc::new(val.clone())))) ;
+    if is_new { st.cnt.create += 1 } else {
+        let (changed, retired) = {
+            let mut state = lock(&node.state) ;
+            try!(check_type(&loc, &state, NodeType::of::<T>())) ;
+            let same = match state.kind {
+                Kind::Cell(ref cell) => Some(same_value(cell, &val)),
+                Kind::Comp(_) => None,
+                ref kind => return Err(kind_mismatch(&loc, "cell", kind_name(kind))),
+            } ;
+            match same {
+                Some(true) => (false, Vec::new()),
+                Some(false) => { state.kind = Kind::Cell(Arc::new(val.clone())) ; (true, Vec::new()) },
+                None => {
+                    // Case: A thunk in a prior run; it becomes a cell,
+                    // and keeps its preds.
+                    info!("{} alloc cell: replacing thunk {:?}", syncMsg!(st), &loc);
+                    match replace(&mut state.kind, Kind::Cell(Arc::new(val.clone()))) {
+                        Kind::Comp(nd) => (true, nd.succs),
+                        _ => unreachable!(),
+                    }
+                },
+            }} ;
+        if !retired.is_empty() { cache_forget(st, &loc) } ;
+        try!(revoke_succs(st, &loc, &retired)) ;
+        if changed { try!(dirty_alloc(st, &loc)) }
+    } ;
+    push_succ(st, Succ{loc:loc.clone(), dep:Dep::Alloc, effect:Effect::Allocate, dirty:false}) ;
+    Ok(MutArt{loc:loc, phantom:PhantomData})
+}
+
+fn do_set<T:'static+Eq+Debug+Clone+Send+Sync> (st:&mut SyncEngine, cell:&MutArt<T,Loc>, val:T) -> AdaptonResult<()> {
+    info!("{} do_set: {:?} <--- {:?}", syncMsg!(st), cell, val);
+    let node = try!(lookup(st, &cell.loc)) ;
+    let changed = {
+        let mut state = lock(&node.state) ;
+        try!(check_type(&cell.loc, &state, NodeType::of::<T>())) ;
+        let same = match state.kind {
+            Kind::Cell(ref v) => same_value(v, &val),
+            ref kind => return Err(kind_mismatch(&cell.loc, "cell", kind_name(kind))),
+        } ;
+        if !same { state.kind = Kind::Cell(Arc::new(val)) } ;
+        !same
+    } ;
+    if changed { dirty_alloc(st, &cell.loc) } else { Ok(()) }
+}
+
+fn do_set_inner<T:'static+Eq+Debug+Clone+Send+Sync> (st:&mut SyncEngine, cell:MutArt<T,Loc>, val:T) -> AdaptonResult<()> {
+    try!(do_set(st, &cell, val.clone())) ;
+    push_succ(st, Succ{loc:cell.loc, dep:Dep::Write(Arc::new(val)), effect:Effect::Write, dirty:false}) ;
+    Ok(())
+}
+
+fn do_thunk<Arg:'static+Eq+Hash+Debug+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Debug+Clone+Send+Sync>
+    (st:&mut SyncEngine,
+     id:ArtIdChoice<Name>,
+     prog_pt:ProgPt,
+     fn_box:Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+     arg:Arg, spurious:Spurious,
+     res_eq:Option<ResEq<Res>>)
+     -> AdaptonResult<Art<Res,Loc>>
+{
+    let hash = match id {
+        ArtIdChoice::Eager => return Ok(Art::Rc(Arc::new(fn_box(st, arg, spurious)))),
+        ArtIdChoice::Structural => Some(my_hash(&(&prog_pt, &arg))),
+        ArtIdChoice::Nominal(_) => None,
+    } ;
+    let loc = match (hash, id) {
+        (Some(hash), _) => loc_of_id(frame_path(st), ArtId::Structural(hash)),
+        (None, ArtIdChoice::Nominal(nm)) => loc_of_id(frame_path(st), ArtId::Nominal(nm)),
+        (None, _) => unreachable!(),
+    } ;
+    info!("{} alloc thunk: {:?} ;; {:?} ;; {:?}", syncMsg!(st), &loc, &prog_pt.symbol, &arg);
+    let producer : Arc<Producer> =
+        Arc::new(App{prog_pt:prog_pt.clone(), fn_box:fn_box, arg:arg.clone(), spurious:spurious, res_eq:res_eq}) ;
+    let comp = |producer:Arc<Producer>| Kind::Comp(Comp{producer:producer, res:None, succs:Vec::new(), gen:0}) ;
+    let (loc, node, is_new) = table_entry(st, loc, || node_state::<Res>(comp(producer.clone()))) ;
+    if is_new {
+        st.cnt.create += 1 ;
+        st.cnt.prog_pt(&prog_pt).create += 1 ;
+    } else {
+        let mut state = lock(&node.state) ;
+        try!(check_type(&loc, &state, NodeType::of::<Res>())) ;
+        let same = match state.kind {
+            Kind::Comp(ref nd) => Some(match nd.producer.as_any().downcast_ref::<App<Arg,Spurious,Res>>() {
+                Some(app) => app.prog_pt == prog_pt && app.arg == arg,
+                None => false,
+            }),
+            Kind::Cell(_) if hash.is_none() => None,
+            ref kind => return Err(kind_mismatch(&loc, "thunk", kind_name(kind))),
+        } ;
+        match (same, hash) {
+            // Structural thunks with the same hash are the same, unless the hash collides:
+            (Some(true), Some(_)) => (),
+            (_, Some(hash)) => return Err(AdaptonError::HashCollision{hash:format!("{:016x}", hash),
+                                                                      first:format!("{:?}", loc),
+                                                                      second:format!("Some({:?}) {:?}", prog_pt, arg)}),
+            (Some(true), None) => {
+                // The same producer; it keeps its result (but takes the new res_eq):
+                match state.kind { Kind::Comp(ref mut nd) => nd.producer = producer, _ => unreachable!() }
+            },
+            (Some(false), None) => {
+                // A new argument, or another producer, which runs on demand:
+                info!("{} alloc thunk: {:?} has a new producer", syncMsg!(st), &loc);
+                match state.kind {
+                    Kind::Comp(ref mut nd) => { nd.producer = producer ; nd.res = None ; nd.gen += 1 },
+                    _ => unreachable!(),
+                } ;
+                drop(state) ;
+                cache_forget(st, &loc) ;
+                st.cnt.realloc += 1 ;
+                st.cnt.prog_pt(&prog_pt).realloc += 1 ;
+                try!(dirty_alloc(st, &loc))
+            },
+            (None, None) => {
+                // Case: A cell in a prior run; it becomes a thunk, and keeps its preds.
+                info!("{} alloc thunk: replacing cell {:?}", syncMsg!(st), &loc);
+                state.kind = comp(producer) ;
+                drop(state) ;
+                try!(dirty_alloc(st, &loc))
+            },
+        }
+    } ;
+    // Structural thunks are never re-allocated with other producers,
+    // so their allocations need no edges.
+    if hash.is_none() {
+        push_succ(st, Succ{loc:loc.clone(), dep:Dep::Alloc, effect:Effect::Allocate, dirty:false})
+    } ;
+    Ok(Art::Loc(loc))
+}
+
+fn do_force<T:'static+Eq+Debug+Clone+Send+Sync> (st:&mut SyncEngine, art:&Art<T,Loc>) -> AdaptonResult<T> {
+    match *art {
+        Art::Rc(ref v) => Ok((**v).clone()),
+        Art::Loc(ref loc) => {
+            let node = try!(lookup(st, loc)) ;
+            let is_comp = {
+                let state = lock(&node.state) ;
+                try!(check_type(loc, &state, NodeType::of::<T>())) ;
+                match state.kind { Kind::Comp(_) => true, _ => false }
+            } ;
+            let val = try!(consistent(st, loc, &node)) ;
+            if is_comp { cache_touch(st, loc) } ;
+            let res : T = try!(value_of(loc, &val)) ;
+            info!("{} force {:?}: {:?}", syncMsg!(st), loc, &res);
+            push_succ(st, Succ{loc:loc.clone(), dep:Dep::Observe(val), effect:Effect::Observe, dirty:false}) ;
+            Ok(res)
+        }
+    }
+}
+
+// Whether an allocation at the outer layer may overwrite a node.
+fn is_write<N>(id:&ArtIdChoice<N>) -> bool {
+    match *id { ArtIdChoice::Nominal(_) => true, _ => false }
+}
+
+impl SyncEngine {
+    /// Makes a new DCG, with the given configuration; `Adapton::new`
+    /// uses `SyncEngineConfig::default()`.  With threads, the DCG owns
+    /// a pool of them, which stop when its last handle is dropped.
+    pub fn with_config (config:SyncEngineConfig) -> SyncEngine {
+        let pool = if config.threads == 0 { None } else {
+            let (jobs, queue) = channel::<Arc<Job>>() ;
+            let queue = Arc::new(Mutex::new(queue)) ;
+            for _ in 0..config.threads {
+                let queue = queue.clone() ;
+                thread::spawn(move || worker(&queue)) ;
+            } ;
+            Some(Mutex::new(jobs))
+        } ;
+        let shared = Shared{
+            table   : (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            epoch   : Mutex::new(Epoch{ readers:0, writer:false, writers_waiting:0 }),
+            epoch_changed : Condvar::new(),
+            claims  : Mutex::new(Claims{ holders:HashMap::new(), waiting:HashMap::new() }),
+            claims_changed : Condvar::new(),
+            handles : AtomicUsize::new(0),
+            pool    : pool,
+            cache   : Mutex::new(Cache{ limit:config.cache_limit, clock:0, stamps:HashMap::new(), order:BTreeMap::new() }),
+        } ;
+        handle(Arc::new(shared))
+    }
+
+    /// Reclaims every DCG node that is unreachable, as `Engine::collect`
+    /// does: it has no outstanding `Art` or `MutArt` handle, and no live
+    /// predecessor that may demand it again.  Waits until no handle
+    /// forces or allocates.  Used by the outer layer; in the inner layer,
+    /// frees nothing.  Counts the freed nodes in `Cnt::collected`, and
+    /// returns their number.
+    pub fn collect (self:&mut Self) -> usize {
+        if !is_outer_layer(self) { return 0 } ;
+        let freed = outer(self, true, |st| {
+            let mut freed = 0 ;
+            loop {
+                // Freeing a node drops the arguments and results it holds,
+                // which may release the last handles of other nodes.
+                let round = collect_round(st) ;
+                if round == 0 { break } else { freed += round }
+            } ;
+            freed
+        }) ;
+        self.cnt.collected += freed ;
+        freed
+    }
+}
+
+impl Adapton for SyncEngine {
+    type Name = Name;
+    type Loc  = Loc;
+
+    fn new () -> SyncEngine {
+        SyncEngine::with_config(SyncEngineConfig::default())
+    }
+
+    fn name_of_string (self:&mut SyncEngine, sym:String) -> Name {
+        let s = NameSym::String(sym) ;
+        Name{ hash:my_hash(&s), symbol:Arc::new(s) }
+    }
+
+    fn name_of_usize (self:&mut SyncEngine, sym:usize) -> Name {
+        let s = NameSym::Usize(sym) ;
+        Name{ hash:my_hash(&s), symbol:Arc::new(s) }
+    }
+
+    fn name_pair (self:&mut SyncEngine, fst:Name, snd:Name) -> Name {
+        let h = my_hash( &("pair", fst.hash, snd.hash) ) ;
+        Name{ hash:h, symbol:Arc::new(NameSym::Pair(fst.symbol, snd.symbol)) }
+    }
+
+    fn name_fork (self:&mut SyncEngine, nm:Name) -> (Name, Name) {
+        let h1 = my_hash( &("fork_l", nm.hash) ) ;
+        let h2 = my_hash( &("fork_r", nm.hash) ) ;
+        (Name{ hash:h1, symbol:Arc::new(NameSym::ForkL(nm.symbol.clone())) },
+         Name{ hash:h2, symbol:Arc::new(NameSym::ForkR(nm.symbol)) })
+    }
+
+    fn ns<T,F> (self:&mut Self, nm:Name, body:F) -> T where F:FnOnce(&mut Self) -> T {
+        let path_body = Arc::new(path_child(frame_path(self), nm)) ;
+        let path_pre = match self.stack.last_mut() {
+            None => replace(&mut self.path, path_body),
+            Some(frame) => replace(&mut frame.path, path_body),
+        } ;
+        let x = body(self) ;
+        match self.stack.last_mut() {
+            None => self.path = path_pre,
+            Some(frame) => frame.path = path_pre,
+        } ;
+        x
+    }
+
+    fn cnt<Res,F> (self:&mut Self, body:F) -> (Res,Cnt)
+        where F:FnOnce(&mut Self) -> Res
+    {
+        let c = self.cnt.clone() ;
+        self.cnt.stack_max = self.stack.len() ; // measures the body, alone
+        let x = body(self) ;
+        self.cnt.table_size = table_size(self) ;
+        let d = self.cnt.clone() - c.clone() ;
+        if c.stack_max > self.cnt.stack_max { self.cnt.stack_max = c.stack_max } ;
+        (x, d)
+    }
+
+    fn put<T:Eq+Send+Sync> (self:&mut SyncEngine, x:T) -> Art<T,Self::Loc> { Art::Rc(Arc::new(x)) }
+
+    fn put_shared<T:'static+Eq+Hash+Debug+Clone+Send+Sync> (self:&mut SyncEngine, x:T) -> Art<T,Self::Loc> {
+        match outer(self, false, |st| do_put_shared(st, x)) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_put_shared<T:'static+Eq+Hash+Debug+Clone+Send+Sync> (self:&mut SyncEngine, x:T) -> AdaptonResult<Art<T,Self::Loc>> {
+        outer(self, false, |st| recover(st, |st| do_put_shared(st, x)))
+    }
+
+    fn cell<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut SyncEngine, nm:Name, val:T) -> MutArt<T,Self::Loc> {
+        match outer(self, true, |st| do_cell(st, nm, val)) { Ok(cell) => cell, Err(err) => fail(self, err) }
+    }
+
+    fn try_cell<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut SyncEngine, nm:Name, val:T) -> AdaptonResult<MutArt<T,Self::Loc>> {
+        outer(self, true, |st| recover(st, |st| do_cell(st, nm, val)))
+    }
+
+    fn set<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) {
+        match self.try_set(cell, val) { Ok(()) => (), Err(err) => fail(self, err) }
+    }
+
+    fn try_set<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) -> AdaptonResult<()> {
+        if !is_outer_layer(self) {
+            let thunk = match self.stack.last() { None => unreachable!(), Some(frame) => format!("{:?}", frame.loc) } ;
+            return Err(AdaptonError::SetInInnerLayer{loc:format!("{:?}", cell.loc), thunk:thunk})
+        } ;
+        outer(self, true, |st| recover(st, |st| do_set(st, &cell, val)))
+    }
+
+    fn set_inner<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) {
+        match self.try_set_inner(cell, val) { Ok(()) => (), Err(err) => fail(self, err) }
+    }
+
+    fn try_set_inner<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut Self, cell:MutArt<T,Self::Loc>, val:T) -> AdaptonResult<()> {
+        if is_outer_layer(self) {
+            return Err(AdaptonError::SetInnerInOuterLayer{loc:format!("{:?}", cell.loc)})
+        } ;
+        recover(self, |st| do_set_inner(st, cell, val))
+    }
+
+    fn thunk<Arg:'static+Eq+Hash+Debug+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut SyncEngine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious)
+         -> Art<Res,Self::Loc>
+    {
+        let write = is_write(&id) ;
+        match outer(self, write, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, None)) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_thunk<Arg:'static+Eq+Hash+Debug+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut SyncEngine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious)
+         -> AdaptonResult<Art<Res,Self::Loc>>
+    {
+        let write = is_write(&id) ;
+        outer(self, write, |st| recover(st, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, None)))
+    }
+
+    fn thunk_with_eq<Arg:'static+Eq+Hash+Debug+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut SyncEngine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> Art<Res,Self::Loc>
+    {
+        let write = is_write(&id) ;
+        match outer(self, write, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, Some(res_eq))) { Ok(art) => art, Err(err) => fail(self, err) }
+    }
+
+    fn try_thunk_with_eq<Arg:'static+Eq+Hash+Debug+Clone+Send+Sync,Spurious:'static+Clone+Send+Sync,Res:'static+Eq+Debug+Clone+Send+Sync>
+        (self:&mut SyncEngine,
+         id:ArtIdChoice<Self::Name>,
+         prog_pt:ProgPt,
+         fn_box:Arc<Box<Fn(&mut SyncEngine, Arg, Spurious) -> Res + Send + Sync>>,
+         arg:Arg, spurious:Spurious,
+         res_eq:ResEq<Res>)
+         -> AdaptonResult<Art<Res,Self::Loc>>
+    {
+        let write = is_write(&id) ;
+        outer(self, write, |st| recover(st, |st| do_thunk(st, id, prog_pt, fn_box, arg, spurious, Some(res_eq))))
+    }
+
+    fn force<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut SyncEngine, art:&Art<T,Self::Loc>) -> T {
+        match outer(self, false, |st| do_force(st, art)) { Ok(res) => res, Err(err) => fail(self, err) }
+    }
+
+    fn try_force<T:'static+Eq+Debug+Clone+Send+Sync> (self:&mut SyncEngine, art:&Art<T,Self::Loc>) -> AdaptonResult<T> {
+        outer(self, false, |st| recover(st, |st| do_force(st, art)))
+    }
+}
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn sum (st:&mut Engine, ts:Vec<Art<u64,Loc>>) -> u64 {
+    let mut s = 0 ;
+    for t in ts.iter() { s += st.force(t) } ;
+    s
+}
+
+#[test]
+fn batch_dirties_once_and_drops_no_op_writes() {
+    let mut st = Engine::new();
+    let mut cells = Vec::new();
+    for i in 0..3 {
+        let nm = st.name_of_usize(i);
+        cells.push(st.cell(nm, i as u64))
+    };
+    let arts : Vec<Art<u64,Loc>> = cells.iter().map(|c| st.read_only(c.clone())).collect();
+    let t = thunk!(&mut st, sum, ts:arts);
+    assert_eq!(st.force(&t), 3);
+    // Two changed cells, and one no-op write: `sum` is dirtied once.
+    let cnt = st.batch(|tx| -> Result<(),()> {
+        tx.set(cells[0].clone(), 10);
+        tx.set(cells[1].clone(), 1);
+        tx.set(cells[2].clone(), 20);
+        Ok(())
+    }).unwrap();
+    assert_eq!(cnt.dirty, 3);
+    assert_eq!(cnt.by_prog_pt["sum"].dirty, 1);
+    assert_eq!(st.force(&t), 31);
+    // A failed batch applies nothing:
+    let res = st.batch(|tx| {
+        tx.set(cells[0].clone(), 100);
+        Err("failed")
+    });
+    assert_eq!(res, Err("failed"));
+    let (v, cnt) = st.cnt(|st| st.force(&t));
+    assert_eq!((v, cnt.eval), (31, 0));
+}
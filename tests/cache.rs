@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn double (_st:&mut Engine, x:u64) -> u64 { x * 2 }
+
+#[test]
+fn bounded_cache_evicts_and_reproduces() {
+    let mut st = Engine::with_config(EngineConfig{ cache_limit:Some(1),
+                                                   eviction:Eviction::LeastRecentlyUsed,
+                                                   check_collisions:false,
+                                                   record_explanations:false });
+    let t1 = thunk!(&mut st, double, x:1);
+    let t2 = thunk!(&mut st, double, x:2);
+    let (_, cnt) = st.cnt(|st| { st.force(&t1) ; st.force(&t2) });
+    assert_eq!(cnt.eval, 2);
+    // Forcing t2 evicted the result of t1:
+    let (r, cnt) = st.cnt(|st| st.force(&t1));
+    assert_eq!((r, cnt.eval), (2, 1));
+    let (r, cnt) = st.cnt(|st| st.force(&t1));
+    assert_eq!((r, cnt.eval), (2, 0));
+}
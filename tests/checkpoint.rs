@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+
+#[test]
+fn rollback_restores_without_recomputing() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let o  = thunk!(&mut st, observe, t:a.clone());
+    assert_eq!(st.force(&o), 101);
+    let cp = st.checkpoint();
+    st.set(c.clone(), 5);
+    assert_eq!(st.force(&o), 105);
+    st.rollback(cp);
+    // The cell, the cached result and the (clean) edges are restored:
+    let (v, cnt) = st.cnt(|st| (st.force(&a), st.force(&o)));
+    assert_eq!(v, (1, 101));
+    assert_eq!(cnt.eval, 0);
+    // Rolling back an edit that was never forced:
+    let cp = st.checkpoint();
+    st.set(c, 7);
+    st.rollback(cp);
+    let (v, cnt) = st.cnt(|st| st.force(&o));
+    assert_eq!((v, cnt.eval), (101, 0));
+}
+
+#[test]
+fn rollback_to_an_undone_checkpoint_is_an_error() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let cp1 = st.checkpoint();
+    st.set(c.clone(), 2);
+    let cp2 = st.checkpoint();
+    st.set(c, 3);
+    st.rollback(cp1);
+    match st.try_rollback(cp2) {
+        Err(AdaptonError::StaleCheckpoint) => (),
+        r => panic!("expected a stale checkpoint, not {:?}", r),
+    }
+}
@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+fn double  (_st:&mut Engine, x:u64) -> u64 { x * 2 }
+
+#[test]
+fn cnt_attributes_work_to_functions() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let o  = thunk!(&mut st, observe, t:a);
+    let d  = thunk!(&mut st, double, x:3);
+    let (_, cnt) = st.cnt(|st| { st.force(&o) ; st.force(&d) });
+    assert_eq!(cnt.eval, 2);
+    assert_eq!(cnt.by_prog_pt["observe"].eval, 1);
+    // After an edit, only `observe` re-executes:
+    st.set(c, 2);
+    let (_, cnt) = st.cnt(|st| { st.force(&o) ; st.force(&d) });
+    assert_eq!(cnt.eval, 1);
+    assert_eq!(cnt.by_prog_pt["observe"].eval, 1);
+    assert_eq!(cnt.by_prog_pt["double"].eval, 0);
+    assert_eq!(cnt.by_prog_pt["double"].hit, 1);
+    assert_eq!((cnt.table_size, cnt.stack_max), (3, 2));
+}
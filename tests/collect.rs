@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn add1 (st:&mut Engine, a:Art<u64,Loc>) -> u64 { st.force(&a) + 1 }
+
+#[test]
+fn collect_frees_unreachable_nodes() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("x".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let t  = thunk!(&mut st, add1, a:a.clone());
+    assert_eq!(st.force(&t), 2);
+    // The thunk is held by `t`, and the cell by `c`, `a` and the thunk.
+    assert_eq!(st.collect(), 0);
+    drop(t);
+    assert_eq!(st.collect(), 1);
+    drop(a);
+    drop(c);
+    let (freed, cnt) = st.cnt(|st| st.collect());
+    assert_eq!(freed, 1);
+    assert_eq!(cnt.collected, 1);
+}
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn selfish (st:&mut Engine, nm:Name) -> u64 {
+    let t = thunk!(st, nm.clone() =>> selfish, nm:nm);
+    st.force(&t) + 1
+}
+
+// Counts up to 5, by a cycle of two thunks.
+fn count_a (st:&mut Engine, u:()) -> u64 {
+    let nm = st.name_of_string("b".to_string());
+    let b  = thunk!(st, nm =>> count_b, u:u);
+    let v  = st.force_fix(&b, 0);
+    if v < 5 { v + 1 } else { v }
+}
+fn count_b (st:&mut Engine, u:()) -> u64 {
+    let nm = st.name_of_string("a".to_string());
+    let a  = thunk!(st, nm =>> count_a, u:u);
+    st.force_fix(&a, 0)
+}
+
+#[test]
+fn cyclic_force_is_an_error() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("t".to_string());
+    let t  = thunk!(&mut st, nm.clone() =>> selfish, nm:nm);
+    match st.try_force(&t) {
+        Err(AdaptonError::Cycle{path}) => assert_eq!(path.len(), 2),
+        r => panic!("expected a cycle error, not {:?}", r),
+    }
+}
+
+#[test]
+fn cyclic_force_fix_iterates_to_a_fixed_point() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("a".to_string());
+    let a  = thunk!(&mut st, nm =>> count_a, u:());
+    assert_eq!(st.force(&a), 5);
+    assert_eq!(st.force(&a), 5);
+}
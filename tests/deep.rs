@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn incr (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 1 }
+
+#[test]
+fn long_chains_dirty_and_repair_without_overflow() {
+    let n = 100000 ;
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 0 as u64);
+    let mut t = st.read_only(c.clone());
+    // Built one link at a time, so that no producer runs deep:
+    for _ in 0..n {
+        t = thunk!(&mut st, incr, t:t);
+        st.force(&t);
+    }
+    assert_eq!(st.force(&t), n);
+    // Dirtying and change propagation each traverse the whole chain:
+    st.set(c, 1);
+    let (r, cnt) = st.cnt(|st| st.force(&t));
+    assert_eq!((r, cnt.eval), (n + 1, n as usize));
+}
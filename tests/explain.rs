@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn plus1  (st:&mut Engine, a:Art<u64,Loc>) -> u64 { st.force(&a) + 1 }
+fn double (st:&mut Engine, a:Art<u64,Loc>) -> u64 { st.force(&a) * 2 }
+
+fn summary (e:&Explanation) -> Vec<(Option<&'static str>, Option<Effect>, String, String)> {
+    e.steps.iter().map(|step| (step.prog_pt.as_ref().map(|p| p.symbol), step.effect.clone(),
+                               step.old.clone(), step.new.clone())).collect()
+}
+
+#[test]
+fn explain_follows_changed_edges_back_to_the_set() {
+    let mut st = Engine::with_config(EngineConfig{ record_explanations:true, ..EngineConfig::default() });
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let r  = st.read_only(c.clone());
+    let a  = thunk!(&mut st, plus1, a:r);
+    let b  = thunk!(&mut st, double, a:a.clone());
+    let p  = st.put(7);
+    let d  = thunk!(&mut st, plus1, a:p);
+    assert_eq!(st.force(&b) + st.force(&d), 12);
+    assert_eq!(st.explain(&b).steps, vec![]);
+    st.set(c.clone(), 2);
+    assert_eq!(st.force(&b), 6);
+    assert_eq!(summary(&st.explain(&b)),
+               vec![(Some("double"), Some(Effect::Observe), "4".to_string(), "6".to_string()),
+                    (Some("plus1"),  Some(Effect::Observe), "2".to_string(), "3".to_string()),
+                    (None,           None,                  "1".to_string(), "2".to_string())]);
+    // Nothing changed for d:
+    assert_eq!(st.force(&d), 8);
+    assert_eq!(st.explain(&d).steps, vec![]);
+}
+
+#[test]
+fn explain_needs_recording() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let r  = st.read_only(c.clone());
+    let a  = thunk!(&mut st, plus1, a:r);
+    assert_eq!(st.force(&a), 2);
+    st.set(c, 2);
+    assert_eq!(st.force(&a), 3);
+    assert_eq!(st.explain(&a).steps, vec![]);
+}
@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+fn double  (_st:&mut Engine, x:u64) -> u64 { x * 2 }
+
+#[test]
+fn export_whole_and_reachable_graph() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c);
+    let o  = thunk!(&mut st, observe, t:a);
+    let d  = thunk!(&mut st, double, x:3);
+    st.force(&o);
+    st.force(&d);
+
+    let mut dot = Vec::new();
+    st.export_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph"));
+    assert!(dot.contains("observe") && dot.contains("double"));
+    assert!(dot.contains("Observe"));
+
+    let mut json = Vec::new();
+    st.export_json_from(&o, &mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains("\"kind\":\"Mut\""));
+    assert!(json.contains("\"effect\":\"Observe\",\"dirty\":false"));
+    assert!(!json.contains("double"));
+}
+
+#[test]
+fn dot_labels_keep_utf8() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("café \"au lait\"".to_string());
+    st.cell(nm, 1 as u64);
+    let mut dot = Vec::new();
+    st.export_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    // DOT has no \u escapes:
+    assert!(dot.contains("café") && !dot.contains("\\u"));
+}
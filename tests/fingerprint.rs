@@ -0,0 +1,47 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn plus (st:&mut Engine, a:Art<u64,Loc>, b:Art<u64,Loc>) -> u64 { st.force(&a) + st.force(&b) }
+
+#[test]
+fn derived_names_are_distinct() {
+    let mut st = Engine::with_config(EngineConfig{ check_collisions:true, ..EngineConfig::default() }) ;
+    let mut names = vec![] ;
+    for i in 0..100 {
+        let nm = st.name_of_usize(i) ;
+        let (l, r) = st.name_fork(nm.clone()) ;
+        let s = st.name_of_string(format!("{}", i)) ;
+        let p = st.name_pair(l.clone(), r.clone()) ;
+        names.push(nm) ; names.push(l) ; names.push(r) ; names.push(s) ; names.push(p) ;
+    } ;
+    for i in 0..names.len() {
+        for j in 0..i { assert!(names[i] != names[j]) }
+    }
+}
+
+#[test]
+fn checked_engine_reuses_nodes() {
+    let mut st = Engine::with_config(EngineConfig{ check_collisions:true, ..EngineConfig::default() }) ;
+    let mut cells = vec![] ;
+    for i in 0..10 {
+        let nm = st.name_of_usize(i) ;
+        cells.push(st.cell(nm, i as u64)) ;
+    } ;
+    let mut sum = st.read_only(cells[0].clone()) ;
+    for c in cells[1..].iter() {
+        let a = st.read_only(c.clone()) ;
+        sum = thunk!(&mut st, plus, a:sum, b:a) ;
+    } ;
+    assert_eq!(st.force(&sum), 45) ;
+    // Re-allocating the same cells finds the same nodes:
+    for i in 0..10 {
+        let nm = st.name_of_usize(i) ;
+        st.try_cell(nm, i as u64 + 1).unwrap() ;
+    } ;
+    assert_eq!(st.force(&sum), 55) ;
+}
@@ -0,0 +1,58 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+fn write   (st:&mut Engine, c:MutArt<u64,Loc>, v:u64) -> () { st.set_inner(c, v) }
+fn misuse  (st:&mut Engine, c:MutArt<u64,Loc>) -> bool {
+    match st.try_set(c, 0) { Err(AdaptonError::SetInInnerLayer{..}) => true, _ => false }
+}
+
+#[test]
+fn outer_layer_sets_cells() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let o  = thunk!(&mut st, observe, t:a);
+    assert_eq!(st.force(&o), 101);
+    st.set(c, 2);
+    assert_eq!(st.force(&o), 102);
+}
+
+#[test]
+fn set_is_rejected_within_a_thunk() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let t  = thunk!(&mut st, misuse, c:c.clone());
+    assert_eq!(st.force(&t), true);
+    match st.try_set_inner(c, 2) {
+        Err(AdaptonError::SetInnerInOuterLayer{..}) => (),
+        r => panic!("expected a layer error, not {:?}", r),
+    }
+}
+
+#[test]
+fn set_inner_is_redone_when_overwritten() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let w  = thunk!(&mut st, write, c:c.clone(), v:5);
+    let o  = thunk!(&mut st, observe, t:a);
+    assert_eq!(st.force(&o), 101);
+    // The write dirties the observer of the cell:
+    st.force(&w);
+    assert_eq!(st.force(&o), 105);
+    // Overwriting the cell dirties the writer, which re-writes on demand:
+    st.set(c, 7);
+    assert_eq!(st.force(&o), 107);
+    let (_, cnt) = st.cnt(|st| st.force(&w));
+    assert_eq!(cnt.eval, 1);
+    assert_eq!(st.force(&o), 105);
+}
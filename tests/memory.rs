@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+use adapton::naive::* ;
+
+fn double       (_st:&mut Engine, x:u64) -> u64 { x * 2 }
+fn double_naive (_st:&mut AdaptonFromScratch, x:u64) -> u64 { x * 2 }
+
+#[test]
+fn memory_report_grows_with_the_dcg() {
+    let mut st = Engine::new();
+    let empty = st.memory_report();
+    assert_eq!(empty.total(), 0);
+    let t = thunk!(&mut st, double, x:1);
+    st.force(&t);
+    let report = st.memory_report();
+    assert!(report.results > 0 && report.keys > 0);
+    assert_eq!(report.by_kind["Comp"], report.by_prog_pt["double"]);
+    let nm = st.name_of_string("c".to_string());
+    st.cell(nm, 1 as u64);
+    let report2 = st.memory_report();
+    assert!(report2.names > 0 && report2.total() > report.total());
+    assert!(report2.by_kind.contains_key("Mut"));
+}
+
+#[test]
+fn memory_report_covers_naive_store() {
+    let mut st = AdaptonFromScratch::new();
+    let t = thunk!(&mut st, double_naive, x:1);
+    st.force(&t);
+    let report = st.memory_report();
+    assert!(report.nodes > 0);
+    assert_eq!(report.by_kind["Comp"], report.by_prog_pt["double_naive"]);
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let report2 = st.memory_report();
+    assert!(report2.results > 0 && report2.by_kind.contains_key("Mut"));
+    st.set(c, 2);
+    assert_eq!(st.memory_report(), report2);
+}
@@ -0,0 +1,108 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn add1   (_st:&mut Engine, x:u64) -> u64 { x + 1 }
+fn double (_st:&mut Engine, x:u64) -> u64 { x * 2 }
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+
+#[test]
+fn name_reused_by_another_producer() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("t".to_string());
+    let t  = thunk!(&mut st, nm.clone() =>> add1, x:3);
+    let o  = thunk!(&mut st, observe, t:t.clone());
+    assert_eq!(st.force(&o), 104);
+    // Re-allocating the name replaces the producer, and dirties `o`:
+    let t2 = thunk!(&mut st, nm =>> double, x:3);
+    assert_eq!(t, t2);
+    assert_eq!(st.force(&o), 106);
+    assert_eq!(st.force(&t), 6);
+}
+
+#[test]
+fn name_switches_between_cell_and_thunk() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("n".to_string());
+    let c  = st.cell(nm.clone(), 3 as u64);
+    let a  = st.read_only(c);
+    let o  = thunk!(&mut st, observe, t:a.clone());
+    assert_eq!(st.force(&o), 103);
+    // The cell becomes a thunk; its observer is dirtied:
+    let t  = thunk!(&mut st, nm.clone() =>> double, x:5);
+    assert_eq!(t, a);
+    assert_eq!(st.force(&o), 110);
+    // ...and it becomes a cell again:
+    st.cell(nm, 7 as u64);
+    assert_eq!(st.force(&o), 107);
+}
+
+fn run (st:&mut Engine) -> Art<u64,Loc> {
+    let a = st.name_of_string("a".to_string()) ;
+    let b = st.name_of_usize(1) ;
+    let ab = st.name_pair(a, b) ;
+    let (l, r) = st.name_fork(ab.clone()) ;
+    st.ns(ab, |st| st.ns(l, |st| st.ns(r, |st| thunk!(st, add1, x:1))))
+}
+
+#[test]
+fn rebuilt_names_and_paths_find_the_same_nodes() {
+    let mut st = Engine::with_config(EngineConfig{ check_collisions:true, ..EngineConfig::default() });
+    let (t1, cnt) = st.cnt(|st| run(st));
+    assert!(cnt.intern > 0);
+    // Re-running finds every name symbol and path interned:
+    let (t2, cnt) = st.cnt(|st| run(st));
+    assert_eq!(cnt.intern, 0);
+    assert_eq!(t1, t2);
+    assert_eq!(st.force(&t2), 2);
+    // Once collected, the names and paths are rebuilt afresh:
+    drop(t1); drop(t2);
+    assert_eq!(st.collect(), 1);
+    let t3 = run(&mut st);
+    assert_eq!(st.force(&t3), 2);
+}
+
+#[test]
+fn collect_prunes_interned_names_and_paths() {
+    // (Checking for collisions keeps the names and paths it checked.)
+    let mut st = Engine::new();
+    let t = run(&mut st);
+    let size = st.interned_size();
+    assert!(size > 0);
+    drop(t);
+    assert_eq!(st.collect(), 1);
+    assert_eq!(st.interned_size(), 0);
+    let (_, cnt) = st.cnt(|st| run(st));
+    assert_eq!(cnt.intern, size);
+}
+
+// Two "libraries" allocate cells with the same local name, each in
+// its own namespace.
+fn two_libraries (st:&mut Engine, x:u64) -> (MutArt<u64,Loc>, MutArt<u64,Loc>) {
+    let a = st.name_of_string("a".to_string()) ;
+    let b = st.name_of_string("b".to_string()) ;
+    let x_a = st.name_of_string("x".to_string()) ;
+    let x_b = x_a.clone() ;
+    let c1 = st.ns(a, |st| st.cell(x_a, x)) ;
+    let c2 = st.ns(b, |st| st.cell(x_b, x + 1)) ;
+    (c1, c2)
+}
+
+#[test]
+fn ns_scopes_allocations_within_thunks() {
+    let mut st = Engine::new();
+    let t = thunk!(&mut st, two_libraries, x:1);
+    let (c1, c2) = st.force(&t);
+    assert!(c1 != c2);
+    let (a1, a2) = (st.read_only(c1), st.read_only(c2));
+    assert_eq!((st.force(&a1), st.force(&a2)), (1, 2));
+    let a = st.name_of_string("a".to_string());
+    let x = st.name_of_string("x".to_string());
+    assert_eq!(st.current_path(), vec![]);
+    let path = st.ns(a.clone(), |st| st.ns(x.clone(), |st| st.current_path()));
+    assert_eq!(path, vec![a, x]);
+}
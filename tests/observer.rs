@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cell::RefCell;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+
+#[derive(Debug)]
+struct Log { events:Rc<RefCell<Vec<String>>> }
+
+impl EngineObserver for Log {
+    fn produce_begin (&mut self, _loc:&Loc, prog_pt:&ProgPt) {
+        self.events.borrow_mut().push(format!("produce {}", prog_pt.symbol))
+    }
+    fn cache_hit (&mut self, _loc:&Loc, prog_pt:&ProgPt) {
+        self.events.borrow_mut().push(format!("hit {}", prog_pt.symbol))
+    }
+    fn edge_dirty (&mut self, _src:&Loc, src_prog_pt:Option<&ProgPt>, effect:&Effect, _tgt:&Loc) {
+        self.events.borrow_mut().push(format!("dirty {:?} {:?}", src_prog_pt.map(|p| p.symbol), effect))
+    }
+    fn cell_set (&mut self, _loc:&Loc, changed:bool) {
+        self.events.borrow_mut().push(format!("set {}", changed))
+    }
+}
+
+#[test]
+fn observer_receives_engine_events() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut st = Engine::new();
+    st.add_observer(Box::new(Log{ events:events.clone() }));
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let o  = thunk!(&mut st, observe, t:a);
+    st.force(&o);
+    st.force(&o);
+    st.set(c, 2);
+    st.force(&o);
+    assert_eq!(*events.borrow(),
+               vec!["produce observe".to_string(),
+                    "hit observe".to_string(),
+                    "set true".to_string(),
+                    "dirty Some(\"observe\") Observe".to_string(),
+                    // Not a hit: change propagation re-produces the result.
+                    "produce observe".to_string()]);
+}
@@ -0,0 +1,158 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+
+// Re-allocates the nodes of a saved DCG, when there is one.
+fn program (st:&mut Engine, c:u64, d:u64) -> (Art<u64,Loc>, Art<u64,Loc>) {
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, c);
+    let a  = st.read_only(c);
+    let nm = st.name_of_string("d".to_string());
+    let d  = st.cell(nm, d);
+    let b  = st.read_only(d);
+    (thunk!(st, observe, t:a), thunk!(st, observe, t:b))
+}
+
+#[test]
+fn reload_reuses_saved_results() {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    let path = env::temp_dir().join(format!("adapton-persist-{}.dcg", nanos));
+    let mut registry = Registry::new();
+    register_thunk!(registry, observe, t:Art<u64,Loc>);
+    registry.value::<u64>();
+    {
+        let mut st = Engine::new();
+        let (o, p) = program(&mut st, 1, 10);
+        assert_eq!((st.force(&o), st.force(&p)), (101, 110));
+        st.save(&path, &registry).unwrap();
+    }
+    let mut st = Engine::load(&path, &registry).unwrap();
+    fs::remove_file(&path).unwrap();
+    let ((o, p), cnt) = st.cnt(|st| program(st, 2, 10));
+    assert_eq!(cnt.create, 0);
+    // Only the observer of the edited cell re-executes:
+    let (r, cnt) = st.cnt(|st| (st.force(&o), st.force(&p)));
+    assert_eq!(r, (102, 110));
+    assert_eq!(cnt.eval, 1);
+    assert_eq!(cnt.by_prog_pt["observe"].eval, 1);
+}
+
+fn temp_path(tag:&str) -> std::path::PathBuf {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    env::temp_dir().join(format!("adapton-persist-{}-{}.dcg", tag, nanos))
+}
+
+fn read_bytes(path:&std::path::Path) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+// The offset of the first name whose symbol is the string s.
+fn name_offset(bytes:&[u8], s:&str) -> usize {
+    let mut pat = vec![1u8,0,0,0,0,0,0,0] ;
+    pat.extend(&[s.len() as u8,0,0,0,0,0,0,0]) ;
+    pat.extend(s.as_bytes()) ;
+    let sym = bytes.windows(pat.len()).position(|w| w == &pat[..]).unwrap() ;
+    sym - 16
+}
+
+#[test]
+fn load_rejects_a_location_saved_twice() {
+    let path = temp_path("twice");
+    let mut registry = Registry::new();
+    registry.value::<u64>();
+    {
+        let mut st = Engine::new();
+        let nm = st.name_of_string("c".to_string());
+        let _c = st.cell(nm, 1u64);
+        st.save(&path, &registry).unwrap();
+    }
+    // The magic (its length and 13 bytes), then the node count, then the one node:
+    let mut bytes = read_bytes(&path);
+    let node = bytes[29..].to_vec();
+    bytes[21] = 2;
+    bytes.extend(node);
+    File::create(&path).unwrap().write_all(&bytes).unwrap();
+    let res = Engine::load(&path, &registry);
+    fs::remove_file(&path).unwrap();
+    assert!(res.is_err());
+}
+
+#[test]
+fn load_with_config_checks_names_for_collisions() {
+    let path = temp_path("collide");
+    let mut registry = Registry::new();
+    register_thunk!(registry, observe, t:Art<u64,Loc>);
+    registry.value::<u64>();
+    {
+        let mut st = Engine::new();
+        let (o, p) = program(&mut st, 1, 10);
+        assert_eq!((st.force(&o), st.force(&p)), (101, 110));
+        st.save(&path, &registry).unwrap();
+    }
+    // Give the name "d" the fingerprint of "c", wherever it is saved:
+    let mut bytes = read_bytes(&path);
+    let (c, d) = (name_offset(&bytes, "c"), name_offset(&bytes, "d"));
+    let (hash_c, hash_d) = (bytes[c..c+16].to_vec(), bytes[d..d+16].to_vec());
+    while let Some(d) = bytes.windows(16).position(|w| w == &hash_d[..]) {
+        bytes[d..d+16].copy_from_slice(&hash_c);
+    }
+    File::create(&path).unwrap().write_all(&bytes).unwrap();
+    let unchecked = Engine::load(&path, &registry);
+    let checked = Engine::load_with_config(&path, &registry, EngineConfig{ check_collisions:true, ..EngineConfig::default() });
+    fs::remove_file(&path).unwrap();
+    assert!(unchecked.is_ok());
+    assert!(checked.is_err());
+}
+
+// Saves an engine with one cell, named s.
+fn save_cell(path:&std::path::Path, registry:&Registry, s:&str) {
+    let mut st = Engine::new();
+    let nm = st.name_of_string(s.to_string());
+    let _c = st.cell(nm, 1u64);
+    st.save(path, registry).unwrap();
+}
+
+#[test]
+fn try_name_of_string_returns_a_collision() {
+    let (path_c, path_d) = (temp_path("name-c"), temp_path("name-d"));
+    let mut registry = Registry::new();
+    registry.value::<u64>();
+    save_cell(&path_c, &registry, "c");
+    save_cell(&path_d, &registry, "d");
+    // Give the name "d" the fingerprint of "c", so that the loaded
+    // engine knows that fingerprint for another symbol:
+    let bytes_c = read_bytes(&path_c);
+    let mut bytes = read_bytes(&path_d);
+    let (c, d) = (name_offset(&bytes_c, "c"), name_offset(&bytes, "d"));
+    let (hash_c, hash_d) = (bytes_c[c..c+16].to_vec(), bytes[d..d+16].to_vec());
+    while let Some(d) = bytes.windows(16).position(|w| w == &hash_d[..]) {
+        bytes[d..d+16].copy_from_slice(&hash_c);
+    }
+    File::create(&path_d).unwrap().write_all(&bytes).unwrap();
+    let st = Engine::load_with_config(&path_d, &registry, EngineConfig{ check_collisions:true, ..EngineConfig::default() });
+    fs::remove_file(&path_c).unwrap();
+    fs::remove_file(&path_d).unwrap();
+    let mut st = st.unwrap();
+    match st.try_name_of_string("c".to_string()) {
+        Err(AdaptonError::HashCollision{..}) => (),
+        res => panic!("expected a collision, got {:?}", res),
+    } ;
+    // The engine is still usable:
+    let nm = st.try_name_of_string("e".to_string()).unwrap();
+    let x = st.try_ns(nm, |st| { let nm = st.name_of_usize(1); st.cell(nm, 2u64) }).unwrap();
+    let x = st.read_only(x);
+    assert_eq!(st.force(&x), 2);
+}
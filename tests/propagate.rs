@@ -0,0 +1,54 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn parity  (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) % 2 }
+fn observe (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + 100 }
+fn twice   (st:&mut Engine, t:Art<u64,Loc>) -> u64 { st.force(&t) + st.force(&t) }
+
+#[test]
+fn propagate_revalidates_roots_eagerly() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let p  = thunk!(&mut st, parity, t:a);
+    let o  = thunk!(&mut st, observe, t:p);
+    assert_eq!(st.force(&o), 101);
+    st.add_root(&o);
+    // Only `parity` re-executes; its result is unchanged:
+    st.set(c, 3);
+    let cnt = st.propagate();
+    assert_eq!(cnt.eval, 1);
+    assert_eq!(cnt.by_prog_pt["parity"].eval, 1);
+    // Later forces are cache hits, which re-validate nothing below `o`:
+    let (v, cnt) = st.cnt(|st| st.force(&o));
+    assert_eq!(v, 101);
+    assert_eq!((cnt.eval, cnt.change_prop, cnt.hit), (0, 1, 1));
+    // Without changes, propagation does no work:
+    let cnt = st.propagate();
+    assert_eq!((cnt.eval, cnt.change_prop), (0, 0));
+}
+
+#[test]
+fn edges_to_the_same_thunk_are_validated_one_by_one() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("c".to_string());
+    let c  = st.cell(nm, 1 as u64);
+    let a  = st.read_only(c.clone());
+    let p  = thunk!(&mut st, parity, t:a);
+    let t  = thunk!(&mut st, twice, t:p);
+    assert_eq!(st.force(&t), 2);
+    st.set(c.clone(), 3);
+    let (v, cnt) = st.cnt(|st| st.force(&t));
+    assert_eq!((v, cnt.eval), (2, 1));
+    // Validation cleaned the dirty edges from `twice`:
+    let (v, cnt) = st.cnt(|st| st.force(&t));
+    assert_eq!((v, cnt.eval, cnt.change_prop), (2, 0, 1));
+    st.set(c, 4);
+    assert_eq!(st.force(&t), 0);
+}
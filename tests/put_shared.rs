@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn range (st:&mut Engine, n:u64) -> Art<Vec<u64>,Loc> { st.put_shared((0..n).collect()) }
+
+fn total (st:&mut Engine, a:Art<Vec<u64>,Loc>) -> u64 {
+    let mut sum = 0 ;
+    for x in st.force(&a).iter() { sum += *x } ;
+    sum
+}
+
+#[test]
+fn equal_values_share_one_node() {
+    let mut st = Engine::new();
+    let (a, cnt) = st.cnt(|st| st.put_shared(vec![0, 1, 2]));
+    assert_eq!(cnt.create, 1);
+    // The same value, put by a thunk, is the same articulation:
+    let t = thunk!(&mut st, range, n:3);
+    let (b, cnt) = st.cnt(|st| st.force(&t));
+    assert_eq!(cnt.create, 0);
+    assert_eq!(a, b);
+    assert!(a != st.put_shared(vec![0, 1]));
+    let s = thunk!(&mut st, total, a:b);
+    assert_eq!(st.force(&s), 3);
+    // Pure nodes are collected with their last handles:
+    drop(t); drop(s);
+    assert_eq!(st.collect(), 3); // Two thunks, and vec![0, 1].
+    drop(a);
+    assert_eq!(st.collect(), 1);
+}
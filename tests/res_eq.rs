@@ -0,0 +1,89 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use std::env;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+use adapton::naive::AdaptonFromScratch ;
+
+fn members<A:Adapton> (st:&mut A, t:Art<Vec<u64>,A::Loc>) -> Vec<u64> { st.force(&t) }
+
+fn total<A:Adapton> (st:&mut A, t:Art<Vec<u64>,A::Loc>) -> u64 {
+    let mut sum = 0 ;
+    for x in st.force(&t).iter() { sum += *x } ;
+    sum
+}
+
+// Compares results as sets.
+fn same_members (v1:&Vec<u64>, v2:&Vec<u64>) -> bool {
+    let mut v1 = v1.clone() ; v1.sort() ;
+    let mut v2 = v2.clone() ; v2.sort() ;
+    v1 == v2
+}
+
+fn program<A:Adapton> (st:&mut A) -> (MutArt<Vec<u64>,A::Loc>, Art<u64,A::Loc>) {
+    let nm = st.name_of_string("c".to_string()) ;
+    let c  = st.cell(nm, vec![1, 2]) ;
+    let a  = st.read_only(c.clone()) ;
+    let m  = thunk!(st, members::<A>, t:a ; eq = ResEq::Comparator(same_members)) ;
+    let t  = thunk!(st, total::<A>, t:m) ;
+    (c, t)
+}
+
+#[test]
+fn res_eq_cuts_off_observers() {
+    let mut st = Engine::new() ;
+    let (c, t) = program(&mut st) ;
+    assert_eq!(st.force(&t), 3) ;
+    // Equal as sets: only `members` re-executes.
+    st.set(c.clone(), vec![2, 1]) ;
+    let (v, cnt) = st.cnt(|st| st.force(&t)) ;
+    assert_eq!((v, cnt.eval), (3, 1)) ;
+    st.set(c, vec![2, 1, 4]) ;
+    let (v, cnt) = st.cnt(|st| st.force(&t)) ;
+    assert_eq!((v, cnt.eval), (7, 2)) ;
+}
+
+#[test]
+fn res_eq_is_ignored_from_scratch() {
+    let mut st = AdaptonFromScratch::new() ;
+    let (c, t) = program(&mut st) ;
+    st.set(c, vec![2, 1, 4]) ;
+    assert_eq!(st.force(&t), 7) ;
+}
+
+fn members_of (st:&mut Engine, t:Art<Vec<u64>,Loc>) -> Vec<u64> { st.force(&t) }
+fn total_of   (st:&mut Engine, t:Art<Vec<u64>,Loc>) -> u64 { total(st, t) }
+
+fn saved_program (st:&mut Engine, v:Vec<u64>) -> Art<u64,Loc> {
+    let nm = st.name_of_string("c".to_string()) ;
+    let c  = st.cell(nm, v) ;
+    let a  = st.read_only(c) ;
+    let m  = thunk!(st, members_of, t:a ; eq = ResEq::Comparator(same_members)) ;
+    thunk!(st, total_of, t:m)
+}
+
+#[test]
+fn res_eq_survives_reloading() {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos() ;
+    let path = env::temp_dir().join(format!("adapton-res-eq-{}.dcg", nanos)) ;
+    let mut registry = Registry::new() ;
+    register_thunk!(registry, members_of, t:Art<Vec<u64>,Loc>) ;
+    register_thunk!(registry, total_of, t:Art<Vec<u64>,Loc>) ;
+    {
+        let mut st = Engine::new() ;
+        let t = saved_program(&mut st, vec![1, 2]) ;
+        assert_eq!(st.force(&t), 3) ;
+        st.save(&path, &registry).unwrap() ;
+    }
+    let mut st = Engine::load(&path, &registry).unwrap() ;
+    fs::remove_file(&path).unwrap() ;
+    // Re-allocation restores the comparator; only `members_of` re-executes:
+    let t = saved_program(&mut st, vec![2, 1]) ;
+    let (v, cnt) = st.cnt(|st| st.force(&t)) ;
+    assert_eq!((v, cnt.eval), (3, 1)) ;
+}
@@ -0,0 +1,341 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::{Arc,Barrier};
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::thread;
+use std::time::{Duration,Instant};
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::naive::AdaptonFromScratch ;
+use adapton::sync_engine::* ;
+
+fn add<A:Adapton> (st:&mut A, l:Art<u64,A::Loc>, r:Art<u64,A::Loc>) -> u64 { st.force(&l) + st.force(&r) }
+
+// A balanced tree of sums over the cells.
+fn tree<A:Adapton> (st:&mut A, leaves:&[Art<u64,A::Loc>]) -> Art<u64,A::Loc> {
+    if leaves.len() == 1 { return leaves[0].clone() } ;
+    let (ls, rs) = leaves.split_at(leaves.len() / 2) ;
+    let l = tree(st, ls) ;
+    let r = tree(st, rs) ;
+    thunk!(st, add::<A>, l:l, r:r)
+}
+
+fn inputs<A:Adapton> (st:&mut A, n:usize) -> (Vec<MutArt<u64,A::Loc>>, Art<u64,A::Loc>) {
+    let mut cells = Vec::new() ;
+    for i in 0..n {
+        let nm = st.name_of_usize(i) ;
+        cells.push(st.cell(nm, i as u64))
+    } ;
+    let leaves : Vec<Art<u64,A::Loc>> = cells.iter().map(|c| st.read_only(c.clone())).collect() ;
+    let t = tree(st, &leaves) ;
+    (cells, t)
+}
+
+#[test]
+fn concurrent_forces_agree_with_naive() {
+    let mut st = SyncEngine::new() ;
+    let (cells, t) = inputs(&mut st, 64) ;
+    let mut n_st = AdaptonFromScratch::new() ;
+    let (n_cells, n_t) = inputs(&mut n_st, 64) ;
+    for round in 0..5 {
+        for i in 0..8 {
+            let j = (round * 13 + i * 7) % 64 ;
+            let v = (round * 100 + i) as u64 ;
+            st.set(cells[j].clone(), v) ;
+            n_st.set(n_cells[j].clone(), v) ;
+        } ;
+        let expected = n_st.force(&n_t) ;
+        let workers : Vec<thread::JoinHandle<u64>> = (0..4).map(|_| {
+            let mut st = st.clone() ;
+            let t = t.clone() ;
+            thread::spawn(move || st.force(&t))
+        }).collect() ;
+        for worker in workers { assert_eq!(worker.join().unwrap(), expected) } ;
+        assert_eq!(st.force(&t), expected) ;
+    }
+}
+
+#[test]
+fn sets_are_serialized_against_forces() {
+    let mut st = SyncEngine::new() ;
+    let (cells, t) = inputs(&mut st, 16) ;
+    st.set(cells[1].clone(), 1001) ;
+    let total = st.force(&t) ;
+    let readers : Vec<thread::JoinHandle<()>> = (0..3).map(|_| {
+        let mut st = st.clone() ;
+        let t = t.clone() ;
+        thread::spawn(move || {
+            for _ in 0..200 {
+                // Each force sees all of a set, or none of it:
+                let sum = st.force(&t) ;
+                assert!(sum == total || sum == total + 1, "torn sum {}", sum)
+            }
+        })
+    }).collect() ;
+    let writer = {
+        let mut st = st.clone() ;
+        let cells = cells.clone() ;
+        thread::spawn(move || {
+            // The sum alternates between total + 1 and total:
+            for i in 1..201 {
+                st.set(cells[0].clone(), i) ;
+                st.set(cells[1].clone(), 1001 - i) ;
+            }
+        })
+    } ;
+    writer.join().unwrap() ;
+    for reader in readers { reader.join().unwrap() } ;
+    let mut n_st = AdaptonFromScratch::new() ;
+    let (n_cells, n_t) = inputs(&mut n_st, 16) ;
+    n_st.set(n_cells[0].clone(), 200) ;
+    n_st.set(n_cells[1].clone(), 801) ;
+    assert_eq!(st.force(&t), n_st.force(&n_t)) ;
+}
+
+fn slow (st:&mut SyncEngine, c:Art<u64,Loc>) -> u64 {
+    thread::sleep(Duration::from_millis(20)) ;
+    st.force(&c) * 2
+}
+
+#[test]
+fn a_thunk_demanded_by_many_threads_runs_once() {
+    let mut st = SyncEngine::new() ;
+    let nm = st.name_of_string("c".to_string()) ;
+    let c  = st.cell(nm, 21 as u64) ;
+    let c  = st.read_only(c) ;
+    let t  = thunk!(&mut st, slow, c:c) ;
+    let barrier = Arc::new(Barrier::new(8)) ;
+    let workers : Vec<thread::JoinHandle<(u64, usize)>> = (0..8).map(|_| {
+        let mut st = st.clone() ;
+        let t = t.clone() ;
+        let barrier = barrier.clone() ;
+        thread::spawn(move || {
+            barrier.wait() ;
+            let (v, cnt) = st.cnt(|st| st.force(&t)) ;
+            (v, cnt.eval)
+        })
+    }).collect() ;
+    let mut evals = 0 ;
+    for worker in workers {
+        let (v, eval) = worker.join().unwrap() ;
+        assert_eq!(v, 42) ;
+        evals += eval
+    } ;
+    assert_eq!(evals, 1) ;
+}
+
+fn selfish (st:&mut SyncEngine, nm:Name) -> u64 {
+    let t = thunk!(st, nm.clone() =>> selfish, nm:nm) ;
+    st.force(&t) + 1
+}
+
+#[test]
+fn cyclic_force_is_an_error() {
+    let mut st = SyncEngine::new() ;
+    let nm = st.name_of_string("t".to_string()) ;
+    let t  = thunk!(&mut st, nm.clone() =>> selfish, nm:nm) ;
+    match st.try_force(&t) {
+        Err(AdaptonError::Cycle{path}) => assert_eq!(path.len(), 2),
+        r => panic!("expected a cycle error, not {:?}", r),
+    }
+}
+
+// Thunks a and b force each other, after a pause; threads that force
+// one each wait for each other, until one finds the cycle.
+fn ping (st:&mut SyncEngine, other:Name, me:Name) -> u64 {
+    thread::sleep(Duration::from_millis(20)) ;
+    let t = thunk!(st, other.clone() =>> ping, other:me, me:other) ;
+    st.force(&t) + 1
+}
+
+#[test]
+fn cyclic_forces_among_threads_are_errors() {
+    let mut st = SyncEngine::new() ;
+    let a_nm = st.name_of_string("a".to_string()) ;
+    let b_nm = st.name_of_string("b".to_string()) ;
+    let a = thunk!(&mut st, a_nm.clone() =>> ping, other:b_nm.clone(), me:a_nm.clone()) ;
+    let b = thunk!(&mut st, b_nm.clone() =>> ping, other:a_nm, me:b_nm) ;
+    let workers : Vec<thread::JoinHandle<bool>> = vec![a, b].into_iter().map(|t| {
+        let mut st = st.clone() ;
+        thread::spawn(move || match st.try_force(&t) { Err(AdaptonError::Cycle{..}) => true, _ => false })
+    }).collect() ;
+    for worker in workers { assert!(worker.join().unwrap()) }
+}
+
+#[test]
+fn parallel_repair_agrees_with_naive_and_sequential() {
+    let mut par = SyncEngine::with_config(SyncEngineConfig{ threads:4, ..SyncEngineConfig::default() }) ;
+    let (p_cells, p_t) = inputs(&mut par, 256) ;
+    let mut seq = SyncEngine::new() ;
+    let (s_cells, s_t) = inputs(&mut seq, 256) ;
+    let mut n_st = AdaptonFromScratch::new() ;
+    let (n_cells, n_t) = inputs(&mut n_st, 256) ;
+    assert_eq!(par.force(&p_t), n_st.force(&n_t)) ;
+    assert_eq!(seq.force(&s_t), n_st.force(&n_t)) ;
+    for round in 0..8 {
+        // Dirty leaves all over the tree:
+        for i in 0..32 {
+            let j = (round * 37 + i * 8) % 256 ;
+            let v = (round * 1000 + i) as u64 ;
+            par.set(p_cells[j].clone(), v) ;
+            seq.set(s_cells[j].clone(), v) ;
+            n_st.set(n_cells[j].clone(), v) ;
+        } ;
+        let (p_sum, p_cnt) = par.cnt(|st| st.force(&p_t)) ;
+        let (s_sum, s_cnt) = seq.cnt(|st| st.force(&s_t)) ;
+        assert_eq!(p_sum, n_st.force(&n_t)) ;
+        assert_eq!(s_sum, p_sum) ;
+        // Sums demand all their succs, so no validation is speculative:
+        assert_eq!(p_cnt.eval, s_cnt.eval) ;
+    }
+}
+
+// Counts the producers that arrive while c is odd, and waits, for up
+// to a second, until two arrive, as only producers that run at once can.
+fn meet (st:&mut SyncEngine, c:Art<u64,Loc>, arrived:Arc<AtomicUsize>, met:Arc<AtomicUsize>) -> u64 {
+    let v = st.force(&c) ;
+    if v % 2 == 1 {
+        arrived.fetch_add(1, Ordering::SeqCst) ;
+        let start = Instant::now() ;
+        while arrived.load(Ordering::SeqCst) < 2 && start.elapsed() < Duration::from_secs(1) { thread::yield_now() } ;
+        if arrived.load(Ordering::SeqCst) >= 2 { met.fetch_add(1, Ordering::SeqCst) ; }
+    } ;
+    v
+}
+
+fn meeting (st:&mut SyncEngine, c:Art<u64,Loc>, arrived:Arc<AtomicUsize>, met:Arc<AtomicUsize>) -> Art<u64,Loc> {
+    st.thunk(ArtIdChoice::Structural, prog_pt!("meet"),
+             Arc::new(Box::new(|st, c, (arrived, met)| meet(st, c, arrived, met))),
+             c, (arrived, met))
+}
+
+#[test]
+fn independent_succs_are_repaired_concurrently() {
+    let mut st = SyncEngine::with_config(SyncEngineConfig{ threads:2, ..SyncEngineConfig::default() }) ;
+    let arrived = Arc::new(AtomicUsize::new(0)) ;
+    let met = Arc::new(AtomicUsize::new(0)) ;
+    let (cells, _) = inputs(&mut st, 2) ;
+    let mut leaves = Vec::new() ;
+    for c in cells.iter() {
+        let c = st.read_only(c.clone()) ;
+        leaves.push(meeting(&mut st, c, arrived.clone(), met.clone()))
+    } ;
+    let t = tree(&mut st, &leaves) ;
+    st.set(cells[1].clone(), 0) ;
+    assert_eq!(st.force(&t), 0) ;
+    st.set(cells[0].clone(), 3) ;
+    st.set(cells[1].clone(), 5) ;
+    assert_eq!(st.force(&t), 8) ;
+    assert_eq!(met.load(Ordering::SeqCst), 2) ;
+}
+
+fn double (st:&mut SyncEngine, c:Art<u64,Loc>) -> u64 { st.force(&c) * 2 }
+
+// Forces the thunk named x, which forces this one, once c is set.
+fn back (st:&mut SyncEngine, cb:Art<u64,Loc>, ca:Art<u64,Loc>, x:Name) -> u64 {
+    if st.force(&cb) == 0 { return 0 } ;
+    let t = thunk!(st, x.clone() =>> both, ca:ca, cb:cb, x:x) ;
+    st.force(&t)
+}
+
+fn both (st:&mut SyncEngine, ca:Art<u64,Loc>, cb:Art<u64,Loc>, x:Name) -> u64 {
+    let a = thunk!(st, double, c:ca.clone()) ;
+    let b = thunk!(st, back, cb:cb, ca:ca, x:x) ;
+    st.force(&a) + st.force(&b)
+}
+
+#[test]
+fn a_cycle_through_helpers_is_an_error() {
+    let mut st = SyncEngine::with_config(SyncEngineConfig{ threads:2, ..SyncEngineConfig::default() }) ;
+    let (cells, _) = inputs(&mut st, 2) ;
+    let ca = st.read_only(cells[0].clone()) ;
+    let cb = st.read_only(cells[1].clone()) ;
+    let x_nm = st.name_of_string("x".to_string()) ;
+    let x = thunk!(&mut st, x_nm.clone() =>> both, ca:ca, cb:cb, x:x_nm) ;
+    st.set(cells[1].clone(), 0) ;
+    assert_eq!(st.force(&x), 0) ;
+    st.set(cells[0].clone(), 1) ;
+    st.set(cells[1].clone(), 1) ;
+    match st.try_force(&x) {
+        Err(AdaptonError::Cycle{..}) => (),
+        r => panic!("expected a cycle error, not {:?}", r),
+    }
+}
+
+fn table_size (st:&mut SyncEngine) -> usize { st.cnt(|_| ()).1.table_size }
+
+#[test]
+fn collect_frees_unreachable_nodes() {
+    let mut st = SyncEngine::new() ;
+    let nm = st.name_of_string("x".to_string()) ;
+    let c  = st.cell(nm, 1 as u64) ;
+    let a  = st.read_only(c.clone()) ;
+    let t  = thunk!(&mut st, double, c:a.clone()) ;
+    assert_eq!(st.force(&t), 2) ;
+    // The thunk is held by `t`, and the cell by `c`, `a` and the thunk.
+    assert_eq!(st.collect(), 0) ;
+    drop(t) ;
+    assert_eq!(st.collect(), 1) ;
+    drop(a) ;
+    drop(c) ;
+    let (freed, cnt) = st.cnt(|st| st.collect()) ;
+    assert_eq!(freed, 1) ;
+    assert_eq!(cnt.collected, 1) ;
+    assert_eq!(table_size(&mut st), 0) ;
+}
+
+#[test]
+fn bounded_cache_evicts_and_reproduces() {
+    let mut st = SyncEngine::with_config(SyncEngineConfig{ cache_limit:Some(1), ..SyncEngineConfig::default() }) ;
+    let (cells, _) = inputs(&mut st, 2) ;
+    let c1 = st.read_only(cells[0].clone()) ;
+    let c2 = st.read_only(cells[1].clone()) ;
+    let t1 = thunk!(&mut st, double, c:c1) ;
+    let t2 = thunk!(&mut st, double, c:c2) ;
+    let (_, cnt) = st.cnt(|st| { st.force(&t1) ; st.force(&t2) }) ;
+    assert_eq!(cnt.eval, 2) ;
+    // Forcing t2 evicted the result of t1:
+    let (r, cnt) = st.cnt(|st| st.force(&t1)) ;
+    assert_eq!((r, cnt.eval), (0, 1)) ;
+    let (r, cnt) = st.cnt(|st| st.force(&t1)) ;
+    assert_eq!((r, cnt.eval), (0, 0)) ;
+}
+
+fn offset (st:&mut SyncEngine, t:Art<u64,Loc>, k:usize) -> u64 { st.force(&t) + k as u64 }
+
+// A server: each request allocates a thunk of its own, forces it, and
+// drops it, while the inputs change between rounds.
+#[test]
+fn a_long_running_engine_stays_bounded() {
+    let mut st = SyncEngine::with_config(SyncEngineConfig{ threads:2, cache_limit:Some(64) }) ;
+    let (cells, t) = inputs(&mut st, 16) ;
+    let mut n_st = AdaptonFromScratch::new() ;
+    let (n_cells, n_t) = inputs(&mut n_st, 16) ;
+    st.force(&t) ;
+    st.collect() ;
+    let retained = table_size(&mut st) ;
+    assert_eq!(retained, 31) ; // 16 cells, and 15 sums.
+    for round in 0..10 {
+        st.set(cells[round % 16].clone(), round as u64) ;
+        n_st.set(n_cells[round % 16].clone(), round as u64) ;
+        let expected = n_st.force(&n_t) ;
+        let workers : Vec<thread::JoinHandle<()>> = (0..4).map(|w| {
+            let mut st = st.clone() ;
+            let t = t.clone() ;
+            thread::spawn(move || {
+                for i in 0..50 {
+                    let k = (round * 4 + w) * 50 + i ;
+                    let nm = st.name_of_usize(k) ;
+                    let req = st.ns(nm, |st| thunk!(st, offset, t:t.clone(), k:k)) ;
+                    assert_eq!(st.force(&req), expected + k as u64)
+                }
+            })
+        }).collect() ;
+        // Collecting while requests run frees only what they dropped:
+        st.collect() ;
+        for worker in workers { worker.join().unwrap() } ;
+        st.collect() ;
+        assert_eq!(table_size(&mut st), retained) ;
+    }
+}
@@ -0,0 +1,62 @@
+#[macro_use]
+extern crate adapton ;
+
+use std::sync::Arc;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use adapton::macros::* ;
+use adapton::adapton_sigs::* ;
+use adapton::engine::* ;
+
+fn nonzero (st:&mut Engine, a:Art<u64,Loc>) -> u64 {
+    let x = st.force(&a) ;
+    if x == 0 { panic!("zero") } ;
+    x
+}
+
+#[test]
+#[should_panic(expected = "type error")]
+fn name_reused_at_another_type() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("x".to_string());
+    st.cell(nm.clone(), 1 as u64);
+    st.cell(nm, "one".to_string());
+}
+
+#[test]
+fn name_reused_at_another_type_is_reported() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("x".to_string());
+    let c  = st.cell(nm.clone(), 1 as u64);
+    match st.try_cell(nm, "one".to_string()) {
+        Err(AdaptonError::TypeMismatch{..}) => (),
+        r => panic!("expected a type error, not {:?}", r),
+    } ;
+    // The engine keeps running:
+    let a = st.read_only(c);
+    assert_eq!(st.try_force(&a), Ok(1));
+}
+
+#[test]
+fn producer_panics_are_resumed_and_abort_the_producer() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("x".to_string());
+    let c  = st.cell(nm, 0 as u64);
+    let a  = st.read_only(c.clone());
+    let t  = thunk!(&mut st, nonzero, a:a);
+    assert!(catch_unwind(AssertUnwindSafe(|| st.try_force(&t))).is_err());
+    // The thunk is not left running (which would report a cycle):
+    st.set(c, 1);
+    assert_eq!(st.try_force(&t), Ok(1));
+}
+
+#[test]
+fn errors_caught_elsewhere_are_not_reported_again() {
+    let mut st = Engine::new();
+    let nm = st.name_of_string("x".to_string());
+    st.cell(nm.clone(), 1 as u64);
+    assert!(catch_unwind(AssertUnwindSafe(|| st.cell(nm.clone(), "one".to_string()))).is_err());
+    // The type error above is not mistaken for the cause of this panic:
+    let p = st.put(0 as u64);
+    let t = thunk!(&mut st, nonzero, a:p);
+    assert!(catch_unwind(AssertUnwindSafe(|| st.try_force(&t))).is_err());
+}